@@ -0,0 +1,34 @@
+/// The HorizontalAlignment determines where the lines of a TextModel will be placed within the region that is passed to
+/// the render_in_region method of TextModel.
+#[derive(PartialEq,Eq,Copy,Clone,Debug)]
+pub enum HorizontalAlignment {
+
+    /// Every line will start at the left side of the region.
+    Left,
+
+    /// Every line will be centered horizontally within the region.
+    Center,
+
+    /// Every line will end at the right side of the region.
+    Right,
+
+    /// Every line will be stretched to the full width of the region by making its whitespace wider. The last line of every
+    /// paragraph (and lines without any whitespace) will be left-aligned instead. This is mostly useful for TextModel's that
+    /// were created with create_wrapped_text_model.
+    Justify
+}
+
+/// The VerticalAlignment determines where the block of text of a TextModel will be placed within the region that is passed
+/// to the render_in_region method of TextModel.
+#[derive(PartialEq,Eq,Copy,Clone,Debug)]
+pub enum VerticalAlignment {
+
+    /// The first line of the text will be at the top of the region.
+    Top,
+
+    /// The text will be centered vertically within the region.
+    Center,
+
+    /// The last line of the text will be at the bottom of the region.
+    Bottom
+}
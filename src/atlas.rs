@@ -0,0 +1,199 @@
+use super::packer::SkylinePacker;
+use super::rasterizer::*;
+
+/// The CPU copy of the backing texture of a Font. The characters are placed with a SkylinePacker, with some padding
+/// between them so that linear filtering won't mix the pixels of neighbouring characters. New characters can be added
+/// at any time, in which case the atlas will grow if it is full (but never beyond its max_size).
+pub(super) struct GlyphAtlas {
+
+    width: u32,
+    height: u32,
+
+    padding: u32,
+    power_of_two: bool,
+    max_size: u32,
+
+    /// The value of the pixels that are not covered by any character
+    empty_pixel: [u8; 4],
+
+    packer: SkylinePacker,
+    pixels: Vec<u8>,
+
+    /// True if the pixels have changed since the last time they were uploaded to the backing texture
+    dirty: bool
+}
+
+impl GlyphAtlas {
+
+    /// Creates an empty atlas with (roughly) the given initial size. If power_of_two is true, the width and height of the
+    /// atlas will always be powers of 2 (so max_size will be rounded down to a power of 2). The width and height will never
+    /// exceed max_size. All pixels will initially have the value empty_pixel, see the get_empty_pixel method of
+    /// GlyphRasterizer.
+    pub fn new(width: u32, height: u32, padding: u32, power_of_two: bool, max_size: u32, empty_pixel: [u8; 4]) -> GlyphAtlas {
+
+        // The atlas grows by doubling, so it will stay a power of 2 as long as its maximum size is a power of 2 as well
+        let max_size = match power_of_two {
+            true => 1 << (u32::BITS - 1 - max_size.max(1).leading_zeros()),
+            false => max_size
+        };
+        let round = |size: u32| {
+            let size = size.max(1);
+            if power_of_two { size.next_power_of_two().min(max_size) } else { size.min(max_size) }
+        };
+        let width = round(width);
+        let height = round(height);
+
+        GlyphAtlas {
+            width,
+            height,
+
+            padding,
+            power_of_two,
+            max_size,
+
+            empty_pixel,
+
+            packer: SkylinePacker::new(width, height),
+            pixels: create_empty_pixels(width, height, empty_pixel),
+
+            dirty: true
+        }
+    }
+
+    /// Copies the given bitmap into free space of this atlas and returns the position (min_x, min_y) of its top-left pixel.
+    /// If there is no room for the bitmap, the atlas will grow. Returns None if the atlas can't grow any further.
+    pub fn add(&mut self, bitmap: &GlyphBitmap) -> Option<(u32, u32)> {
+        let padded_width = bitmap.width + self.padding;
+        let padded_height = bitmap.height + self.padding;
+
+        let (min_x, min_y) = loop {
+            if let Some(position) = self.packer.pack(padded_width.max(1), padded_height.max(1)) {
+                break position;
+            }
+
+            // Grow the smallest dimension first to keep the atlas roughly square
+            let grow_width = self.width < self.max_size && (self.width <= self.height || self.height >= self.max_size);
+            if grow_width {
+                self.resize((2 * self.width).min(self.max_size), self.height);
+            } else if self.height < self.max_size {
+                self.resize(self.width, (2 * self.height).min(self.max_size));
+            } else {
+                return None;
+            }
+        };
+
+        bitmap.copy_to(&mut self.pixels, self.width, min_x, min_y);
+        self.dirty = true;
+
+        Some((min_x, min_y))
+    }
+
+    /// Reduces the height of this atlas to the height that is actually used by its characters. This is useful after the
+    /// initial characters have been added, since there is no way to predict how much space they will need.
+    pub fn shrink_to_fit(&mut self){
+        let mut used_height = self.packer.get_used_height().max(1);
+        if self.power_of_two {
+            used_height = used_height.next_power_of_two();
+        }
+        if used_height < self.height {
+            self.resize(self.width, used_height);
+        }
+    }
+
+    /// Changes the size of this atlas, without moving the characters that were already added
+    fn resize(&mut self, new_width: u32, new_height: u32){
+        let mut new_pixels = create_empty_pixels(new_width, new_height, self.empty_pixel);
+
+        let row_length = (4 * self.width.min(new_width)) as usize;
+        for y in 0..self.height.min(new_height) {
+            let source_index = (4 * y * self.width) as usize;
+            let dest_index = (4 * y * new_width) as usize;
+            new_pixels[dest_index..dest_index + row_length].copy_from_slice(&self.pixels[source_index..source_index + row_length]);
+        }
+
+        self.packer.resize(new_width, new_height);
+        self.width = new_width;
+        self.height = new_height;
+        self.pixels = new_pixels;
+        self.dirty = true;
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get_pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn set_uploaded(&mut self){
+        self.dirty = false;
+    }
+}
+
+/// Creates the pixels of an atlas where every pixel has the given value, which indicates empty space
+fn create_empty_pixels(width: u32, height: u32, empty_pixel: [u8; 4]) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity((4 * width * height) as usize);
+    for _ in 0..width * height {
+        pixels.extend_from_slice(&empty_pixel);
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use super::super::metrics::GlyphMetrics;
+
+    fn get_pixel(atlas: &GlyphAtlas, x: u32, y: u32) -> &[u8] {
+        let index = (4 * (x + y * atlas.get_width())) as usize;
+        &atlas.get_pixels()[index..index + 4]
+    }
+
+    #[test]
+    fn distance_field_atlas_is_cleared_to_outside(){
+        let mut atlas = GlyphAtlas::new(4, 4, 1, false, 64, EMPTY_DISTANCE_PIXEL);
+        assert!(atlas.get_pixels().chunks_exact(4).all(|pixel| pixel == EMPTY_DISTANCE_PIXEL));
+
+        // The space that is added when the atlas grows must be empty as well
+        let mut glyph = GlyphBitmap::empty(3, 3, 0, 0, GlyphMetrics::default());
+        glyph.pixels.fill(200);
+        for _ in 0..4 {
+            atlas.add(&glyph).expect("The glyphs should fit");
+        }
+        assert!(atlas.get_width() > 4 || atlas.get_height() > 4);
+        assert_eq!(get_pixel(&atlas, atlas.get_width() - 1, atlas.get_height() - 1), EMPTY_DISTANCE_PIXEL);
+        assert_eq!(get_pixel(&atlas, 0, 0), [200; 4]);
+    }
+
+    #[test]
+    fn power_of_two_atlas_stays_power_of_two(){
+        let is_power_of_two = |size: u32| size.is_power_of_two() && size <= 64;
+        let mut atlas = GlyphAtlas::new(10, 10, 1, true, 100, EMPTY_PIXEL);
+        assert_eq!((atlas.get_width(), atlas.get_height()), (16, 16));
+
+        let glyph = GlyphBitmap::empty(7, 5, 0, 0, GlyphMetrics::default());
+        while atlas.add(&glyph).is_some() {
+            assert!(is_power_of_two(atlas.get_width()) && is_power_of_two(atlas.get_height()));
+        }
+        assert_eq!((atlas.get_width(), atlas.get_height()), (64, 64));
+
+        let atlas = GlyphAtlas::new(500, 20, 1, true, 100, EMPTY_PIXEL);
+        assert_eq!((atlas.get_width(), atlas.get_height()), (64, 32));
+    }
+
+    #[test]
+    fn bitmap_atlas_is_cleared_to_red(){
+        let atlas = GlyphAtlas::new(4, 4, 1, false, 64, EMPTY_PIXEL);
+        assert!(atlas.get_pixels().chunks_exact(4).all(|pixel| pixel == EMPTY_PIXEL));
+    }
+}
@@ -0,0 +1,251 @@
+use super::error::TextError;
+
+/// A handle to a texture that was created by a TextBackend. Handles are only meaningful for the backend that created
+/// them. Backends are free to choose the values of their handles, as long as they are unique for every living texture.
+#[derive(PartialEq,Eq,Hash,Copy,Clone,Debug)]
+pub struct TextureHandle {
+
+    value: u32
+}
+
+impl TextureHandle {
+
+    pub const fn new(value: u32) -> TextureHandle {
+        TextureHandle {
+            value
+        }
+    }
+
+    pub fn get_value(&self) -> u32 {
+        self.value
+    }
+}
+
+/// A handle to a vertex buffer that was created by a TextBackend. See TextureHandle for more information about handles.
+#[derive(PartialEq,Eq,Hash,Copy,Clone,Debug)]
+pub struct BufferHandle {
+
+    value: u32
+}
+
+impl BufferHandle {
+
+    pub const fn new(value: u32) -> BufferHandle {
+        BufferHandle {
+            value
+        }
+    }
+
+    pub fn get_value(&self) -> u32 {
+        self.value
+    }
+}
+
+/// A handle to a shader program that was created by a TextBackend. See TextureHandle for more information about handles.
+#[derive(PartialEq,Eq,Hash,Copy,Clone,Debug)]
+pub struct ProgramHandle {
+
+    value: u32
+}
+
+impl ProgramHandle {
+
+    pub const fn new(value: u32) -> ProgramHandle {
+        ProgramHandle {
+            value
+        }
+    }
+
+    pub fn get_value(&self) -> u32 {
+        self.value
+    }
+}
+
+/// A handle to a uniform variable of a shader program of a TextBackend. See TextureHandle for more information about handles.
+#[derive(PartialEq,Eq,Hash,Copy,Clone,Debug)]
+pub struct UniformHandle {
+
+    value: u32
+}
+
+impl UniformHandle {
+
+    pub const fn new(value: u32) -> UniformHandle {
+        UniformHandle {
+            value
+        }
+    }
+
+    pub fn get_value(&self) -> u32 {
+        self.value
+    }
+}
+
+/// The type of the components of a vertex attribute in its buffer
+#[derive(PartialEq,Eq,Copy,Clone,Debug)]
+pub enum AttributeType {
+
+    /// Every component is a 32-bit float
+    Float,
+
+    /// Every component is an unsigned 16-bit integer, which will be mapped to the range [0.0, 1.0]
+    NormalizedUnsignedShort
+}
+
+/// A handle to a vertex array object that was created by a TextBackend. See TextureHandle for more information about
+/// handles. Vertex array objects are only used by backends that support instancing.
+#[derive(PartialEq,Eq,Hash,Copy,Clone,Debug)]
+pub struct VertexArrayHandle {
+
+    value: u32
+}
+
+impl VertexArrayHandle {
+
+    pub const fn new(value: u32) -> VertexArrayHandle {
+        VertexArrayHandle {
+            value
+        }
+    }
+
+    pub fn get_value(&self) -> u32 {
+        self.value
+    }
+}
+
+/// The usage hint of a vertex buffer, with the same meaning as the usage parameter of the bufferData function of WebGl.
+/// Buffers whose content changes often should use Dynamic.
+#[derive(PartialEq,Eq,Copy,Clone,Debug)]
+pub enum BufferUsage {
+
+    Static,
+    Dynamic
+}
+
+/// Converts the given floats to the bytes that the buffer methods of TextBackend expect. Every float takes 4 bytes in
+/// little-endian order, which is the byte order of WebAssembly (and thus of the buffers of WebGl).
+pub fn float_bytes(floats: &[f32]) -> Vec<u8> {
+    floats.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+/// The TextBackend is the abstraction of the graphics api that is used by the Font's and TextModel's of this crate. All
+/// texture uploads, buffer uploads, uniform changes and draw calls of this crate go through the TextBackend of the
+/// TextRenderer.
+///
+/// Normally, you won't have to deal with this trait because the from_gl, from_rc and from_canvas methods of TextRenderer
+/// will use a WebGlBackend. If you would like to run the text code without a browser (for instance in unit tests), you
+/// can create the TextRenderer with the from_backend method and supply a RecordingBackend (or your own implementation).
+///
+/// All methods take &self rather than &mut self because the backend is shared by the TextRenderer and all its Font's and
+/// TextModel's, so implementations will need interior mutability to keep track of their objects.
+///
+/// The methods that create objects return a TextError when they fail, so that the TextRenderer can report the failure
+/// to the application instead of panicking.
+pub trait TextBackend {
+
+    /// Creates a new (empty) texture and returns a handle to it.
+    fn create_texture(&self) -> Result<TextureHandle, TextError>;
+
+    /// Uploads the given pixels to the given texture. The pixels are stored row by row, with 4 bytes (red, green, blue and alpha)
+    /// per pixel, so the length of pixels will be 4 * width * height. The texture should use linear filtering and clamp to its
+    /// edges.
+    fn upload_texture(&self, texture: TextureHandle, width: u32, height: u32, pixels: &[u8]) -> Result<(), TextError>;
+
+    /// Binds the given texture to the given texture unit.
+    fn bind_texture(&self, texture_unit: u32, texture: TextureHandle);
+
+    fn delete_texture(&self, texture: TextureHandle);
+
+    /// Gets the maximum width and height (in pixels) of the textures of this backend. The Font's will make sure that their
+    /// backing textures are not larger than this.
+    fn get_max_texture_size(&self) -> u32;
+
+    /// Creates a new (empty) vertex buffer and returns a handle to it.
+    fn create_buffer(&self) -> Result<BufferHandle, TextError>;
+
+    /// Replaces the content of the given buffer with the given bytes. The buffer will use the Static usage hint. Floats
+    /// are stored in little-endian byte order (see float_bytes), like all other multi-byte values in the buffers.
+    fn upload_buffer(&self, buffer: BufferHandle, data: &[u8]);
+
+    /// Gives the given buffer room for byte_count bytes with the given usage hint. The previous content of the buffer
+    /// will be lost, and the new content is undefined until it is written with update_buffer.
+    fn allocate_buffer(&self, buffer: BufferHandle, byte_count: usize, usage: BufferUsage);
+
+    /// Replaces a part of the content of the given buffer with the given bytes, starting at offset (in bytes). The buffer
+    /// must be large enough to hold the data, so it should be allocated (or uploaded) first.
+    fn update_buffer(&self, buffer: BufferHandle, offset: usize, data: &[u8]);
+
+    fn delete_buffer(&self, buffer: BufferHandle);
+
+    /// Lets the vertex attribute at the given location read its values from the given buffer. Every vertex will read
+    /// num_components components of the given type. The stride and offset are expressed in bytes, like in the
+    /// vertexAttribPointer function of WebGl.
+    fn set_vertex_attribute(&self, buffer: BufferHandle, location: i32, num_components: i32, attribute_type: AttributeType, stride: i32, offset: i32);
+
+    /// Stops the vertex attribute at the given location from reading its values from a buffer.
+    fn disable_vertex_attribute(&self, location: i32);
+
+    /// Replaces the content of the given buffer with the given indices, so that it can be used as index buffer. The buffer
+    /// will also become the bound index buffer (see bind_index_buffer). A buffer that has been used as index buffer must
+    /// not be used as vertex buffer.
+    fn upload_index_buffer(&self, buffer: BufferHandle, indices: &[u16]);
+
+    /// Makes the given buffer the index buffer of the next calls to draw_indexed_triangles.
+    fn bind_index_buffer(&self, buffer: BufferHandle);
+
+    /// Draws count indices of the bound index buffer as triangles, starting at the index with index first. The vertices
+    /// will be read from the buffers that were passed to the set_vertex_attribute method.
+    fn draw_indexed_triangles(&self, first: i32, count: i32);
+
+    /// Checks whether this backend supports vertex array objects and instanced drawing. If this returns true, the text will
+    /// be drawn with 1 instance per character, which needs much less vertex memory. If this returns false, the methods
+    /// below that deal with vertex arrays and instancing will never be called.
+    fn supports_instancing(&self) -> bool;
+
+    /// Creates a new vertex array object and returns a handle to it.
+    fn create_vertex_array(&self) -> Result<VertexArrayHandle, TextError>;
+
+    /// Binds the given vertex array object, or the default vertex array object if vertex_array is None. The calls to
+    /// set_vertex_attribute, disable_vertex_attribute and set_attribute_divisor will affect the bound vertex array object.
+    fn bind_vertex_array(&self, vertex_array: Option<VertexArrayHandle>);
+
+    fn delete_vertex_array(&self, vertex_array: VertexArrayHandle);
+
+    /// Sets the number of instances that will share the same value of the vertex attribute at the given location. A
+    /// divisor of 0 means that every vertex reads its own value, which is the default.
+    fn set_attribute_divisor(&self, location: i32, divisor: u32);
+
+    /// Draws instance_count instances of count vertices as triangles, starting at the vertex with index first.
+    fn draw_instanced_triangles(&self, first: i32, count: i32, instance_count: i32);
+
+    /// Compiles and links a shader program with the given vertex shader source and fragment shader source. The returned
+    /// error should contain the info log if compiling or linking failed.
+    fn create_program(&self, vertex_source: &str, fragment_source: &str) -> Result<ProgramHandle, TextError>;
+
+    fn use_program(&self, program: ProgramHandle);
+
+    fn delete_program(&self, program: ProgramHandle);
+
+    fn get_attrib_location(&self, program: ProgramHandle, name: &str) -> i32;
+
+    fn get_uniform_location(&self, program: ProgramHandle, name: &str) -> Result<UniformHandle, TextError>;
+
+    fn set_uniform_1i(&self, uniform: UniformHandle, value: i32);
+
+    fn set_uniform_1f(&self, uniform: UniformHandle, value: f32);
+
+    fn set_uniform_2f(&self, uniform: UniformHandle, x: f32, y: f32);
+
+    fn set_uniform_4f(&self, uniform: UniformHandle, x: f32, y: f32, z: f32, w: f32);
+
+    /// Draws count vertices as triangles, starting at the vertex with index first. The vertices will be read from the
+    /// buffers that were passed to the set_vertex_attribute method.
+    fn draw_triangles(&self, first: i32, count: i32);
+
+    /// Enables the blending that is needed to render transparent text.
+    fn enable_blending(&self);
+
+    /// Gets the size (width, height) in pixels of the surface this backend is drawing on, or None if there is currently no
+    /// such surface (for instance when the canvas has been removed).
+    fn get_surface_size(&self) -> Option<(u32, u32)>;
+}
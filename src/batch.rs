@@ -0,0 +1,302 @@
+use wasmuri_core::*;
+
+use super::backend::{
+    BufferHandle,
+    BufferUsage,
+    float_bytes
+};
+use super::character::Character;
+use super::error::TextError;
+use super::layout::TextLayout;
+use super::shaders::{
+    BatchProgram,
+    get_color_components
+};
+use super::model::TextModel;
+use super::Font;
+
+use std::rc::Rc;
+
+/// A TextBatch collects many strings of a single Font and draws all of them at once. Every TextModel needs at least 1 draw
+/// call (and sets 5 uniform variables) every time it is rendered, which becomes expensive when hundreds of labels are drawn
+/// every frame. A TextBatch only needs 1 draw call per atlas page of its Font, no matter how many strings it contains.
+///
+/// Instances of TextBatch can be created with the create_text_batch method of TextRenderer. Use the add_text and add_model
+/// methods to add strings to the batch and call flush to draw all of them. Just like the render method of TextModel, flush
+/// should only be called after the start_rendering method of the TextRenderer. The batch will be empty after flushing, so
+/// the strings need to be added again every frame.
+///
+/// The positions, scales and colors of the strings have the same meaning as for the render method of TextModel. There is
+/// one difference: the backgrounds of all strings in the batch are drawn before any of the characters, so the background
+/// of a string will never cover the characters of another string in the same batch.
+pub struct TextBatch {
+
+    font: Rc<Font>,
+    program: Rc<BatchProgram>,
+
+    buffer: BufferHandle,
+
+    /// The number of bytes the buffer can hold
+    buffer_capacity: usize,
+
+    backgrounds: Vec<BatchQuad>,
+    quads: Vec<BatchQuad>
+}
+
+/// A single quad that has been added to a TextBatch. The coordinates are in the OpenGL coordinate space.
+struct BatchQuad {
+
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+
+    /// The character of this quad, or None if this quad is the background of a string
+    character: Option<Character>,
+
+    fill_color: [f32; 4],
+    stroke_color: [f32; 4],
+    background_color: [f32; 4],
+
+    /// The smoothing and stroke width for distance field fonts
+    distance_params: [f32; 2]
+}
+
+impl TextBatch {
+
+    pub(super) fn new(font: Rc<Font>, program: Rc<BatchProgram>) -> Result<TextBatch, TextError> {
+        let buffer = font.backend.create_buffer()?;
+        Ok(TextBatch {
+            font,
+            program,
+            buffer,
+            buffer_capacity: 0,
+            backgrounds: Vec::new(),
+            quads: Vec::new()
+        })
+    }
+
+    /// Adds the given text to this batch. It will be drawn the next time flush is called, at the same place and with the
+    /// same colors as a TextModel for the same text would be drawn by its render method with the same parameters.
+    pub fn add_text(&mut self, text: &str, offset_x: f32, offset_y: f32, scale_y: f32, colors: TextColors){
+        let layout = self.font.layout_text(text);
+        self.add_layout(&layout, offset_x, offset_y, scale_y, colors);
+    }
+
+    /// Adds the text of the given TextModel to this batch, as if it would be rendered with its render method. The buffer of
+    /// the TextModel won't be used, so this also works for models that have never been rendered.
+    ///
+    /// This method will panic if the TextModel was created by another Font than the Font of this batch.
+    pub fn add_model(&mut self, model: &TextModel, offset_x: f32, offset_y: f32, scale_y: f32, colors: TextColors){
+        assert!(Rc::ptr_eq(model.get_font(), &self.font), "The TextModel should be created by the Font of the TextBatch");
+        self.add_layout(model.get_layout(), offset_x, offset_y, scale_y, colors);
+    }
+
+    fn add_layout(&mut self, layout: &TextLayout, offset_x: f32, offset_y: f32, scale_y: f32, colors: TextColors){
+
+        // Just like the render method of TextModel, don't draw anything (not even the background) for empty text
+        if layout.quads.is_empty() {
+            return;
+        }
+
+        let scale_x = scale_y / self.font.aspect_ratio.get();
+        let fill_color = get_color_components(colors.fill_color);
+        let stroke_color = get_color_components(colors.stroke_color);
+        let distance_params = [self.font.get_smoothing(scale_y), self.font.get_stroke_width(self.font.get_default_line_width())];
+
+        let background_color = get_color_components(colors.background_color);
+        if background_color[3] > 0.0 {
+            self.backgrounds.push(BatchQuad {
+                min_x: offset_x,
+                min_y: offset_y,
+                max_x: offset_x + scale_x * layout.width,
+                max_y: offset_y + scale_y * layout.height,
+                character: None,
+                fill_color,
+                stroke_color,
+                background_color,
+                distance_params
+            });
+        }
+
+        for quad in &layout.quads {
+            self.quads.push(BatchQuad {
+                min_x: offset_x + scale_x * quad.min_x,
+                min_y: offset_y + scale_y * quad.min_y,
+                max_x: offset_x + scale_x * quad.max_x,
+                max_y: offset_y + scale_y * quad.max_y,
+                character: Some(quad.character),
+                fill_color,
+                stroke_color,
+                background_color: [0.0; 4],
+                distance_params
+            });
+        }
+    }
+
+    /// Draws all strings that were added to this batch since the last flush, and removes them from this batch. This will use
+    /// 1 draw call for every atlas page that is used by the strings.
+    pub fn flush(&mut self){
+        if self.quads.is_empty() {
+            return;
+        }
+
+        let font = Rc::clone(&self.font);
+        let backend = &font.backend;
+        font.upload_pages();
+        let page_sizes = font.get_page_sizes();
+
+        // The backgrounds are put in front of the characters of the first page, so they will be drawn before any character
+        let mut quads = std::mem::take(&mut self.quads);
+        quads.sort_by_key(|quad| quad.character.map_or(0, |character| character.get_page()));
+        let first_page = quads[0].character.map_or(0, |character| character.get_page());
+        let backgrounds = std::mem::take(&mut self.backgrounds);
+
+        let mut page_draws: Vec<(usize, i32, i32)> = vec![(first_page, 0, 6 * backgrounds.len() as i32)];
+        for quad in &quads {
+            let page = quad.character.map_or(first_page, |character| character.get_page());
+            let last_index = page_draws.len() - 1;
+            let (last_page, first_vertex, vertex_count) = page_draws[last_index];
+            if last_page == page {
+                page_draws[last_index].2 += 6;
+            } else {
+                page_draws.push((page, first_vertex + vertex_count, 6));
+            }
+        }
+
+        let floats_per_vertex = self.program.get_floats_per_vertex();
+        let mut buffer_data = Vec::with_capacity(6 * floats_per_vertex * (backgrounds.len() + quads.len()));
+        for quad in backgrounds.iter().chain(quads.iter()) {
+            let (left_u, bottom_v, right_u, top_v) = match quad.character {
+                Some(character) => {
+                    let (texture_width, texture_height) = page_sizes[character.get_page()];
+                    (character.get_left_u(texture_width), character.get_bottom_v(texture_height), character.get_right_u(texture_width), character.get_top_v(texture_height))
+                },
+
+                // The bottom-right corner of every page is empty, so it will only show the background color
+                None => (1.0, 1.0, 1.0, 1.0)
+            };
+
+            let corners = [
+                (quad.min_x, quad.min_y, left_u, bottom_v),
+                (quad.max_x, quad.min_y, right_u, bottom_v),
+                (quad.max_x, quad.max_y, right_u, top_v),
+                (quad.max_x, quad.max_y, right_u, top_v),
+                (quad.min_x, quad.max_y, left_u, top_v),
+                (quad.min_x, quad.min_y, left_u, bottom_v)
+            ];
+            for (x, y, u, v) in corners.iter() {
+                buffer_data.extend_from_slice(&[*x, *y, *u, *v]);
+                buffer_data.extend_from_slice(&quad.fill_color);
+                buffer_data.extend_from_slice(&quad.stroke_color);
+                buffer_data.extend_from_slice(&quad.background_color);
+                if floats_per_vertex > 16 {
+                    buffer_data.extend_from_slice(&quad.distance_params);
+                }
+            }
+        }
+
+        let buffer_data = float_bytes(&buffer_data);
+        if buffer_data.len() > self.buffer_capacity {
+            self.buffer_capacity = buffer_data.len().max(2 * self.buffer_capacity);
+            backend.allocate_buffer(self.buffer, self.buffer_capacity, BufferUsage::Dynamic);
+        }
+        backend.update_buffer(self.buffer, 0, &buffer_data);
+
+        self.program.use_program();
+        self.program.set_texture_sampler(0);
+        self.program.bind(self.buffer);
+        for (page, first_vertex, vertex_count) in page_draws {
+            backend.bind_texture(0, font.get_page_texture(page));
+            backend.draw_triangles(first_vertex, vertex_count);
+        }
+        self.program.unbind();
+
+        // The shader program of the Font is no longer in use, so the next TextModel must select its Font again
+        font.selected_font.set(None);
+    }
+
+    /// Removes all strings from this batch without drawing them
+    pub fn clear(&mut self){
+        self.backgrounds.clear();
+        self.quads.clear();
+    }
+
+    /// Checks whether no strings (with at least 1 character) have been added to this batch since the last flush
+    pub fn is_empty(&self) -> bool {
+        self.quads.is_empty()
+    }
+
+    pub fn get_font(&self) -> &Rc<Font> {
+        &self.font
+    }
+}
+
+impl Drop for TextBatch {
+
+    fn drop(&mut self){
+        self.font.backend.delete_buffer(self.buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use super::super::*;
+    use super::super::testing::*;
+
+    fn get_draws(backend: &RecordingBackend) -> Vec<(i32, i32)> {
+        backend.get_calls().iter().filter_map(|call| match call {
+            BackendCall::DrawTriangles { first, count } => Some((*first, *count)),
+            _ => None
+        }).collect()
+    }
+
+    #[test]
+    fn flush_draws_once_per_page_with_the_backgrounds_first() {
+        let (backend, mut renderer) = create_test_renderer(false);
+        renderer.atlas_options.max_size = Some(64);
+        renderer.all_chars = String::new();
+        let font = add_test_font(&mut renderer);
+        let mut batch = renderer.create_text_batch(&font);
+
+        let mut colors = test_colors(Color::from_rgb(255, 0, 0));
+        colors.background_color = Color::from_rgb(0, 0, 255);
+        batch.add_text("ABCDE", -0.5, 0.0, 0.1, colors);
+        batch.add_text("FGHIJ", 0.0, -0.5, 0.1, colors);
+        let page_count = font.get_page_sizes().len();
+        assert!(page_count > 1);
+
+        renderer.start_rendering();
+        backend.clear_calls();
+        batch.flush();
+        assert!(batch.is_empty());
+
+        // The first draw call starts with the 2 backgrounds, and the other draw calls continue where the previous one ended
+        let draws = get_draws(&backend);
+        assert_eq!(draws.len(), page_count);
+        assert_eq!(draws[0].0, 0);
+        assert_eq!(draws.iter().map(|(_first, count)| count).sum::<i32>(), 6 * (2 + 10));
+        for (previous, next) in draws.iter().zip(draws.iter().skip(1)) {
+            assert_eq!(previous.0 + previous.1, next.0);
+        }
+
+        // The backgrounds only use the empty bottom-right corner of the page
+        let floats_per_vertex = batch.program.get_floats_per_vertex();
+        let floats = backend.get_buffer_floats(batch.buffer).unwrap();
+        assert_eq!(floats.len(), 6 * floats_per_vertex * 12);
+        for vertex in floats.chunks_exact(floats_per_vertex).take(12) {
+            assert_eq!(&vertex[2..4], &[1.0, 1.0]);
+            assert_eq!(&vertex[12..16], &[0.0, 0.0, 1.0, 1.0]);
+        }
+        for vertex in floats.chunks_exact(floats_per_vertex).skip(12) {
+            assert_eq!(&vertex[12..16], &[0.0; 4]);
+        }
+
+        // The batch is empty after flushing, so the next flush doesn't draw anything
+        backend.clear_calls();
+        batch.flush();
+        assert!(backend.get_calls().is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+use web_sys::{
+    CanvasRenderingContext2d,
+    Document,
+    window,
+    HtmlCanvasElement,
+    HtmlElement
+};
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen::JsValue;
+
+use super::error::TextError;
+use super::font::FontDetails;
+use super::metrics::{
+    FontMetrics,
+    GlyphMetrics
+};
+use super::rasterizer::*;
+
+/// The GlyphRasterizer that uses a (hidden) canvas with a 2d context to draw the characters. This rasterizer needs a DOM
+/// and the results may differ (a little) between browsers.
+pub(super) struct CanvasRasterizer {
+
+    font_string: String,
+
+    line_width: f64,
+    line_margin: u32,
+    line_height: u32,
+    baseline: f32,
+    font_metrics: FontMetrics,
+
+    measure_ctx: CanvasRenderingContext2d,
+
+    draw_canvas: HtmlCanvasElement,
+    draw_ctx: CanvasRenderingContext2d
+}
+
+impl CanvasRasterizer {
+
+    pub fn new(font_details: &FontDetails, font_size: usize, line_width: f64) -> Result<CanvasRasterizer, TextError> {
+        let document = window().ok_or_else(|| dom_error("get the window"))?.document().ok_or_else(|| dom_error("get the document"))?;
+        let font_string = format!("{} {}px {}", font_details.get_before_size(), font_size, font_details.get_after_size());
+
+        let measure_canvas = create_canvas(&document)?;
+        measure_canvas.set_width(1);
+        measure_canvas.set_height(1);
+
+        let measure_ctx = get_context_2d(&measure_canvas)?;
+        measure_ctx.set_font(&font_string);
+
+        // Modern browsers can tell the ascent and descent of the font, which gives the exact position of the baseline
+        let font_box = measure_ctx.measure_text("M").ok().map(|metrics| (metrics.font_bounding_box_ascent(), metrics.font_bounding_box_descent()));
+        let (line_height, baseline, font_metrics) = match font_box {
+            Some((ascent, descent)) if ascent.is_finite() && descent.is_finite() && ascent + descent > 0.0 => {
+                let line_height = (ascent + descent).ceil() as u32;
+                (line_height, ascent as f32, FontMetrics::new(ascent as f32, descent as f32, 0.0))
+            }, _ => {
+                let line_height = measure_line_height(&document, &font_string)?;
+
+                // Without proper text metrics, we will have to do this dirty approximation
+                let baseline = (line_height * 4 / 5) as f32;
+                (line_height, baseline, FontMetrics::new(baseline, line_height as f32 - baseline, 0.0))
+            }
+        };
+
+        let draw_canvas = create_canvas(&document)?;
+        let draw_ctx = get_context_2d(&draw_canvas)?;
+
+        Ok(CanvasRasterizer {
+            font_string,
+
+            line_width: line_width * font_size as f64,
+            line_margin: (2.0 * line_width * font_size as f64).ceil() as u32,
+            line_height,
+            baseline,
+            font_metrics,
+
+            measure_ctx,
+
+            draw_canvas,
+            draw_ctx
+        })
+    }
+
+    /// Measures the width of the given text with the font of this rasterizer
+    fn measure_width(&self, text: &str) -> Option<f64> {
+        self.measure_ctx.measure_text(text).ok().map(|metrics| metrics.width())
+    }
+}
+
+/// Measures the line height of the given font by putting some text in a div. This is only used in old browsers that can't
+/// measure the ascent and descent of a font.
+fn measure_line_height(document: &Document, font_string: &str) -> Result<u32, TextError> {
+
+    // Code is based on https://github.com/knokko/Image-Helper/blob/master/ImageFactory.js -> determineFontHeight
+    let body = document.body().ok_or_else(|| dom_error("get the document body"))?;
+    let dummy = document.create_element("div").ok().and_then(|element| element.dyn_into::<HtmlElement>().ok())
+            .ok_or_else(|| dom_error("create the div to measure the line height"))?;
+    let dummy_text = document.create_text_node("M");
+    dummy.append_child(&dummy_text).map_err(|_| dom_error("add the text to measure the line height"))?;
+    dummy.set_attribute("style", &format!("font: {};", font_string)).map_err(|_| dom_error("set the font of the div to measure the line height"))?;
+    body.append_child(&dummy).map_err(|_| dom_error("add the div to measure the line height to the body"))?;
+    let line_height = dummy.offset_height() as u32;
+    body.remove_child(&dummy).map_err(|_| dom_error("remove the div to measure the line height from the body"))?;
+    Ok(line_height)
+}
+
+fn dom_error(step: &str) -> TextError {
+    TextError::Dom(format!("Couldn't {}", step))
+}
+
+fn create_canvas(document: &Document) -> Result<HtmlCanvasElement, TextError> {
+    document.create_element("canvas").ok().and_then(|element| element.dyn_into::<HtmlCanvasElement>().ok())
+            .ok_or_else(|| dom_error("create a canvas to draw the characters"))
+}
+
+fn get_context_2d(canvas: &HtmlCanvasElement) -> Result<CanvasRenderingContext2d, TextError> {
+    canvas.get_context("2d").ok().flatten().and_then(|context| context.dyn_into::<CanvasRenderingContext2d>().ok())
+            .ok_or_else(|| dom_error("get the 2d context of the canvas to draw the characters"))
+}
+
+impl GlyphRasterizer for CanvasRasterizer {
+
+    fn get_line_height(&self) -> u32 {
+        self.line_height
+    }
+
+    fn get_baseline(&self) -> f32 {
+        self.baseline
+    }
+
+    fn get_font_metrics(&self) -> FontMetrics {
+        self.font_metrics
+    }
+
+    fn rasterize(&self, character: char) -> Option<GlyphBitmap> {
+        let mut substring = [0; 4];
+        let substring = character.encode_utf8(&mut substring);
+
+        let text_metrics = self.measure_ctx.measure_text(substring).ok()?;
+        let advance = text_metrics.width() as f32;
+
+        // Older browsers only support the width of the text metrics
+        let ink_left = text_metrics.actual_bounding_box_left() as f32;
+        let ink_right = text_metrics.actual_bounding_box_right() as f32;
+        let ink_ascent = text_metrics.actual_bounding_box_ascent() as f32;
+        let ink_descent = text_metrics.actual_bounding_box_descent() as f32;
+        let metrics = if ink_left.is_finite() && ink_right.is_finite() && ink_ascent.is_finite() && ink_descent.is_finite() {
+            GlyphMetrics::new(advance, -ink_left, advance - ink_right, ink_ascent, ink_descent)
+        } else {
+            GlyphMetrics::new(advance, 0.0, 0.0, self.baseline, self.line_height as f32 - self.baseline)
+        };
+
+        // Leave room for the stroke and the anti-aliasing around the ink
+        let (offset_x, offset_y, width, height) = compute_ink_box(&metrics, self.baseline, self.line_margin + 1);
+
+        // Changing the size of a canvas will reset its state, so the font needs to be set after resizing
+        self.draw_canvas.set_width(width);
+        self.draw_canvas.set_height(height);
+        let ctx = &self.draw_ctx;
+
+        // Make sure that everything is red before drawing the text
+        // The red color will indicate empty space
+        ctx.set_fill_style(&JsValue::from_str("rgb(255,0,0)"));
+        ctx.fill_rect(0.0, 0.0, width as f64, height as f64);
+
+        // Now prepare for drawing the text
+        ctx.set_line_width(self.line_width);
+        ctx.set_font(&self.font_string);
+
+        // The origin of the character must end up at -offset_x, and the baseline at baseline - offset_y
+        let draw_x = -offset_x as f64;
+        let draw_y = (self.baseline - offset_y as f32) as f64;
+
+        // The green color will indicate the interior of the text
+        ctx.set_fill_style(&JsValue::from_str("rgb(0,255,0)"));
+        ctx.fill_text(substring, draw_x, draw_y).ok()?;
+
+        // The blue color will indicate the border of the text
+        ctx.set_stroke_style(&JsValue::from_str("rgb(0,0,255)"));
+        ctx.stroke_text(substring, draw_x, draw_y).ok()?;
+
+        let image_data = ctx.get_image_data(0.0, 0.0, width as f64, height as f64).ok()?;
+
+        Some(GlyphBitmap {
+            width,
+            height,
+            offset_x,
+            offset_y,
+            metrics,
+            pixels: image_data.data().0
+        })
+    }
+
+    fn get_advance(&self, character: char) -> Option<f32> {
+        let mut substring = [0; 4];
+        self.measure_width(character.encode_utf8(&mut substring)).map(|width| width as f32)
+    }
+
+    fn get_kerning(&self, left: char, right: char) -> f32 {
+
+        // The canvas doesn't expose the kerning table, but it does apply kerning when it measures the pair
+        let pair: String = [left, right].iter().collect();
+        let widths = (self.measure_width(&pair), self.measure_width(&left.to_string()), self.measure_width(&right.to_string()));
+        match widths {
+            (Some(pair_width), Some(left_width), Some(right_width)) => (pair_width - left_width - right_width) as f32,
+            _ => 0.0
+        }
+    }
+}
@@ -1,45 +1,87 @@
+use super::metrics::GlyphMetrics;
+use super::rasterizer::GlyphBitmap;
+
+/// The position of a single character in the backing textures of a Font. The position is stored in pixels rather than
+/// texture coordinates because the backing texture can grow when new characters are added to it. The page is the index of
+/// the backing texture that contains the character. The offset and metrics of the character are stored in pixels as well.
 #[derive(Clone,Copy)]
 pub struct Character {
 
-    min_u: f32,
-    min_v: f32,
-    max_u: f32,
-    max_v: f32,
+    page: usize,
+
+    min_x: u32,
+    min_y: u32,
+    max_x: u32,
+    max_y: u32,
 
-    width: u32
+    /// The position of the top-left corner of the quad of this character, relative to its origin at the top of the line.
+    /// See the offset_x and offset_y of GlyphBitmap.
+    offset_x: i32,
+    offset_y: i32,
+
+    metrics: GlyphMetrics
 }
 
 impl Character {
 
-    pub fn new(texture_width: u32, texture_height: u32, min_x: u32,  min_y: u32, max_x: u32, max_y: u32) -> Character {
-        let float_width = texture_width as f32 + 1.0;
-        let float_height = texture_height as f32 + 1.0;
+    /// Creates the Character for the given bitmap, which has been copied to (min_x, min_y) on the given page
+    pub fn new(page: usize, min_x: u32, min_y: u32, bitmap: &GlyphBitmap) -> Character {
         Character {
-            min_u: min_x as f32 / float_width,
-            min_v: max_y as f32 / float_height,
-            max_u: max_x as f32 / float_width,
-            max_v: min_y as f32 / float_height,
-            width: max_x - min_x + 1
+            page,
+            min_x,
+            min_y,
+            max_x: min_x + bitmap.width - 1,
+            max_y: min_y + bitmap.height - 1,
+            offset_x: bitmap.offset_x,
+            offset_y: bitmap.offset_y,
+            metrics: bitmap.metrics
         }
     }
 
-    pub fn get_left_u(&self) -> f32 {
-        self.min_u
+    pub fn get_page(&self) -> usize {
+        self.page
+    }
+
+    pub fn get_metrics(&self) -> GlyphMetrics {
+        self.metrics
+    }
+
+    pub fn get_left_u(&self, texture_width: u32) -> f32 {
+        self.min_x as f32 / (texture_width as f32 + 1.0)
     }
 
-    pub fn get_bottom_v(&self) -> f32 {
-        self.min_v
+    pub fn get_bottom_v(&self, texture_height: u32) -> f32 {
+        self.max_y as f32 / (texture_height as f32 + 1.0)
     }
 
-    pub fn get_right_u(&self) -> f32 {
-        self.max_u
+    pub fn get_right_u(&self, texture_width: u32) -> f32 {
+        self.max_x as f32 / (texture_width as f32 + 1.0)
     }
 
-    pub fn get_top_v(&self) -> f32 {
-        self.max_v
+    pub fn get_top_v(&self, texture_height: u32) -> f32 {
+        self.min_y as f32 / (texture_height as f32 + 1.0)
     }
 
+    /// Gets the width of the quad of this character, in pixels
     pub fn get_width(&self) -> u32 {
-        self.width
+        self.max_x - self.min_x + 1
+    }
+
+    /// Gets the height of the quad of this character, in pixels
+    pub fn get_height(&self) -> u32 {
+        self.max_y - self.min_y + 1
+    }
+
+    pub fn get_offset_x(&self) -> i32 {
+        self.offset_x
+    }
+
+    pub fn get_offset_y(&self) -> i32 {
+        self.offset_y
+    }
+
+    /// Gets the horizontal distance (in pixels) from the origin of this character to the origin of the next character
+    pub fn get_advance(&self) -> f32 {
+        self.metrics.get_advance()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,119 @@
+use super::alignment::HorizontalAlignment;
+use super::layout::TextLayout;
+use super::Font;
+
+/// The TextDecorations determine which lines will be drawn along text. They can be given to a TextModel (with its
+/// set_decorations method) or to a single TextSpan of a RichTextModel. The position and thickness of the lines are taken
+/// from the FontMetrics of the Font, and every line of wrapped text gets its own decoration lines.
+///
+/// The decoration lines have the fill color of the text, and will be drawn over the characters.
+#[derive(PartialEq,Eq,Copy,Clone,Debug,Default)]
+pub struct TextDecorations {
+
+    /// Whether a line should be drawn a little below the baseline
+    pub underline: bool,
+
+    /// Whether a line should be drawn through the middle of the lowercase characters
+    pub strikethrough: bool,
+
+    /// Whether a line should be drawn at the top of the ascent of the Font
+    pub overline: bool
+}
+
+impl TextDecorations {
+
+    /// No decoration lines at all
+    pub const NONE: TextDecorations = TextDecorations {
+        underline: false,
+        strikethrough: false,
+        overline: false
+    };
+
+    /// Only an underline, which is commonly used for hyperlinks
+    pub const UNDERLINE: TextDecorations = TextDecorations {
+        underline: true,
+        ..TextDecorations::NONE
+    };
+
+    /// Only a strikethrough line, which is commonly used for deleted or unavailable items
+    pub const STRIKETHROUGH: TextDecorations = TextDecorations {
+        strikethrough: true,
+        ..TextDecorations::NONE
+    };
+
+    /// Only an overline
+    pub const OVERLINE: TextDecorations = TextDecorations {
+        overline: true,
+        ..TextDecorations::NONE
+    };
+
+    /// Checks whether none of the decoration lines should be drawn
+    pub fn is_empty(&self) -> bool {
+        !self.underline && !self.strikethrough && !self.overline
+    }
+}
+
+/// A single decoration line of a TextLayout. The coordinates are in model space (see GlyphQuad).
+pub(super) struct DecorationLine {
+
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+
+    /// The index of the TextSpan of the characters above, below or through which this line is drawn
+    pub span: usize
+}
+
+/// Computes the decoration lines of the given layout when its lines are aligned with the given alignment within a region
+/// that is region_width wide (in model space). Every line of the layout gets decoration lines for every group of successive
+/// characters of the same span, where get_decorations gives the decorations of every span.
+///
+/// The decoration lines will be placed with the FontMetrics of the given Font, which should be the Font that determines the
+/// baselines of the layout.
+pub(super) fn compute_decoration_lines(font: &Font, layout: &TextLayout, alignment: HorizontalAlignment, region_width: f32, get_decorations: impl Fn(usize) -> TextDecorations) -> Vec<DecorationLine> {
+    let metrics = font.get_font_metrics(1.0);
+    let baseline_height = font.get_baseline_height();
+    let offsets = layout.compute_offsets(alignment, region_width);
+
+    let mut decoration_lines = Vec::new();
+    for line in &layout.lines {
+        let baseline = line.min_y + baseline_height;
+        let quad_range = line.first_quad..line.first_quad + line.quad_count;
+        let mut first_quad = line.first_quad;
+        for span_quads in layout.quads[quad_range].chunk_by(|left, right| left.span == right.span) {
+            let last_quad = first_quad + span_quads.len() - 1;
+            let span = span_quads[0].span;
+            let decorations = get_decorations(span);
+            let min_x = span_quads[0].advance_min_x + offsets[first_quad];
+            let max_x = span_quads[span_quads.len() - 1].advance_max_x + offsets[last_quad];
+            first_quad = last_quad + 1;
+
+            // The y-coordinates are the tops of the decoration lines
+            let mut tops = Vec::with_capacity(3);
+            if decorations.overline {
+                tops.push((baseline + metrics.get_ascent(), metrics.get_underline_thickness()));
+            }
+            if decorations.strikethrough {
+                tops.push((baseline + metrics.get_strikethrough_position(), metrics.get_strikethrough_thickness()));
+            }
+            if decorations.underline {
+                tops.push((baseline - metrics.get_underline_position(), metrics.get_underline_thickness()));
+            }
+
+            for (top, thickness) in tops {
+
+                // The overline must not stick out above the line, since it would cover the previous line
+                let max_y = top.min(line.min_y + 1.0);
+                decoration_lines.push(DecorationLine {
+                    min_x,
+                    min_y: max_y - thickness,
+                    max_x,
+                    max_y,
+                    span
+                });
+            }
+        }
+    }
+    decoration_lines
+}
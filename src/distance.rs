@@ -0,0 +1,199 @@
+use super::metrics::FontMetrics;
+use super::rasterizer::*;
+
+/// The distance field glyphs are computed from glyphs that are rasterized with a resolution that is this many times
+/// higher than the resolution of the distance field, which makes the distances more accurate.
+pub(super) const DISTANCE_FIELD_SUPERSAMPLING: u32 = 4;
+
+/// Used as 'infinite' squared distance in the distance transform
+const FAR_AWAY: f32 = 1e20;
+
+/// The GlyphRasterizer for Font's with a signed distance field atlas. It wraps another rasterizer that draws the characters
+/// at a higher resolution (without stroke) and converts its results to signed distance fields.
+///
+/// Every channel (red, green, blue and alpha) of the resulting glyphs will contain the distance to the edge of the character.
+/// A value of 0.5 (or 127.5) means the pixel is exactly on the edge. Larger values are inside the character and smaller values
+/// are outside the character. The spread determines the distance (in pixels of the distance field) at which the values reach 0
+/// or 1.
+pub(super) struct DistanceFieldRasterizer {
+
+    inner: Box<dyn GlyphRasterizer>,
+    downscale: u32,
+    spread: f32
+}
+
+impl DistanceFieldRasterizer {
+
+    pub fn new(inner: Box<dyn GlyphRasterizer>, downscale: u32, spread: f32) -> DistanceFieldRasterizer {
+        DistanceFieldRasterizer {
+            inner,
+            downscale,
+            spread
+        }
+    }
+}
+
+impl GlyphRasterizer for DistanceFieldRasterizer {
+
+    fn get_line_height(&self) -> u32 {
+        self.inner.get_line_height().div_ceil(self.downscale)
+    }
+
+    fn get_baseline(&self) -> f32 {
+        self.inner.get_baseline() / self.downscale as f32
+    }
+
+    fn get_font_metrics(&self) -> FontMetrics {
+        self.inner.get_font_metrics().scale(1.0 / self.downscale as f32)
+    }
+
+    fn get_kerning(&self, left: char, right: char) -> f32 {
+        self.inner.get_kerning(left, right) / self.downscale as f32
+    }
+
+    fn get_empty_pixel(&self) -> [u8; 4] {
+        EMPTY_DISTANCE_PIXEL
+    }
+
+    fn rasterize(&self, character: char) -> Option<GlyphBitmap> {
+        let source = self.inner.rasterize(character)?;
+
+        // Leave some space around the character so that its stroke won't be cut off
+        let downscale = self.downscale as i32;
+        let margin = self.spread.ceil() as i32;
+        let offset_x = source.offset_x.div_euclid(downscale) - margin;
+        let offset_y = source.offset_y.div_euclid(downscale) - margin;
+        let width = ((source.offset_x + source.width as i32 + downscale - 1).div_euclid(downscale) + margin - offset_x) as u32;
+        let height = ((source.offset_y + source.height as i32 + downscale - 1).div_euclid(downscale) + margin - offset_y) as u32;
+
+        let big_width = width * self.downscale;
+        let big_height = height * self.downscale;
+
+        // The position of the top-left pixel of the source bitmap in the big grid
+        let source_x = source.offset_x - offset_x * downscale;
+        let source_y = source.offset_y - offset_y * downscale;
+
+        let mut to_inside = vec![FAR_AWAY; (big_width * big_height) as usize];
+        let mut to_outside = vec![FAR_AWAY; (big_width * big_height) as usize];
+        for y in 0..big_height {
+            for x in 0..big_width {
+                let index = (x + y * big_width) as usize;
+                let x_in_source = x as i32 - source_x;
+                let y_in_source = y as i32 - source_y;
+                let inside = x_in_source >= 0 && y_in_source >= 0 && x_in_source < source.width as i32 && y_in_source < source.height as i32 && {
+                    let source_index = (4 * (x_in_source + y_in_source * source.width as i32)) as usize;
+                    source.pixels[source_index + 1] as u32 + source.pixels[source_index + 2] as u32 >= 128
+                };
+                if inside {
+                    to_inside[index] = 0.0;
+                } else {
+                    to_outside[index] = 0.0;
+                }
+            }
+        }
+
+        distance_transform(&mut to_inside, big_width, big_height);
+        distance_transform(&mut to_outside, big_width, big_height);
+
+        let metrics_scale = 1.0 / self.downscale as f32;
+        let metrics = source.metrics.scale(metrics_scale, metrics_scale);
+        let mut bitmap = GlyphBitmap::empty(width, height, offset_x, offset_y, metrics);
+        let block_size = (self.downscale * self.downscale) as f32;
+        for y in 0..height {
+            for x in 0..width {
+
+                // Use the average signed distance of all big pixels that correspond to this pixel
+                let mut total_distance = 0.0;
+                for big_y in y * self.downscale..(y + 1) * self.downscale {
+                    for big_x in x * self.downscale..(x + 1) * self.downscale {
+                        let big_index = (big_x + big_y * big_width) as usize;
+                        total_distance += to_outside[big_index].sqrt() - to_inside[big_index].sqrt();
+                    }
+                }
+                let distance = total_distance / block_size / self.downscale as f32;
+
+                let value = (255.0 * (0.5 + distance / (2.0 * self.spread))).round().clamp(0.0, 255.0) as u8;
+                let index = (4 * (x + y * width)) as usize;
+                for channel in 0..4 {
+                    bitmap.pixels[index + channel] = value;
+                }
+            }
+        }
+
+        Some(bitmap)
+    }
+
+    fn get_advance(&self, character: char) -> Option<f32> {
+        self.inner.get_advance(character).map(|advance| advance / self.downscale as f32)
+    }
+}
+
+/// Replaces every value in the grid with the squared distance to the nearest pixel whose value was 0. All other pixels
+/// should have the value FAR_AWAY. This is the algorithm of Felzenszwalb and Huttenlocher, which first transforms all
+/// columns and then all rows.
+fn distance_transform(grid: &mut [f32], width: u32, height: u32){
+    let length = width.max(height) as usize;
+    let mut source = vec![0.0; length];
+    let mut dest = vec![0.0; length];
+    let mut parabolas = vec![0; length];
+    let mut boundaries = vec![0.0; length + 1];
+
+    for x in 0..width as usize {
+        for y in 0..height as usize {
+            source[y] = grid[x + y * width as usize];
+        }
+        distance_transform_1d(&source[..height as usize], &mut dest, &mut parabolas, &mut boundaries);
+        for y in 0..height as usize {
+            grid[x + y * width as usize] = dest[y];
+        }
+    }
+
+    for y in 0..height as usize {
+        let row = &mut grid[y * width as usize..(y + 1) * width as usize];
+        source[..width as usize].copy_from_slice(row);
+        distance_transform_1d(&source[..width as usize], &mut dest, &mut parabolas, &mut boundaries);
+        row.copy_from_slice(&dest[..width as usize]);
+    }
+}
+
+fn distance_transform_1d(source: &[f32], dest: &mut [f32], parabolas: &mut [usize], boundaries: &mut [f32]){
+    let length = source.len();
+    let mut k = 0;
+    parabolas[0] = 0;
+    boundaries[0] = -FAR_AWAY;
+    boundaries[1] = FAR_AWAY;
+
+    for q in 1..length {
+        let mut s;
+        loop {
+            let p = parabolas[k];
+            s = ((source[q] + (q * q) as f32) - (source[p] + (p * p) as f32)) / (2.0 * q as f32 - 2.0 * p as f32);
+            if s <= boundaries[k] && k > 0 {
+                k -= 1;
+            } else {
+                break;
+            }
+        }
+        if s <= boundaries[k] {
+            // This can only happen when k is 0, in which case the new parabola replaces the first one
+            parabolas[0] = q;
+            boundaries[0] = -FAR_AWAY;
+            boundaries[1] = FAR_AWAY;
+        } else {
+            k += 1;
+            parabolas[k] = q;
+            boundaries[k] = s;
+            boundaries[k + 1] = FAR_AWAY;
+        }
+    }
+
+    k = 0;
+    for (q, value) in dest.iter_mut().enumerate().take(length) {
+        while boundaries[k + 1] < q as f32 {
+            k += 1;
+        }
+        let p = parabolas[k];
+        let difference = q as f32 - p as f32;
+        *value = difference * difference + source[p];
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+
+/// The errors that can occur while creating a TextRenderer, Font or TextModel. The try_... methods of those structs will
+/// return these errors rather than panicking, so that the application can show a fallback message instead. The
+/// parse_markup function uses these errors as well.
+#[derive(PartialEq,Eq,Clone,Debug)]
+pub enum TextError {
+
+    /// No webgl context could be obtained from the canvas. This happens for instance when another type of context has
+    /// already been created for the canvas.
+    NoWebGlContext,
+
+    /// A DOM operation that is needed to rasterize the characters of a Font failed. The string describes the failing step.
+    Dom(String),
+
+    /// The backend couldn't create an object (like a texture or buffer). The string describes the kind of object.
+    ObjectCreation(String),
+
+    /// The backend couldn't upload the pixels of a texture. The string describes the cause.
+    TextureUpload(String),
+
+    /// A shader of the text program couldn't be compiled.
+    ShaderCompilation { shader_type: String, info_log: String },
+
+    /// The text shader program couldn't be linked.
+    ProgramLink { info_log: String },
+
+    /// The text shader program doesn't have the uniform variable with this name.
+    MissingUniform(String),
+
+    /// The data passed to the add_ttf_font method of TextRenderer is not a valid TrueType or OpenType font.
+    InvalidFont,
+
+    /// The markup passed to parse_markup is invalid. The offset is the index (in bytes) of the problem in the markup, and
+    /// the message describes the problem.
+    InvalidMarkup { offset: usize, message: String }
+}
+
+impl fmt::Display for TextError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextError::NoWebGlContext => write!(f, "Couldn't obtain a webgl context from the canvas"),
+            TextError::Dom(step) => write!(f, "DOM operation failed: {}", step),
+            TextError::ObjectCreation(object) => write!(f, "Couldn't create {}", object),
+            TextError::TextureUpload(cause) => write!(f, "Couldn't upload texture: {}", cause),
+            TextError::ShaderCompilation { shader_type, info_log } => write!(f, "Couldn't compile {} text shader: {}", shader_type, info_log),
+            TextError::ProgramLink { info_log } => write!(f, "Couldn't link the text shader program: {}", info_log),
+            TextError::MissingUniform(name) => write!(f, "Couldn't get {} uniform location", name),
+            TextError::InvalidFont => write!(f, "The font data is not a valid TrueType or OpenType font"),
+            TextError::InvalidMarkup { offset, message } => write!(f, "Invalid markup at byte {}: {}", offset, message)
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
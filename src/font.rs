@@ -1,28 +1,30 @@
-use web_sys::{
-    CanvasRenderingContext2d,
-    WebGlBuffer,
-    WebGlRenderingContext,
-    WebGlRenderingContext as GL,
-    WebGlTexture,
-    window,
-    HtmlCanvasElement,
-    HtmlElement
-};
-
-use js_sys::Float32Array;
-
-use wasm_bindgen::JsCast;
-use wasm_bindgen::JsValue;
-
 use wasmuri_core::*;
 
 use std::cell::RefCell;
 use std::cell::Cell;
+use std::collections::{
+    HashMap,
+    HashSet
+};
 use std::rc::Rc;
 
+use super::alignment::HorizontalAlignment;
+use super::atlas::GlyphAtlas;
+use super::backend::*;
 use super::character::Character;
+use super::error::TextError;
+use super::layout::{
+    LineChar,
+    TextLayout
+};
+use super::metrics::*;
 use super::model::TextModel;
-use super::shaders::TextProgram;
+use super::rasterizer::*;
+use super::rich::TextSpan;
+use super::shaders::{
+    InstanceArray,
+    TextProgram
+};
 
 #[derive(PartialEq,Eq,Copy,Clone)]
 pub(super) struct FontID {
@@ -45,13 +47,17 @@ impl FontID {
 /// to that example font, you would need to use FontDetails::new("bold", "Arial").
 /// 
 /// Whenever a Font is created, an instance of FontDetails needs to be passed as parameter to describe all
-/// the info/details about the font to create. Internally, a canvas with a 2d context will be used to generate
-/// the backing texture of all characters for the Font. The before_size of the FontDetails (plus an extra whitespace)
+/// the info/details about the font to create. Unless the Font is created from a font file (with the add_ttf_font
+/// method of TextRenderer), a canvas with a 2d context will be used to generate the backing texture of all 
+/// characters for the Font. The before_size of the FontDetails (plus an extra whitespace)
 /// will literally be pasted before the size declaration of the font and the after_size of the FontDetails will
 /// be pasted after the size declaration (plus an extra whitespace). 
 /// 
 /// The size declaration of the font will be handled internally, but note that the size of the drawn text does
 /// NOT depend on that because the scaling of rendered text will be done on-the-fly.
+/// 
+/// For Font's that are created from a font file, the FontDetails will only be used to identify the Font (for
+/// instance with the get_font_by_details method of TextRenderer).
 pub struct FontDetails {
 
     before_size: String,
@@ -91,6 +97,84 @@ impl FontDetails {
     }
 }
 
+/// The AtlasMode determines how the characters of a Font are stored in its backing texture. The atlas_mode of the
+/// TextRenderer will be used to choose the AtlasMode of new Font's.
+#[derive(PartialEq,Copy,Clone,Debug)]
+pub enum AtlasMode {
+
+    /// The characters will be drawn in the backing texture at the font_size of the TextRenderer, with their stroke
+    /// (with the line_width of the TextRenderer) baked in. This is the default mode.
+    Bitmap,
+
+    /// The backing texture will store a signed distance field of every character: the distance from every pixel to
+    /// the edge of the character. This allows characters to stay sharp at any scale, needs a much smaller backing
+    /// texture and allows the stroke width to be chosen every time the text is rendered.
+    /// 
+    /// The font_size is the size of the distance field characters in the backing texture (the font_size of the 
+    /// TextRenderer will be ignored for distance field Font's). The spread is the maximum distance (as a fraction of
+    /// the font_size) that will be stored in the distance field. The line_width of rendered text can't be larger than
+    /// twice the spread.
+    DistanceField { font_size: usize, spread: f64 }
+}
+
+/// The AtlasOptions determine how the characters of a Font are packed in its backing texture. The atlas_options of the
+/// TextRenderer will be used for new Font's.
+#[derive(PartialEq,Eq,Copy,Clone,Debug)]
+pub struct AtlasOptions {
+
+    /// The number of empty pixels between the characters in the backing texture. Without padding, the edges of characters
+    /// could show a bit of their neighbours when the text is scaled.
+    pub padding: u32,
+
+    /// If true, the width and height of the backing texture will always be powers of 2.
+    pub power_of_two: bool,
+
+    /// The maximum width and height of the backing texture. The MAX_TEXTURE_SIZE of the webgl context will always be
+    /// respected, so this is only useful to choose an even smaller maximum. If None, only the MAX_TEXTURE_SIZE will be used.
+    pub max_size: Option<u32>
+}
+
+pub const DEFAULT_ATLAS_PADDING: u32 = 1;
+
+impl Default for AtlasOptions {
+
+    fn default() -> AtlasOptions {
+        AtlasOptions {
+            padding: DEFAULT_ATLAS_PADDING,
+            power_of_two: false,
+            max_size: None
+        }
+    }
+}
+
+pub const DEFAULT_DISTANCE_FIELD_SIZE: usize = 48;
+pub const DEFAULT_DISTANCE_FIELD_SPREAD: f64 = 0.125;
+
+impl AtlasMode {
+
+    /// Gets the DistanceField AtlasMode with the default font_size and spread
+    pub fn default_distance_field() -> AtlasMode {
+        AtlasMode::DistanceField {
+            font_size: DEFAULT_DISTANCE_FIELD_SIZE,
+            spread: DEFAULT_DISTANCE_FIELD_SPREAD
+        }
+    }
+}
+
+/// The properties of a Font with a distance field atlas that are needed when rendering its text
+#[derive(Copy,Clone)]
+pub(super) struct DistanceFieldProperties {
+
+    /// The font size, in pixels of the backing texture
+    pub font_size: f32,
+
+    /// The spread, in pixels of the backing texture
+    pub spread: f32,
+
+    /// The default line_width (as fraction of the font size) for text rendered with this Font
+    pub line_width: f32
+}
+
 /// Fonts are the structs responsible for creating TextModel's that can draw text onto the webgl canvas. Instances
 /// of Font can be created by using the add_font or add_fonts method of a TextRenderer.
 /// 
@@ -111,209 +195,205 @@ pub struct Font {
     font_details: FontDetails,
 
     max_text_height: u32,
+    baseline: f32,
+    font_metrics: FontMetrics,
+    line_spacing: f32,
     pub(super) aspect_ratio: Cell<f32>,
+    pub(super) surface_height: Cell<f32>,
+
+    pub(super) distance_field: Option<DistanceFieldProperties>,
 
     pub(super) id: FontID,
     pub(super) selected_font: Rc<Cell<Option<FontID>>>,
 
-    characters: Vec<Option<Character>>,
-    fill_buffer: WebGlBuffer,
+    rasterizer: Box<dyn GlyphRasterizer>,
+    dynamic_atlas: bool,
+    atlas_options: AtlasOptions,
+    pages: RefCell<Vec<AtlasPage>>,
 
-    pub(super) gl: Rc<WebGlRenderingContext>,
-    pub(super) shader_program: Rc<RefCell<TextProgram>>,
-    texture: WebGlTexture
-}
+    /// The index of the page whose texture was bound the last time this Font was made current
+    current_page: Cell<usize>,
+    characters: RefCell<HashMap<char, Character>>,
+    fallback_char: Option<char>,
 
-impl Font {
+    /// The characters that the rasterizer of this Font failed to draw, so that it won't try again
+    unavailable_characters: RefCell<HashSet<char>>,
 
-    pub(super) fn new(gl: Rc<WebGlRenderingContext>, shader_program: Rc<RefCell<TextProgram>>, font_id: FontID, selected_font: Rc<Cell<Option<FontID>>>, font_size: usize, line_width: f64, font_details: FontDetails, chars: &str) -> Font {
-        let document = window().unwrap().document().unwrap();
-        let font_string = &format!("{} {}px {}", font_details.get_before_size(), font_size, font_details.get_after_size());
+    /// The kerning of all pairs of characters that have been laid out with this Font so far
+    kerning_pairs: RefCell<HashMap<(char, char), f32>>,
 
-        let test_canvas = document.create_element("canvas").unwrap().dyn_into::<HtmlCanvasElement>().unwrap();
-        test_canvas.set_width(1);
-        test_canvas.set_height(1);
-
-        let test_ctx = test_canvas.get_context("2d").unwrap().unwrap().dyn_into::<CanvasRenderingContext2d>().unwrap();
-        test_ctx.set_font(font_string);
-
-        // Even though chars.len() will return the length in bytes rather than the length in chars,
-        // it is still a nice approximation and the initial capacity doesn't have to be exact.
-        let mut char_sizes = Vec::with_capacity(chars.len());
-
-        let mut max_height = 0;
-
-        let line_margin = (2.0 * line_width * font_size as f64).ceil() as u32;
-
-        let mut max_char_code = 0;
-        let mut char_counter = 0;
-        
-        for character in chars.chars() {
-            let mut substring = [0; 4];
-            let bounds = test_ctx.measure_text(character.encode_utf8(&mut substring)).unwrap();
-
-            // I would like to obtain stuff like height as well, but... well... browser compatibility...
-            // https://developer.mozilla.org/en-US/docs/Web/API/TextMetrics
-            let char_width = bounds.width().ceil() as u32;
-
-            // So... let's obtain the char_height the hard way...
-            // Code is based on https://github.com/knokko/Image-Helper/blob/master/ImageFactory.js -> determineFontHeight
-            let body = document.body().unwrap();
-            let dummy = document.create_element("div").unwrap().dyn_into::<HtmlElement>().unwrap();
-            let dummy_text = document.create_text_node("M");
-            dummy.append_child(&dummy_text).unwrap();
-            dummy.set_attribute("style", &format!("font: {};", font_string)).unwrap();
-            body.append_child(&dummy).unwrap();
-            let char_height = dummy.offset_height() as u32;
-            body.remove_child(&dummy).unwrap();
-
-            char_sizes.push((char_width, char_height));
-
-            if char_height > max_height {
-                max_height = char_height;
-            }
-
-            let char_code = character as usize;
-            if char_code > max_char_code {
-                max_char_code = char_code;
-            }
-
-            char_counter += 1;
-        }
-
-        let chars_per_row = (char_counter as f64).sqrt().ceil() as u32;
-        let rows;
-        {
-            let divided = char_counter / chars_per_row;
-            if divided * chars_per_row >= char_counter {
-                rows = divided;
-            } else {
-                rows = divided + 1;
-            }
-        }
-
-        let total_width;
-        {
-            // We will have to start with some value...
-            let mut max_width = 0;
-
-            for row in char_sizes.chunks(chars_per_row as usize) {
-                let mut current_width = 0;
-                for char_size in row {
-                    current_width += char_size.0 + 2 * line_margin;
-                }
-                if current_width > max_width {
-                    max_width = current_width;
-                }
-            }
-
-            total_width = max_width;
-        }
+    fill_buffer: BufferHandle,
 
-        let texture_canvas = document.create_element("canvas").unwrap().dyn_into::<HtmlCanvasElement>().unwrap();
-        texture_canvas.set_width(total_width);
+    /// Whether the texture coordinates in the vertex data of the TextModel's of this Font are stored as u16's
+    compact_texture_coords: bool,
 
-        let total_height = rows * max_height;
+    /// Whether this Font can draw shadows with a softness larger than 0 (see TextShadow)
+    soft_shadows: bool,
 
-        texture_canvas.set_height(total_height);
-        let texture_ctx = texture_canvas.get_context("2d").unwrap().unwrap().dyn_into::<CanvasRenderingContext2d>().unwrap();
+    /// The vertex array object for the fill_buffer, or None if the shader program of this Font is not instanced
+    fill_array: Option<InstanceArray>,
 
-        // Make sure that everything is red before drawing the text
-        // The red color will indicate empty space
-        texture_ctx.set_fill_style(&JsValue::from_str("rgb(255,0,0)"));
-        texture_ctx.fill_rect(0.0, 0.0, total_width as f64, total_height as f64);
-
-        // Now prepare for drawing the text
-        texture_ctx.set_line_width(line_width * font_size as f64);
-        texture_ctx.set_font(font_string);
-
-        // Due to lack of proper text metrics, we will have to do this dirty approximation
-        let mut draw_y = (max_height * 4 / 5) as f64;
-
-        let mut min_y = 0;
-        let mut draw_x = 0;
-
-        let mut chars_in_this_row = 0;
+    pub(super) backend: Rc<dyn TextBackend>,
+    pub(super) shader_program: Rc<RefCell<TextProgram>>
+}
 
-        let mut character_map = vec![None; max_char_code + 1];
+/// A single backing texture of a Font, together with its CPU copy. A Font will get another page when all its pages are full.
+struct AtlasPage {
 
-        let mut index = 0;
-        for character in chars.chars() {
+    atlas: GlyphAtlas,
+    texture: TextureHandle
+}
 
-            let mut substring = [0; 4];
-            let min_x = draw_x;
+impl Font {
 
-            // The green color will indicate the interior of the text
-            texture_ctx.set_fill_style(&JsValue::from_str("rgb(0,255,0)"));
-            texture_ctx.fill_text(character.encode_utf8(&mut substring), draw_x as f64, draw_y).unwrap();
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn new(backend: Rc<dyn TextBackend>, shader_program: Rc<RefCell<TextProgram>>, font_id: FontID, selected_font: Rc<Cell<Option<FontID>>>, font_details: FontDetails, rasterizer: Box<dyn GlyphRasterizer>, dynamic_atlas: bool, atlas_options: AtlasOptions, distance_field: Option<DistanceFieldProperties>, line_spacing: f32, chars: &str, fallback_char: Option<char>, compact_texture_coords: bool, soft_shadows: bool) -> Result<Font, TextError> {
 
-            // The blue color will indicate the border of the text
-            texture_ctx.set_stroke_style(&JsValue::from_str("rgb(0,0,255)"));
-            texture_ctx.stroke_text(character.encode_utf8(&mut substring), draw_x as f64, draw_y).unwrap();
+        // Even though chars.len() will return the length in bytes rather than the length in chars,
+        // it is still a nice approximation and the initial capacity doesn't have to be exact.
+        let mut bitmaps = Vec::with_capacity(chars.len());
 
-            draw_x += char_sizes[index].0 + 2 * line_margin;
+        let max_height = rasterizer.get_line_height();
 
-            let max_x = draw_x - line_margin;
-            let max_y = min_y + max_height - 1;
+        // The fallback character must always be in the atlas
+        let extra_char = fallback_char.filter(|fallback| !chars.contains(*fallback));
 
-            character_map[character as usize] = Some(Character::new(total_width, total_height, min_x, min_y, max_x, max_y));
+        for character in chars.chars().chain(extra_char) {
+            match rasterizer.rasterize(character) {
+                Some(bitmap) => bitmaps.push((character, bitmap)),
+                None => print(&format!("Can't rasterize character {}", character))
+            };
+        }
 
-            chars_in_this_row += 1;
-            if chars_in_this_row >= chars_per_row {
-                chars_in_this_row = 0;
-                draw_x = 0;
-                draw_y += max_height as f64;
-                min_y += max_height;
+        // Resolve the maximum size so that new pages don't need to ask the backend again
+        let atlas_options = AtlasOptions {
+            max_size: Some(match atlas_options.max_size {
+                Some(max_size) => max_size.min(backend.get_max_texture_size()),
+                None => backend.get_max_texture_size()
+            }),
+            ..atlas_options
+        };
+
+        // Start with a square atlas that has roughly the right area, and remove the unused space after packing
+        let padding = atlas_options.padding;
+        let total_area: u64 = bitmaps.iter().map(|(_character, bitmap)| ((bitmap.width + padding) * (bitmap.height + padding)) as u64).sum();
+        let max_width = bitmaps.iter().map(|(_character, bitmap)| bitmap.width + padding).max().unwrap_or(1);
+        let initial_size = ((total_area as f64).sqrt().ceil() as u32).max(max_width);
+
+        let empty_pixel = rasterizer.get_empty_pixel();
+        let mut atlases = vec![create_page_atlas(atlas_options, empty_pixel, initial_size)];
+        let mut character_map = HashMap::with_capacity(bitmaps.len());
+
+        // Packing the tallest (and then the widest) characters first gives the best results
+        bitmaps.sort_by(|(_char_a, a), (_char_b, b)| b.height.cmp(&a.height).then(b.width.cmp(&a.width)));
+        for (character, bitmap) in &bitmaps {
+            let last_page = atlases.len() - 1;
+            let mut maybe_texture_char = add_to_atlas(&mut atlases[last_page], last_page, bitmap);
+
+            // If the current page is full, continue on a new page
+            if maybe_texture_char.is_none() {
+                let mut new_atlas = create_page_atlas(atlas_options, empty_pixel, initial_size);
+                maybe_texture_char = add_to_atlas(&mut new_atlas, last_page + 1, bitmap);
+                if maybe_texture_char.is_some() {
+                    atlases.push(new_atlas);
+                }
             }
 
-            index += 1;
+            match maybe_texture_char {
+                Some(texture_char) => { character_map.insert(*character, texture_char); },
+                None => print(&format!("Character {} is too large for the atlas", character))
+            };
         }
 
-        // Now we have drawn all text onto the canvas, so it's time to convert it to a WebGL texture
-        let image_data = texture_ctx.get_image_data(0.0, 0.0, total_width as f64, total_height as f64).unwrap();
-
-        let texture = gl.create_texture().unwrap();
-        gl.bind_texture(GL::TEXTURE_2D, Some(&texture));
-        gl.tex_image_2d_with_u32_and_u32_and_image_data(GL::TEXTURE_2D, 0, GL::RGBA as i32, 
-            GL::RGBA, GL::UNSIGNED_BYTE, &image_data).unwrap();
-        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE as i32);
-        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE as i32);
-        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::LINEAR as i32);
-        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MAG_FILTER, GL::LINEAR as i32);
+        // Now we have drawn all characters, so it's time to convert the atlases to textures
+        let mut pages: Vec<AtlasPage> = Vec::with_capacity(atlases.len());
+        let delete_textures = |pages: &[AtlasPage]| {
+            for page in pages {
+                backend.delete_texture(page.texture);
+            }
+        };
+        for mut atlas in atlases {
+            atlas.shrink_to_fit();
+            let texture = match backend.create_texture() {
+                Ok(texture) => texture,
+                Err(error) => {
+                    delete_textures(&pages);
+                    return Err(error);
+                }
+            };
+            pages.push(AtlasPage { atlas, texture });
+            let page = pages.last_mut().expect("Just pushed a page");
+            if let Err(error) = backend.upload_texture(texture, page.atlas.get_width(), page.atlas.get_height(), page.atlas.get_pixels()) {
+                delete_textures(&pages);
+                return Err(error);
+            }
+            page.atlas.set_uploaded();
+        }
 
         // Creating the fill_rect helper model
-        let fill_buffer = gl.create_buffer().unwrap();
-        {
+        let fill_buffer = match backend.create_buffer() {
+            Ok(buffer) => buffer,
+            Err(error) => {
+                delete_textures(&pages);
+                return Err(error);
+            }
+        };
+        let instanced = shader_program.borrow().is_instanced();
+        let fill_array = match instanced {
+            true => match InstanceArray::new(Rc::clone(&backend)) {
+                Ok(fill_array) => Some(fill_array),
+                Err(error) => {
+                    delete_textures(&pages);
+                    backend.delete_buffer(fill_buffer);
+                    return Err(error);
+                }
+            }, false => None
+        };
+        if instanced {
+
+            // A single instance that covers the unit square and only uses the bottom-right corner of the atlas
+            backend.upload_buffer(fill_buffer, &float_bytes(&[0.0,0.0, 1.0,1.0, 1.0,1.0, 1.0,1.0]));
+        } else {
             //let position_data = vec![0.0,0.0, 1.0,0.0, 1.0,1.0, 1.0,1.0, 0.0,1.0, 0.0,0.0];
             //let texture_data = vec![1.0,1.0, 1.0,1.0, 1.0,1.0, 1.0,1.0, 1.0,1.0, 1.0,1.0];
             let buffer_data = vec![0.0,0.0, 1.0,0.0, 1.0,1.0, 1.0,1.0, 0.0,1.0, 0.0,0.0,   1.0,1.0, 1.0,1.0, 1.0,1.0, 1.0,1.0, 1.0,1.0, 1.0,1.0];
-
-            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&fill_buffer));
-
-            // Really? Is there no safe way to do this?
-            unsafe {
-                let js_array = Float32Array::view(&buffer_data);
-                gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &js_array, GL::STATIC_DRAW);
-            }
+            backend.upload_buffer(fill_buffer, &float_bytes(&buffer_data));
         }
 
-        Font {
+        Ok(Font {
             font_details,
             max_text_height: max_height,
+            baseline: rasterizer.get_baseline(),
+            font_metrics: rasterizer.get_font_metrics(),
+            line_spacing,
 
             // The initial aspect_ratio doesn't matter because the TextRenderer will update the aspect_ratio of this font before every frame
             aspect_ratio: Cell::new(1.0),
+            surface_height: Cell::new(1.0),
+
+            distance_field,
 
             id: font_id,
             selected_font,
 
-            characters: character_map,
+            // The rasterizer is also needed to determine the kerning, so all fonts keep it
+            rasterizer,
+            dynamic_atlas,
+            atlas_options,
+            pages: RefCell::new(pages),
+            current_page: Cell::new(0),
+            characters: RefCell::new(character_map),
+            fallback_char,
+            unavailable_characters: RefCell::new(HashSet::new()),
+            kerning_pairs: RefCell::new(HashMap::new()),
             fill_buffer,
+            compact_texture_coords,
+            soft_shadows,
+            fill_array,
 
-            gl,
-            shader_program,
-            texture
-        }
+            backend,
+            shader_program
+        })
     }
 
     /// Gets the FontDetails instance that was used to create this Font. See the description of FontDetails for more info
@@ -324,164 +404,729 @@ impl Font {
 
     /// Creates a TextModel for the given string. The returned TextModel has a render method that will draw this text and can 
     /// be reused as often as you like. Reusing the returned TextModel is encouraged to avoid needless allocation of buffers.
+    /// 
+    /// The text can consist of multiple lines, separated by '\n' characters. The first line will be drawn at the top and
+    /// the space between the lines is determined by the line_spacing of the TextRenderer at the time this Font was created.
+    /// 
+    /// This method will panic if the buffer of the TextModel can't be created. Use try_create_text_model to handle that case.
     pub fn create_text_model(self: Rc<Self>, text: &str) -> TextModel {
+        self.try_create_text_model(text).expect("Should be able to create the buffer of the text model")
+    }
 
-        let mut char_counter = 0;
-        for _char in text.chars() {
-            char_counter += 1;
-        }
+    /// Creates a TextModel for the given string, just like create_text_model, but returns an error rather than panicking
+    /// if the buffer of the TextModel can't be created.
+    pub fn try_create_text_model(self: Rc<Self>, text: &str) -> Result<TextModel, TextError> {
+        let layout = self.layout_text(text);
+        self.create_model_from_layout(layout)
+    }
 
-        let gl = &self.gl;
+    /// Creates a TextModel for the given string that will be at most max_width wide when it is rendered with the given
+    /// scale_y. The max_width is expressed in the OpenGL coordinate space, just like the result of the get_render_width
+    /// method of TextModel.
+    /// 
+    /// The text will be broken into lines at whitespace where needed. Words that are too wide to fit on a line on their own 
+    /// will be broken inside the word. Just like create_text_model, the text can also contain explicit '\n' line breaks.
+    /// Use the get_line_count and get_line_widths methods of the returned TextModel to find out how the text was wrapped.
+    /// 
+    /// Note that the wrapping depends on the current aspect ratio of the canvas, so the TextModel should be recreated when
+    /// the canvas is resized.
+    /// 
+    /// This method will panic if the buffer of the TextModel can't be created. Use try_create_wrapped_text_model to handle
+    /// that case.
+    pub fn create_wrapped_text_model(self: Rc<Self>, text: &str, max_width: f32, scale_y: f32) -> TextModel {
+        self.try_create_wrapped_text_model(text, max_width, scale_y).expect("Should be able to create the buffer of the text model")
+    }
 
-        let buffer = gl.create_buffer().unwrap();
-        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&buffer));
+    /// Creates a TextModel for the given string, just like create_wrapped_text_model, but returns an error rather than
+    /// panicking if the buffer of the TextModel can't be created.
+    pub fn try_create_wrapped_text_model(self: Rc<Self>, text: &str, max_width: f32, scale_y: f32) -> Result<TextModel, TextError> {
+        let layout = self.layout_wrapped_text(text, max_width, scale_y);
+        self.create_model_from_layout(layout)
+    }
 
-        let mut pos_x = 0;
+    /// Lays out the given text like create_wrapped_text_model would do
+    pub(super) fn layout_wrapped_text(&self, text: &str, max_width: f32, scale_y: f32) -> TextLayout {
+        let paragraphs = text.split('\n').map(|paragraph| self.get_line_characters(paragraph)).collect();
+        self.wrap_paragraphs(paragraphs, max_width, scale_y)
+    }
 
-        let position_floats_per_char = 12;
-        let texture_floats_per_char = 12;
+    /// Lays out the given spans like the create_rich_text_model method of TextRenderer would do. The characters of spans
+    /// with their own Font will be looked up in that Font (see get_mixed_characters).
+    pub(super) fn layout_spans(&self, spans: &[TextSpan]) -> TextLayout {
+        let lines = split_span_lines(spans).into_iter().map(|line| (self.get_mixed_characters(line, spans), true)).collect();
+        TextLayout::from_lines(lines, self.line_spacing)
+    }
 
-        let pos_factor_x = 1.0 / self.max_text_height as f32;
-        let pos_max_y = 1.0;
+    /// Lays out the given spans like the create_wrapped_rich_text_model method of TextRenderer would do
+    pub(super) fn layout_wrapped_spans(&self, spans: &[TextSpan], max_width: f32, scale_y: f32) -> TextLayout {
+        let paragraphs = split_span_lines(spans).into_iter().map(|paragraph| self.get_mixed_characters(paragraph, spans)).collect();
+        self.wrap_paragraphs(paragraphs, max_width, scale_y)
+    }
 
-        let mut buffer_data = vec![0.0; (position_floats_per_char + texture_floats_per_char) * char_counter];
-        let mut char_index = 0;
-        for text_char in text.chars() {
+    /// Wraps the given paragraphs such that every line will be at most max_width wide when it is rendered with the given
+    /// scale_y, and lays out the resulting lines
+    fn wrap_paragraphs(&self, paragraphs: Vec<Vec<LineChar>>, max_width: f32, scale_y: f32) -> TextLayout {
+        let scale_x = scale_y / self.aspect_ratio.get();
+        let max_model_width = (max_width / scale_x).max(0.0);
+        let mut lines = Vec::new();
+        for paragraph in paragraphs {
+            let paragraph_lines = TextLayout::wrap_line(paragraph, max_model_width);
+            let last_index = paragraph_lines.len() - 1;
+            lines.extend(paragraph_lines.into_iter().enumerate().map(|(index, line)| (line, index == last_index)));
+        }
+        TextLayout::from_lines(lines, self.line_spacing)
+    }
 
-            let maybe_texture_char = self.characters[text_char as usize];
-            
-            match maybe_texture_char {
-                Some(texture_char) => {
-                    let offset = char_index * position_floats_per_char;
+    fn create_model_from_layout(self: Rc<Self>, layout: TextLayout) -> Result<TextModel, TextError> {
+        let buffer_data = self.create_buffer_data(&layout, HorizontalAlignment::Left, 0.0, &self.get_page_sizes());
+        let instance_array = match self.is_instanced() {
+            true => Some(InstanceArray::new(Rc::clone(&self.backend))?),
+            false => None
+        };
+        let buffer = self.backend.create_buffer()?;
+        self.backend.upload_buffer(buffer, &buffer_data);
+
+        Ok(TextModel::new(self, buffer, instance_array, layout, buffer_data.len()))
+    }
 
-                    let min_x = pos_x as f32 * pos_factor_x;
-                    let min_y = 0.0;
-                    pos_x += texture_char.get_width();
-                    let max_x = pos_x as f32 * pos_factor_x;
-                    let max_y = pos_max_y;
+    /// Checks whether the shader program of this Font draws every character as an instance (see TextProgram::is_instanced)
+    pub(super) fn is_instanced(&self) -> bool {
+        self.fill_array.is_some()
+    }
+
+    /// Checks whether the texture coordinates in the vertex data of the TextModel's of this Font are stored as normalized
+    /// u16's rather than floats. This is the compact_texture_coords of the TextRenderer at the time this Font was created.
+    pub(super) fn has_compact_texture_coords(&self) -> bool {
+        self.compact_texture_coords
+    }
 
-                    buffer_data[offset + 0] = min_x;
-                    buffer_data[offset + 1] = min_y;
+    /// Checks whether this Font can blur the shadows of its text. This is true for Font's with a distance field atlas, and
+    /// for Font's with a bitmap atlas that were created while the shadow_blur of the TextRenderer was larger than 0.
+    pub(super) fn has_soft_shadows(&self) -> bool {
+        self.soft_shadows
+    }
 
-                    buffer_data[offset + 2] = max_x;
-                    buffer_data[offset + 3] = min_y;
+    /// Creates the content of the buffer of a TextModel with the given layout: instance data if the shader program of this
+    /// Font is instanced and vertex data otherwise. See the create_vertex_data method of TextLayout for the parameters.
+    pub(super) fn create_buffer_data(&self, layout: &TextLayout, alignment: HorizontalAlignment, region_width: f32, page_sizes: &[(u32, u32)]) -> Vec<u8> {
+        match self.is_instanced() {
+            true => float_bytes(&layout.create_instance_data(alignment, region_width, page_sizes)),
+            false => layout.create_vertex_data(alignment, region_width, page_sizes, self.compact_texture_coords)
+        }
+    }
 
-                    buffer_data[offset + 4] = max_x;
-                    buffer_data[offset + 5] = max_y;
+    /// Looks up the Character for every char of the line, and the kerning between every pair of successive characters.
+    /// Characters this Font can't draw will be replaced by the fallback character of this Font, or skipped if this Font
+    /// doesn't have a fallback character.
+    fn get_line_characters(&self, line: &str) -> Vec<LineChar> {
+        self.get_span_characters(line.chars().map(|text_char| (text_char, 0)).collect())
+    }
 
-                    buffer_data[offset + 6] = max_x;
-                    buffer_data[offset + 7] = max_y;
+    /// Looks up the Character of every char of the line like get_line_characters, but every char is paired with the index
+    /// of the TextSpan it came from. The kerning between characters of different spans is taken into account as well.
+    fn get_span_characters(&self, line: Vec<(char, usize)>) -> Vec<LineChar> {
+        let mut characters = Vec::with_capacity(line.len());
+        let mut previous_char = None;
+        for (text_char, span) in line {
 
-                    buffer_data[offset + 8] = min_x;
-                    buffer_data[offset + 9] = max_y;
+            // Windows line endings should be handled the same way as normal line endings
+            if text_char == '\r' {
+                continue;
+            }
 
-                    buffer_data[offset + 10] = min_x;
-                    buffer_data[offset + 11] = min_y;
+            match self.get_character(text_char) {
+                Some((drawn_char, texture_char)) => {
+                    characters.push(LineChar {
+                        text_char,
+                        character: texture_char,
+                        span,
+                        pixel_size: 1.0 / self.max_text_height as f32,
+                        baseline_offset: 0.0,
+                        kerning: previous_char.map_or(0.0, |previous_char| self.get_kerning(previous_char, drawn_char))
+                    });
+                    previous_char = Some(drawn_char);
                 }, None => print(&format!("No texture for character {}", text_char))
             };
+        }
+        characters
+    }
 
-            char_index += 1;
+    /// Looks up the Character of every char of the line like get_span_characters, but the chars of spans with their own Font
+    /// will be looked up in that Font. The characters of other Font's are scaled such that the line height of their Font
+    /// matches the line height of this Font, and moved such that their baseline matches the baseline of this Font. There is
+    /// no kerning between characters of different Font's.
+    fn get_mixed_characters(&self, line: Vec<(char, usize)>, spans: &[TextSpan]) -> Vec<LineChar> {
+        let mut characters = Vec::with_capacity(line.len());
+        let own_baseline = self.baseline / self.max_text_height as f32;
+        for font_chars in line.chunk_by(|left, right| std::ptr::eq(self.get_span_font(&spans[left.1]), self.get_span_font(&spans[right.1]))) {
+            let font = self.get_span_font(&spans[font_chars[0].1]);
+            let baseline_offset = own_baseline - font.baseline / font.max_text_height as f32;
+            characters.extend(font.get_span_characters(font_chars.to_vec()).into_iter().map(|line_char| LineChar {
+                baseline_offset,
+                ..line_char
+            }));
         }
+        characters
+    }
 
-        let max_width = pos_x as f32 * pos_factor_x;
+    /// Gets the Font that will draw the characters of the given span: the Font of the span, or this Font if the span doesn't
+    /// have its own Font
+    pub(super) fn get_span_font<'a>(&'a self, span: &'a TextSpan) -> &'a Font {
+        span.font.as_deref().unwrap_or(self)
+    }
 
-        let mut char_index = 0;
-        for text_char in text.chars() {
-            let maybe_texture_char = self.characters[text_char as usize];
-            
-            match maybe_texture_char {
-                Some(texture_char) => {
-                    let left_u = texture_char.get_left_u();
-                    let bottom_v = texture_char.get_bottom_v();
-                    let right_u = texture_char.get_right_u();
-                    let top_v = texture_char.get_top_v();
-                    let offset = position_floats_per_char * char_counter + char_index * texture_floats_per_char;
+    /// Gets the Character for the given char, together with the char that will actually be drawn. If the char is not yet
+    /// in the atlas, it will be added if this Font has a dynamic atlas. If that is not possible, the fallback character
+    /// will be drawn instead.
+    fn get_character(&self, text_char: char) -> Option<(char, Character)> {
+        let maybe_character = self.characters.borrow().get(&text_char).copied();
+        maybe_character.or_else(|| self.add_character(text_char)).map(|character| (text_char, character)).or_else(|| {
+            let fallback = self.fallback_char?;
+            self.characters.borrow().get(&fallback).map(|character| (fallback, *character))
+        })
+    }
 
-                    buffer_data[offset + 0] = left_u;
-                    buffer_data[offset + 1] = bottom_v;
+    /// Gets the kerning (in pixels of the backing texture) between the given pair of characters. The kerning will only be
+    /// asked from the rasterizer the first time, after which it will be remembered.
+    fn get_kerning(&self, left: char, right: char) -> f32 {
+        let maybe_kerning = self.kerning_pairs.borrow().get(&(left, right)).copied();
+        match maybe_kerning {
+            Some(kerning) => kerning,
+            None => {
+                let kerning = self.rasterizer.get_kerning(left, right);
+                self.kerning_pairs.borrow_mut().insert((left, right), kerning);
+                kerning
+            }
+        }
+    }
 
-                    buffer_data[offset + 2] = right_u;
-                    buffer_data[offset + 3] = bottom_v;
+    /// Gets the character that will be drawn in place of characters this Font can't draw, or None if such characters
+    /// will be skipped. This is the fallback_char of the TextRenderer at the time this Font was created.
+    pub fn get_fallback_char(&self) -> Option<char> {
+        self.fallback_char
+    }
 
-                    buffer_data[offset + 4] = right_u;
-                    buffer_data[offset + 5] = top_v;
+    /// Gets the advance (in pixels of the backing texture) of the given char, together with the char that will actually be
+    /// drawn, like get_character. Unlike get_character, this won't add the char to the atlas: the advance of chars that are
+    /// not yet in the atlas is asked from the rasterizer, which doesn't need to rasterize them for that.
+    fn get_advance(&self, text_char: char) -> Option<(char, f32)> {
+        if let Some(character) = self.characters.borrow().get(&text_char) {
+            return Some((text_char, character.get_advance()));
+        }
 
-                    buffer_data[offset + 6] = right_u;
-                    buffer_data[offset + 7] = top_v;
+        let can_add = self.dynamic_atlas && !self.unavailable_characters.borrow().contains(&text_char);
+        let maybe_advance = if can_add { self.rasterizer.get_advance(text_char) } else { None };
+        maybe_advance.map(|advance| (text_char, advance)).or_else(|| {
+            let fallback = self.fallback_char?;
+            self.characters.borrow().get(&fallback).map(|character| (fallback, character.get_advance()))
+        })
+    }
 
-                    buffer_data[offset + 8] = left_u;
-                    buffer_data[offset + 9] = top_v;
+    /// Rasterizes the given character and adds it to the last atlas page of this Font, or to a new page if the last page is
+    /// full. Returns None if this Font doesn't have a dynamic atlas or its rasterizer can't draw the character. The backing
+    /// texture will be updated the next time this Font is used for rendering.
+    fn add_character(&self, text_char: char) -> Option<Character> {
+        if !self.dynamic_atlas || self.unavailable_characters.borrow().contains(&text_char) {
+            return None;
+        }
 
-                    buffer_data[offset + 10] = left_u;
-                    buffer_data[offset + 11] = bottom_v;
-                }, None => print(&format!("No texture for character {}", text_char))
-            };
+        let maybe_character = match self.rasterizer.rasterize(text_char) {
+            Some(bitmap) => {
+                let maybe_character = self.add_to_pages(&bitmap);
+                if maybe_character.is_none() {
+                    print(&format!("No room in the atlas for character {}", text_char));
+                }
+                maybe_character
+            }, None => {
+                print(&format!("Can't rasterize character {}", text_char));
+                None
+            }
+        };
+
+        match maybe_character {
+            Some(character) => { self.characters.borrow_mut().insert(text_char, character); },
+            None => { self.unavailable_characters.borrow_mut().insert(text_char); }
+        };
+        maybe_character
+    }
 
-            char_index += 1;
+    /// Adds the given bitmap to the last atlas page of this Font. If that page is full, a new page will be created for it.
+    fn add_to_pages(&self, bitmap: &GlyphBitmap) -> Option<Character> {
+        let mut pages = self.pages.borrow_mut();
+        let last_page = pages.len() - 1;
+        if let Some(character) = add_to_atlas(&mut pages[last_page].atlas, last_page, bitmap) {
+            return Some(character);
         }
 
-        // Really? Is there no safe way to do this?
-        unsafe {
-            let js_array = Float32Array::view(&buffer_data);
-            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &js_array, GL::STATIC_DRAW);
+        // Make sure the character fits on an empty page before creating the texture of the new page
+        let mut atlas = create_page_atlas(self.atlas_options, self.rasterizer.get_empty_pixel(), 8 * self.max_text_height);
+        let character = add_to_atlas(&mut atlas, last_page + 1, bitmap)?;
+        match self.backend.create_texture() {
+            Ok(texture) => {
+                pages.push(AtlasPage { atlas, texture });
+                Some(character)
+            }, Err(error) => {
+                print(&format!("Can't create a new atlas page: {}", error));
+                None
+            }
         }
+    }
 
-        TextModel::new(Rc::clone(&self), buffer, char_counter, max_width)
+    /// Gets the current size (width, height) of every backing texture of this Font, in pixels
+    pub(super) fn get_page_sizes(&self) -> Vec<(u32, u32)> {
+        self.pages.borrow().iter().map(|page| (page.atlas.get_width(), page.atlas.get_height())).collect()
     }
 
-    pub(super) fn set_current(&self){
-        self.gl.active_texture(GL::TEXTURE0);
-        self.gl.bind_texture(GL::TEXTURE_2D, Some(&self.texture));
-        let shader = self.shader_program.borrow();
-        shader.set_texture_sampler(0);
+    /// Measures the given text as if it would be rendered with the given scale_y, without creating a TextModel. This is
+    /// much cheaper than creating a TextModel and calling its get_render_width method because it doesn't need to create
+    /// or upload any buffer. See the description of TextMetrics for the meaning of the results.
+    /// 
+    /// Just like create_text_model, the text can consist of multiple lines, separated by '\n' characters. Note that the
+    /// measured width depends on the current aspect ratio of the canvas. Measuring characters that are not yet in the atlas
+    /// of this Font won't add them to the atlas.
+    pub fn measure(&self, text: &str, scale_y: f32) -> TextMetrics {
+        let scale_x = scale_y / self.aspect_ratio.get();
+        let pos_factor_x = scale_x / self.max_text_height as f32;
+
+        let mut advances = Vec::with_capacity(text.len());
+        let mut line_count = 1;
+        let mut line_width: f32 = 0.0;
+        let mut max_line_width: f32 = 0.0;
+        let mut previous_char = None;
+
+        for text_char in text.chars() {
+            if text_char == '\n' {
+                line_count += 1;
+                max_line_width = max_line_width.max(line_width);
+                line_width = 0.0;
+                previous_char = None;
+                advances.push(0.0);
+                continue;
+            }
+
+            // Unlike get_line_characters, this doesn't print missing characters because it may be called very often
+            let maybe_advance = match text_char {
+                '\r' => None,
+                _ => self.get_advance(text_char)
+            };
+            let advance = match maybe_advance {
+                Some((drawn_char, advance)) => {
+                    let kerning = previous_char.map_or(0.0, |previous_char| self.get_kerning(previous_char, drawn_char));
+                    previous_char = Some(drawn_char);
+                    kerning + advance
+                }, None => 0.0
+            };
+            line_width += advance;
+            advances.push(advance * pos_factor_x);
+        }
+        max_line_width = max_line_width.max(line_width);
+
+        let ascent = scale_y * self.baseline / self.max_text_height as f32;
+        let height = scale_y * (line_count as f32 + (line_count - 1) as f32 * self.line_spacing);
+        TextMetrics::new(max_line_width * pos_factor_x, height, ascent, scale_y - ascent, advances)
     }
 
-    /// Fills the given region with the given color.
+    /// Gets the metrics of the given character when it would be rendered with the given scale_y. The metrics will be
+    /// expressed in the OpenGL coordinate space, just like the results of the measure method. See the description of
+    /// GlyphMetrics for the meaning of the results.
     /// 
-    /// Must only be used during the text render phase
-    pub fn fill_rect(&self, region: Region, color: Color){
+    /// If this Font can't draw the character, the metrics of the fallback character will be returned instead. If this Font
+    /// doesn't have a fallback character, None will be returned. Unlike the measure method, this method needs the rasterized
+    /// character, so a character that is not yet in a dynamic atlas will be added to it.
+    pub fn get_glyph_metrics(&self, character: char, scale_y: f32) -> Option<GlyphMetrics> {
+        let scale_x = scale_y / self.aspect_ratio.get();
+        let pixel_height = self.max_text_height as f32;
+        self.get_character(character).map(|(_drawn_char, texture_char)| {
+            texture_char.get_metrics().scale(scale_x / pixel_height, scale_y / pixel_height)
+        })
+    }
+
+    /// Gets the ascent, descent, line gap and decoration metrics of this Font when it would be rendered with the given
+    /// scale_y. The metrics will be expressed in the OpenGL coordinate space, just like the results of the measure method.
+    pub fn get_font_metrics(&self, scale_y: f32) -> FontMetrics {
+        self.font_metrics.scale(scale_y / self.max_text_height as f32)
+    }
 
-        let need_set_font;
+    pub(super) fn layout_text(&self, text: &str) -> TextLayout {
+        let lines = text.split('\n').map(|line| (self.get_line_characters(line), true)).collect();
+        TextLayout::from_lines(lines, self.line_spacing)
+    }
+
+    /// Binds the backing texture of the given atlas page and uses the shader program of this Font
+    pub(super) fn set_current(&self, page: usize){
+        self.backend.bind_texture(0, self.pages.borrow()[page].texture);
+        self.current_page.set(page);
+        let shader = self.shader_program.borrow();
+        shader.use_program();
+        shader.set_texture_sampler(0);
+    }
+
+    /// Makes sure this Font is the current font of its TextRenderer, that the given atlas page is bound and that the backing
+    /// textures contain all characters that were added to the atlas pages. This should be called before every draw call with
+    /// this Font.
+    pub(super) fn prepare_rendering(&self, page: usize){
+        let mut need_set_font;
         {
             let selected_font = self.selected_font.get();
             match selected_font {
-                Some(font_id) => need_set_font = font_id != self.id,
+                Some(font_id) => need_set_font = font_id != self.id || self.current_page.get() != page,
                 None => need_set_font = true
             };
         }
 
+        // Uploading might have changed the bound texture
+        if self.upload_pages() {
+            need_set_font = true;
+        }
+
         if need_set_font {
-            self.set_current();
+            self.set_current(page);
             self.selected_font.set(Some(self.id));
         }
+    }
 
-        let mut shader = self.shader_program.borrow_mut();
+    /// Uploads the atlas pages that changed since they were last uploaded to their backing textures. Returns true if any
+    /// texture was uploaded (which may have changed the bound texture).
+    pub(super) fn upload_pages(&self) -> bool {
+        let mut uploaded = false;
+        for atlas_page in self.pages.borrow_mut().iter_mut() {
+            let atlas = &mut atlas_page.atlas;
+            if atlas.is_dirty() {
+
+                // Just ignore upload failures since the characters that were already uploaded can still be drawn
+                let _ = self.backend.upload_texture(atlas_page.texture, atlas.get_width(), atlas.get_height(), atlas.get_pixels());
+                atlas.set_uploaded();
+                uploaded = true;
+            }
+        }
+        uploaded
+    }
+
+    /// Gets the backing texture of the given atlas page
+    pub(super) fn get_page_texture(&self, page: usize) -> TextureHandle {
+        self.pages.borrow()[page].texture
+    }
 
-        let gl = &self.gl;
-        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.fill_buffer));
-        let num_components = 2;
+    /// Fills the given region with the given color.
+    /// 
+    /// Must only be used during the text render phase
+    pub fn fill_rect(&self, region: Region, color: Color){
+        self.fill_area(region.get_float_min_x(), region.get_float_min_y(), region.get_float_width(), region.get_float_height(), color);
+    }
 
-        gl.vertex_attrib_pointer_with_i32(shader.get_relative_position() as u32, num_components, WebGlRenderingContext::FLOAT, false, 0, 0);
-        gl.enable_vertex_attrib_array(shader.get_relative_position() as u32);
+    /// Fills the rectangle with the given bottom-left corner and size (in the OpenGL coordinate space) with the given color
+    pub(super) fn fill_area(&self, min_x: f32, min_y: f32, width: f32, height: f32, color: Color){
 
-        let f32_size = 4;
-        gl.vertex_attrib_pointer_with_i32(shader.get_texture_coords() as u32, num_components, WebGlRenderingContext::FLOAT, false, 0, f32_size * num_components * 6);
-        gl.enable_vertex_attrib_array(shader.get_texture_coords() as u32);
+        // The bottom-right corner of every page is either empty space or the margin of a character, so any page will do
+        self.prepare_rendering(self.current_page.get());
 
+        let mut shader = self.shader_program.borrow_mut();
         shader.set_background_color(color);
-        shader.set_screen_position(region.get_float_min_x(), region.get_float_min_y());
-        shader.set_scale(region.get_float_width(), region.get_float_height());
-        gl.draw_arrays(GL::TRIANGLES, 0, 6);
+        shader.set_screen_position(min_x, min_y);
+        shader.set_scale(width, height);
+
+        let backend = &self.backend;
+        match &self.fill_array {
+            Some(fill_array) => {
+                fill_array.bind(&shader, self.fill_buffer, 0);
+                backend.draw_instanced_triangles(0, 6, 1);
+                backend.bind_vertex_array(None);
+            }, None => {
+                let num_components = 2;
+
+                backend.set_vertex_attribute(self.fill_buffer, shader.get_relative_position(), num_components, AttributeType::Float, 0, 0);
+
+                let f32_size = 4;
+                backend.set_vertex_attribute(self.fill_buffer, shader.get_texture_coords(), num_components, AttributeType::Float, 0, f32_size * num_components * 6);
+
+                backend.draw_triangles(0, 6);
+            }
+        };
     }
 
-    pub(super) fn set_aspect_ratio(&self, aspect_ratio: f32){
-        self.aspect_ratio.set(aspect_ratio);
+    pub(super) fn set_surface_size(&self, width: u32, height: u32){
+        self.aspect_ratio.set(width as f32 / height as f32);
+        self.surface_height.set(height as f32);
+    }
+
+    /// Gets the smoothing that should be used to render distance field text with the given scale_y. The smoothing will be
+    /// chosen such that the edges of the characters will be blurred over roughly 1 pixel of the canvas.
+    pub(super) fn get_smoothing(&self, scale_y: f32) -> f32 {
+        match self.distance_field {
+            Some(distance_field) => {
+                let screen_pixels = 0.5 * scale_y * self.surface_height.get();
+                let texels_per_pixel = self.max_text_height as f32 / screen_pixels;
+                0.5 * texels_per_pixel / (2.0 * distance_field.spread)
+            }, None => 0.0
+        }
+    }
+
+    /// Gets the distance (in model space) between the bottom of a line and its baseline
+    pub(super) fn get_baseline_height(&self) -> f32 {
+        1.0 - self.baseline / self.max_text_height as f32
+    }
+
+    /// Gets the line_width that will be used by the render method of TextModel's of this Font
+    pub(super) fn get_default_line_width(&self) -> f32 {
+        match self.distance_field {
+            Some(distance_field) => distance_field.line_width,
+            None => 0.0
+        }
+    }
+
+    /// Converts the given line_width (as fraction of the font size) to the stroke width that the distance field shader
+    /// expects, which is a fraction of the distance range of the distance field
+    pub(super) fn get_stroke_width(&self, line_width: f32) -> f32 {
+        match self.distance_field {
+            Some(distance_field) => line_width * distance_field.font_size / (2.0 * distance_field.spread),
+            None => 0.0
+        }
     }
 }
 
+/// Splits the text of the given spans into lines at the '\n' characters. Every char of a line is paired with the index of
+/// the span it came from, so a single line can contain the chars of multiple spans and a single span can cover multiple
+/// lines.
+fn split_span_lines(spans: &[TextSpan]) -> Vec<Vec<(char, usize)>> {
+    let mut lines = vec![Vec::new()];
+    for (span_index, span) in spans.iter().enumerate() {
+        for text_char in span.text.chars() {
+            match text_char {
+                '\n' => lines.push(Vec::new()),
+                _ => lines.last_mut().expect("There is always at least 1 line").push((text_char, span_index))
+            };
+        }
+    }
+    lines
+}
+
+/// Creates an empty atlas for a new page with (roughly) the given initial size, whose pixels are cleared to empty_pixel
+fn create_page_atlas(atlas_options: AtlasOptions, empty_pixel: [u8; 4], initial_size: u32) -> GlyphAtlas {
+    let max_size = atlas_options.max_size.expect("The max_size of the atlas options should have been resolved");
+    GlyphAtlas::new(initial_size, initial_size, atlas_options.padding, atlas_options.power_of_two, max_size, empty_pixel)
+}
+
+/// Adds the given bitmap to the atlas of the given page and returns the Character that describes its position, or None
+/// if the atlas is full
+fn add_to_atlas(atlas: &mut GlyphAtlas, page: usize, bitmap: &GlyphBitmap) -> Option<Character> {
+    let (min_x, min_y) = atlas.add(bitmap)?;
+    Some(Character::new(page, min_x, min_y, bitmap))
+}
+
 impl Drop for Font {
 
     fn drop(&mut self){
-        self.gl.delete_texture(Some(&self.texture));
+        for page in self.pages.borrow().iter() {
+            self.backend.delete_texture(page.texture);
+        }
+        self.backend.delete_buffer(self.fill_buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use super::super::*;
+    use super::super::testing::*;
+
+    /// Gets the bottom-right pixel of the first atlas page of the given Font, which is used to fill areas
+    fn get_corner_pixel(backend: &RecordingBackend, font: &Font) -> Vec<u8> {
+        let texture = font.pages.borrow()[0].texture;
+        let (width, height, pixels) = backend.get_texture_data(texture).expect("The Font should have a texture");
+        let index = (4 * (width * height - 1)) as usize;
+        pixels[index..index + 4].to_vec()
+    }
+
+    #[test]
+    fn atlas_is_cleared_to_the_empty_pixel_of_its_rasterizer() {
+        let (backend, mut renderer) = create_test_renderer(false);
+
+        // The last row of characters is not full, so the bottom-right corner is empty
+        renderer.all_chars = "Wi.".to_string();
+        let bitmap_font = add_test_font(&mut renderer);
+        assert_eq!(get_corner_pixel(&backend, &bitmap_font), EMPTY_PIXEL);
+
+        renderer.atlas_mode = AtlasMode::DistanceField { font_size: TEST_FONT_SIZE, spread: DEFAULT_DISTANCE_FIELD_SPREAD };
+        let distance_font = add_test_font(&mut renderer);
+        assert_eq!(get_corner_pixel(&backend, &distance_font), EMPTY_DISTANCE_PIXEL);
+    }
+
+    #[test]
+    fn measure_matches_the_text_model() {
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        backend.clear_calls();
+
+        // Measuring the text shouldn't touch the backend at all
+        let text = "AVAST\nWaves";
+        let metrics = font.measure(text, 0.1);
+        assert!(backend.get_calls().is_empty());
+
+        let model = Rc::clone(&font).create_text_model(text);
+        assert!((metrics.get_width() - model.get_render_width(0.1)).abs() < 0.0001);
+        assert!((metrics.get_height() - model.get_render_height(0.1)).abs() < 0.0001);
+    }
+
+    #[test]
+    fn measure_does_not_add_characters_to_the_atlas() {
+        let (backend, mut renderer) = create_test_renderer(false);
+        renderer.all_chars = String::new();
+        let font = add_test_font(&mut renderer);
+        backend.clear_calls();
+
+        let text = "AVAST é\nWaves";
+        let measured = font.measure(text, 0.1);
+        assert!(font.characters.borrow().is_empty());
+        assert!(backend.get_calls().is_empty());
+
+        // The advances from the rasterizer must be the same as the advances of the rasterized characters
+        let _model = Rc::clone(&font).create_text_model(text);
+        assert!(!font.characters.borrow().is_empty());
+        let rasterized = font.measure(text, 0.1);
+        assert!((measured.get_width() - rasterized.get_width()).abs() < 0.0001);
+    }
+
+    #[test]
+    fn kerning_is_applied_in_measure_and_layout() {
+        let (_backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        let kerning = font.get_kerning('A', 'V');
+        assert!(kerning < 0.0);
+
+        // The width of a character in the measurements is proportional to its width in pixels
+        let (_, advance_a) = font.get_advance('A').unwrap();
+        let width_a = font.measure("A", 1.0).get_width();
+        let width_v = font.measure("V", 1.0).get_width();
+        let width_av = font.measure("AV", 1.0).get_width();
+        assert!((width_av - (width_a + width_v + kerning * width_a / advance_a)).abs() < 0.0001);
+
+        let layout = font.layout_text("AV");
+        let pixel_size = 1.0 / font.max_text_height as f32;
+        assert!((layout.quads[1].advance_min_x - (advance_a + kerning) * pixel_size).abs() < 0.0001);
+        assert!((layout.width * width_a / layout.quads[0].advance_max_x - width_av).abs() < 0.0001);
+    }
+
+    #[test]
+    fn characters_of_span_fonts_are_scaled_and_aligned() {
+        let (_backend, mut renderer) = create_test_renderer(false);
+        renderer.all_chars = String::new();
+        let font = add_test_font(&mut renderer);
+        renderer.font_size = 20;
+        let small_font = add_test_font(&mut renderer);
+        let colors = test_colors(Color::from_rgb(0, 0, 0));
+        let spans = vec![TextSpan::new("Ab", colors), TextSpan::with_font("cd", colors, &small_font), TextSpan::new("e", colors)];
+
+        let line = vec![('A', 0), ('b', 0), ('c', 1), ('d', 1), ('e', 2)];
+        let characters = font.get_mixed_characters(line, &spans);
+        let text: String = characters.iter().map(|line_char| line_char.text_char).collect();
+        assert_eq!(text, "Abcde");
+
+        // Every character is measured in pixels of its own Font, which has the same line height in model space
+        let own_pixel_size = 1.0 / font.max_text_height as f32;
+        let small_pixel_size = 1.0 / small_font.max_text_height as f32;
+        assert!(small_pixel_size > own_pixel_size);
+        let pixel_sizes: Vec<_> = characters.iter().map(|line_char| line_char.pixel_size).collect();
+        assert_eq!(pixel_sizes, vec![own_pixel_size, own_pixel_size, small_pixel_size, small_pixel_size, own_pixel_size]);
+
+        // The characters of the small Font are moved such that their baseline matches the baseline of the Font
+        let baseline_offset = font.baseline / font.max_text_height as f32 - small_font.baseline / small_font.max_text_height as f32;
+        let baseline_offsets: Vec<_> = characters.iter().map(|line_char| line_char.baseline_offset).collect();
+        assert_eq!(baseline_offsets, vec![0.0, 0.0, baseline_offset, baseline_offset, 0.0]);
+
+        // The characters of a span with its own Font are only added to the atlas of that Font
+        assert!(small_font.characters.borrow().contains_key(&'d'));
+        assert!(!font.characters.borrow().contains_key(&'d'));
+
+        // There is no kerning between characters of different Font's
+        assert_eq!(characters[2].kerning, 0.0);
+        assert_eq!(characters[4].kerning, 0.0);
     }
-}
\ No newline at end of file
+
+    fn count_texture_uploads(backend: &RecordingBackend) -> usize {
+        backend.get_calls().iter().filter(|call| matches!(call, BackendCall::UploadTexture { .. })).count()
+    }
+
+    #[test]
+    fn missing_characters_are_added_on_demand() {
+        let (backend, mut renderer) = create_test_renderer(false);
+        renderer.all_chars = String::new();
+        let font = add_test_font(&mut renderer);
+        assert!(font.characters.borrow().is_empty());
+
+        let model = Rc::clone(&font).create_text_model("abca");
+        assert_eq!(font.characters.borrow().len(), 3);
+
+        // The backing texture is only uploaded when it is used after new characters were added
+        renderer.start_rendering();
+        backend.clear_calls();
+        model.render(0.0, 0.0, 0.1, test_colors(Color::from_rgb(0, 0, 0)));
+        assert_eq!(count_texture_uploads(&backend), 1);
+        model.render(0.0, 0.0, 0.1, test_colors(Color::from_rgb(0, 0, 0)));
+        assert_eq!(count_texture_uploads(&backend), 1);
+    }
+
+    #[test]
+    fn static_atlas_only_has_all_chars() {
+        let (_backend, mut renderer) = create_test_renderer(false);
+        renderer.dynamic_atlas = false;
+        renderer.all_chars = "ab".to_string();
+        let font = add_test_font(&mut renderer);
+        assert_eq!(font.characters.borrow().len(), 2);
+        assert_eq!(font.layout_text("abc").quads.len(), 2);
+        assert_eq!(font.characters.borrow().len(), 2);
+    }
+
+    #[test]
+    fn fallback_char_replaces_characters_that_can_not_be_drawn() {
+        let (_backend, mut renderer) = create_test_renderer(false);
+        let font_without_fallback = add_test_font(&mut renderer);
+        renderer.fallback_char = Some('?');
+        let font = add_test_font(&mut renderer);
+
+        // The test font doesn't have any Chinese characters
+        let layout = font.layout_text("a\u{4e2d}b");
+        assert_eq!(layout.quads.len(), 3);
+        let fallback_metrics = font.characters.borrow()[&'?'].get_metrics();
+        assert_eq!(layout.quads[1].character.get_metrics(), fallback_metrics);
+        assert!(font.unavailable_characters.borrow().contains(&'\u{4e2d}'));
+        assert!(!font.characters.borrow().contains_key(&'\u{4e2d}'));
+
+        assert_eq!(font_without_fallback.layout_text("a\u{4e2d}b").quads.len(), 2);
+        assert_eq!(font_without_fallback.get_glyph_metrics('\u{4e2d}', 0.1), None);
+    }
+
+    #[test]
+    fn high_code_points_are_supported() {
+        let (_backend, mut renderer) = create_test_renderer(false);
+        renderer.fallback_char = Some('?');
+        let font = add_test_font(&mut renderer);
+
+        let text = "\u{10FFFF}\u{1F600}\u{E000}\u{FFFD}";
+        assert_eq!(font.layout_text(text).quads.len(), 4);
+        assert!(font.measure(text, 0.1).get_width() > 0.0);
+        assert_eq!(font.get_glyph_metrics('\u{10FFFF}', 0.1), font.get_glyph_metrics('?', 0.1));
+    }
+
+    #[test]
+    fn new_pages_are_created_when_the_atlas_is_full() {
+        let (backend, mut renderer) = create_test_renderer(false);
+        renderer.atlas_options.max_size = Some(64);
+        renderer.all_chars = String::new();
+        let font = add_test_font(&mut renderer);
+        assert_eq!(font.pages.borrow().len(), 1);
+
+        let model = Rc::clone(&font).create_text_model("ABCDEFGHIJ");
+        let page_count = font.pages.borrow().len();
+        assert!(page_count > 1);
+        assert_eq!(font.layout_text("ABCDEFGHIJ").quads.len(), 10);
+        assert!(font.get_page_sizes().iter().all(|&(width, height)| width <= 64 && height <= 64));
+
+        // Every page needs its own draw call
+        renderer.start_rendering();
+        backend.clear_calls();
+        model.render(0.0, 0.0, 0.1, test_colors(Color::from_rgb(0, 0, 0)));
+        assert_eq!(backend.get_draw_count(), page_count);
+        assert_eq!(count_texture_uploads(&backend), page_count);
+    }
+}
@@ -0,0 +1,209 @@
+use wasmuri_core::*;
+
+use super::alignment::HorizontalAlignment;
+use super::backend::*;
+use super::error::TextError;
+use super::layout::TextLayout;
+use super::model::{
+    BufferedText,
+    compute_page_draws,
+    draw_buffered_text
+};
+use super::shaders::InstanceArray;
+use super::Font;
+
+use std::rc::Rc;
+
+/// The text that was queued with the draw_text method of TextRenderer during the current frame. All queued text will be
+/// drawn from a single streaming buffer that is reused every frame, and will only be reallocated when the text of a frame
+/// doesn't fit in it.
+pub(super) struct ImmediateText {
+
+    backend: Rc<dyn TextBackend>,
+
+    /// The streaming buffer, which will be created the first time text is flushed
+    buffer: Option<BufferHandle>,
+
+    /// The vertex array object for the streaming buffer, which is only needed if the backend supports instancing
+    instance_array: Option<InstanceArray>,
+
+    /// The number of bytes the buffer can hold
+    buffer_capacity: usize,
+
+    entries: Vec<ImmediateEntry>,
+
+    /// The vertex data of the last flush, kept to avoid allocating it again every frame
+    vertex_data: Vec<u8>
+}
+
+struct ImmediateEntry {
+
+    font: Rc<Font>,
+    layout: TextLayout,
+
+    offset_x: f32,
+    offset_y: f32,
+    scale_y: f32,
+    colors: TextColors
+}
+
+impl ImmediateText {
+
+    pub fn new(backend: Rc<dyn TextBackend>) -> ImmediateText {
+        ImmediateText {
+            backend,
+            buffer: None,
+            instance_array: None,
+            buffer_capacity: 0,
+            entries: Vec::new(),
+            vertex_data: Vec::new()
+        }
+    }
+
+    /// Lays out the given text and queues it to be drawn during the next flush
+    pub fn push(&mut self, font: &Rc<Font>, text: &str, offset_x: f32, offset_y: f32, scale_y: f32, colors: TextColors){
+        let layout = font.layout_text(text);
+
+        // There is nothing to draw for empty text, not even the background
+        if !layout.quads.is_empty() {
+            self.entries.push(ImmediateEntry {
+                font: Rc::clone(font),
+                layout,
+                offset_x,
+                offset_y,
+                scale_y,
+                colors
+            });
+        }
+    }
+
+    /// Draws all queued text and clears the queue
+    pub fn flush(&mut self){
+        if self.entries.is_empty() {
+            return;
+        }
+
+        let buffer = match self.buffer {
+            Some(buffer) => buffer,
+            None => match self.create_buffer() {
+                Ok(buffer) => buffer,
+                Err(error) => {
+                    print(&format!("Can't create the buffer for immediate text: {}", error));
+                    self.entries.clear();
+                    return;
+                }
+            }
+        };
+
+        // The buffer data is computed now because the atlas pages may have grown since the text was queued
+        let mut first_bytes = Vec::with_capacity(self.entries.len());
+        self.vertex_data.clear();
+        for entry in &self.entries {
+            first_bytes.push(self.vertex_data.len());
+            self.vertex_data.extend(entry.font.create_buffer_data(&entry.layout, HorizontalAlignment::Left, 0.0, &entry.font.get_page_sizes()));
+        }
+
+        if self.vertex_data.len() > self.buffer_capacity {
+            self.buffer_capacity = self.vertex_data.len().max(2 * self.buffer_capacity);
+            self.backend.allocate_buffer(buffer, self.buffer_capacity, BufferUsage::Dynamic);
+        }
+        self.backend.update_buffer(buffer, 0, &self.vertex_data);
+
+        for (entry, first_byte) in self.entries.drain(..).zip(first_bytes) {
+            let page_draws = compute_page_draws(&entry.layout);
+            let text = BufferedText {
+                buffer,
+                instance_array: self.instance_array.as_ref(),
+                first_byte,
+                page_draws: &page_draws,
+                block_min_x: 0.0,
+                block_width: entry.layout.width,
+                block_height: entry.layout.height,
+                decoration_lines: &[],
+                shadow: None
+            };
+            let line_width = entry.font.get_default_line_width();
+            draw_buffered_text(&entry.font, &text, entry.offset_x, entry.offset_y, entry.scale_y, entry.colors, line_width);
+        }
+    }
+
+    /// Creates the streaming buffer, and its vertex array object if the backend supports instancing
+    fn create_buffer(&mut self) -> Result<BufferHandle, TextError> {
+        if self.backend.supports_instancing() {
+            self.instance_array = Some(InstanceArray::new(Rc::clone(&self.backend))?);
+        }
+        let buffer = self.backend.create_buffer()?;
+        self.buffer = Some(buffer);
+        Ok(buffer)
+    }
+}
+
+impl Drop for ImmediateText {
+
+    fn drop(&mut self){
+        if let Some(buffer) = self.buffer {
+            self.backend.delete_buffer(buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use super::super::*;
+    use super::super::testing::*;
+
+    fn get_allocations(backend: &RecordingBackend) -> Vec<usize> {
+        backend.get_calls().iter().filter_map(|call| match call {
+            BackendCall::AllocateBuffer { byte_count, .. } => Some(*byte_count),
+            _ => None
+        }).collect()
+    }
+
+    fn count_buffer_updates(backend: &RecordingBackend) -> usize {
+        backend.get_calls().iter().filter(|call| matches!(call, BackendCall::UpdateBuffer { .. })).count()
+    }
+
+    #[test]
+    fn the_buffer_is_reused_until_the_text_grows() {
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        let colors = test_colors(Color::from_rgb(0, 0, 0));
+
+        renderer.start_rendering();
+        backend.clear_calls();
+        renderer.draw_text(&font, "Hello", 0.0, 0.0, 0.1, colors);
+        renderer.draw_text(&font, "World", 0.0, -0.5, 0.1, colors);
+        renderer.end_rendering();
+        let allocations = get_allocations(&backend);
+        assert_eq!(allocations.len(), 1);
+        assert_eq!(count_buffer_updates(&backend), 1);
+        assert_eq!(backend.get_draw_count(), 2);
+        let capacity = allocations[0];
+        assert_eq!(renderer.immediate_text.buffer_capacity, capacity);
+
+        // The same text fits in the same buffer, and shorter text as well
+        renderer.start_rendering();
+        backend.clear_calls();
+        renderer.draw_text(&font, "Hello", 0.0, 0.0, 0.1, colors);
+        renderer.draw_text(&font, "World", 0.0, -0.5, 0.1, colors);
+        renderer.end_rendering();
+        renderer.draw_text(&font, "Hi", 0.0, 0.0, 0.1, colors);
+        renderer.end_rendering();
+        assert!(get_allocations(&backend).is_empty());
+        assert_eq!(count_buffer_updates(&backend), 2);
+        assert_eq!(backend.get_draw_count(), 3);
+
+        // Text that doesn't fit makes the buffer at least twice as large
+        backend.clear_calls();
+        renderer.draw_text(&font, "Hello World", 0.0, 0.0, 0.1, colors);
+        renderer.end_rendering();
+        assert_eq!(get_allocations(&backend), vec![2 * capacity]);
+
+        // Nothing is uploaded or drawn when no text was queued
+        backend.clear_calls();
+        renderer.end_rendering();
+        assert!(backend.get_calls().is_empty());
+    }
+}
@@ -0,0 +1,535 @@
+use super::alignment::HorizontalAlignment;
+use super::character::Character;
+use super::shaders::FLOATS_PER_INSTANCE;
+
+/// The position of a single character in a TextLayout. The coordinates are in the model space of TextModel's: the height
+/// of a single line is 1.0 and the bottom of the last line is at y = 0.0.
+#[derive(Clone,Copy)]
+pub(super) struct GlyphQuad {
+
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+
+    /// The horizontal range between the pen position before and after this character. Unlike min_x and max_x, the ranges
+    /// of neighbouring characters on the same line don't overlap.
+    pub advance_min_x: f32,
+    pub advance_max_x: f32,
+
+    /// Whether this quad belongs to a whitespace character. Justified text distributes its extra space over these quads.
+    pub is_space: bool,
+
+    /// The index of the TextSpan this character came from, or 0 for text that was not laid out from spans
+    pub span: usize,
+
+    pub character: Character
+}
+
+/// A character of a line that still needs to be laid out
+#[derive(Clone,Copy)]
+pub(super) struct LineChar {
+
+    pub text_char: char,
+    pub character: Character,
+
+    /// The index of the TextSpan this character came from (see GlyphQuad)
+    pub span: usize,
+
+    /// The kerning (in pixels of the backing texture) between the previous character of the line and this character. This
+    /// is ignored for the first character of a line.
+    pub kerning: f32,
+
+    /// The size (in model space) of a pixel of the backing texture of the Font of this character
+    pub pixel_size: f32,
+
+    /// The distance (in model space) this character is moved down, which is used to align the baselines of characters of
+    /// different Font's on the same line
+    pub baseline_offset: f32
+}
+
+impl LineChar {
+
+    /// Gets the horizontal distance (in model space) between the start of the previous character and the start of the next
+    /// character when this character is not the first character of its line
+    fn get_advance(&self) -> f32 {
+        (self.kerning + self.character.get_advance()) * self.pixel_size
+    }
+
+    /// Gets the horizontal distance (in model space) between the start of this character and the start of the next
+    /// character when this character is the first character of its line
+    fn get_first_advance(&self) -> f32 {
+        self.character.get_advance() * self.pixel_size
+    }
+}
+
+/// A single line of a TextLayout
+#[derive(Clone,Copy)]
+pub(super) struct LayoutLine {
+
+    /// The index of the first GlyphQuad of this line in the quads of the TextLayout
+    pub first_quad: usize,
+    pub quad_count: usize,
+
+    /// The width of this line in model space
+    pub width: f32,
+
+    /// The bottom of this line in model space. The top of the line is at min_y + 1.0.
+    pub min_y: f32,
+
+    /// True if this is the last line of a paragraph (so it is followed by a line break or the end of the text). Such lines
+    /// are not stretched when the text is justified.
+    pub ends_paragraph: bool
+}
+
+/// The result of laying out a string with a Font: the positions of all characters that can be drawn, and the size of the
+/// resulting block of text. All values are in model space (see GlyphQuad).
+pub(super) struct TextLayout {
+
+    pub quads: Vec<GlyphQuad>,
+    pub lines: Vec<LayoutLine>,
+
+    pub width: f32,
+    pub height: f32
+}
+
+impl TextLayout {
+
+    /// Lays out the given lines of characters from top to bottom. Every line is a list of LineChar's, together with a bool
+    /// that indicates whether it is the last line of its paragraph. The lines will be separated by line_spacing (as
+    /// fraction of the line height).
+    /// 
+    /// The characters are placed at their advance (plus kerning), but their quads cover the ink of the characters, so
+    /// the quads of neighbouring characters may overlap.
+    pub fn from_lines(lines: Vec<(Vec<LineChar>, bool)>, line_spacing: f32) -> TextLayout {
+        let line_count = lines.len().max(1);
+        let line_distance = 1.0 + line_spacing;
+
+        let mut quads = Vec::new();
+        let mut layout_lines = Vec::with_capacity(line_count);
+        let mut width: f32 = 0.0;
+
+        for (line_index, (line, ends_paragraph)) in lines.into_iter().enumerate() {
+
+            // The first line is at the top of the block of text
+            let min_y = (line_count - 1 - line_index) as f32 * line_distance;
+            let mut pos_x = 0.0;
+            let first_quad = quads.len();
+
+            for (char_index, line_char) in line.into_iter().enumerate() {
+                let pixel_size = line_char.pixel_size;
+                if char_index > 0 {
+                    pos_x += line_char.kerning * pixel_size;
+                }
+                let character = line_char.character;
+                let quad_min_x = pos_x + character.get_offset_x() as f32 * pixel_size;
+                let quad_max_y = min_y + 1.0 - line_char.baseline_offset - character.get_offset_y() as f32 * pixel_size;
+                let advance_min_x = pos_x;
+                pos_x += line_char.get_first_advance();
+
+                quads.push(GlyphQuad {
+                    min_x: quad_min_x,
+                    min_y: quad_max_y - character.get_height() as f32 * pixel_size,
+                    max_x: quad_min_x + character.get_width() as f32 * pixel_size,
+                    max_y: quad_max_y,
+                    advance_min_x,
+                    advance_max_x: pos_x,
+                    is_space: line_char.text_char.is_whitespace(),
+                    span: line_char.span,
+                    character: line_char.character
+                });
+            }
+
+            let line_width = pos_x;
+            layout_lines.push(LayoutLine {
+                first_quad,
+                quad_count: quads.len() - first_quad,
+                width: line_width,
+                min_y,
+                ends_paragraph
+            });
+            width = width.max(line_width);
+        }
+
+        TextLayout {
+            quads,
+            lines: layout_lines,
+            width,
+            height: line_count as f32 + (line_count - 1) as f32 * line_spacing
+        }
+    }
+
+    /// Splits the given line into lines that are at most max_width wide (in model space). The line will
+    /// preferably be broken at whitespace. Words that are wider than max_width on their own will be broken inside the word.
+    /// The whitespace at the places where the line is broken will be dropped, and so will the whitespace at the end of the
+    /// line. The whitespace at the start of the line is kept, unless the first word would fit on the first line without it.
+    pub fn wrap_line(line: Vec<LineChar>, max_width: f32) -> Vec<Vec<LineChar>> {
+
+        // Group the chars into words and the whitespace between them
+        let mut tokens: Vec<(bool, Vec<LineChar>)> = Vec::new();
+        for line_char in line {
+            let is_space = line_char.text_char.is_whitespace();
+            match tokens.last_mut() {
+                Some((last_is_space, last_token)) if *last_is_space == is_space => last_token.push(line_char),
+                _ => tokens.push((is_space, vec![line_char]))
+            };
+        }
+
+        let mut lines = Vec::new();
+        let mut current_line: Vec<LineChar> = Vec::new();
+        let mut current_width = 0.0;
+        let mut pending_space: Vec<LineChar> = Vec::new();
+
+        for (is_space, token) in tokens {
+            if is_space {
+
+                // Whitespace at the start of the text is kept, but whitespace at the start of a wrapped line is not
+                if current_line.is_empty() && lines.is_empty() {
+                    current_width += total_width(&token);
+                    current_line.extend(token);
+                } else {
+                    pending_space = token;
+                }
+                continue;
+            }
+
+            let space_width = total_width(&pending_space);
+            if !current_line.is_empty() {
+                let word_width = total_width(&token);
+                if current_width + space_width + word_width <= max_width {
+                    current_line.append(&mut pending_space);
+                    current_line.extend(token);
+                    current_width += space_width + word_width;
+                    continue;
+                }
+
+                // A line with only the whitespace at the start of the text would be pointless
+                if contains_word(&current_line) {
+                    lines.push(std::mem::take(&mut current_line));
+                    current_width = 0.0;
+                }
+            }
+            pending_space.clear();
+
+            // The kerning of the first character of a line doesn't count
+            let word_width = total_width(&token) - token[0].kerning * token[0].pixel_size;
+            if word_width <= max_width {
+                current_line = token;
+                current_width = word_width;
+            } else {
+
+                // The word doesn't fit on a line on its own, so it has to be broken
+                for line_char in token {
+                    if contains_word(&current_line) && current_width + line_char.get_advance() > max_width {
+                        lines.push(std::mem::take(&mut current_line));
+                        current_width = 0.0;
+                    }
+                    current_width += if current_line.is_empty() { line_char.get_first_advance() } else { line_char.get_advance() };
+                    current_line.push(line_char);
+                }
+            }
+        }
+
+        lines.push(current_line);
+        lines
+    }
+
+    /// Computes the horizontal offset (in model space) of every quad when the lines are aligned with the given alignment
+    /// within a region that is region_width wide (in model space).
+    pub fn compute_offsets(&self, alignment: HorizontalAlignment, region_width: f32) -> Vec<f32> {
+        let mut offsets = vec![0.0; self.quads.len()];
+        for line in &self.lines {
+            let free_space = region_width - line.width;
+            let quad_range = line.first_quad..line.first_quad + line.quad_count;
+            match alignment {
+                HorizontalAlignment::Left => {},
+                HorizontalAlignment::Center => offsets[quad_range].fill(free_space / 2.0),
+                HorizontalAlignment::Right => offsets[quad_range].fill(free_space),
+                HorizontalAlignment::Justify => {
+                    if self.is_stretched(line, region_width) {
+                        let space_count = self.quads[quad_range.clone()].iter().filter(|quad| quad.is_space).count();
+                        let extra_space = free_space / space_count as f32;
+                        let mut current_offset = 0.0;
+                        for quad_index in quad_range {
+                            offsets[quad_index] = current_offset;
+                            if self.quads[quad_index].is_space {
+                                current_offset += extra_space;
+                            }
+                        }
+                    }
+                }
+            };
+        }
+        offsets
+    }
+
+    /// Checks whether the given line of this layout will be stretched to the full region_width when the text is justified.
+    /// The last line of a paragraph stays left-aligned, and so do lines without any whitespace.
+    fn is_stretched(&self, line: &LayoutLine, region_width: f32) -> bool {
+        let quads = &self.quads[line.first_quad..line.first_quad + line.quad_count];
+        !line.ends_paragraph && line.width < region_width && quads.iter().any(|quad| quad.is_space)
+    }
+
+    /// Computes the horizontal range (min_x, max_x) in model space that is covered by the lines of this layout when they are
+    /// aligned like in compute_offsets. This is the range that should be filled with the background color. Note that min_x
+    /// will be negative if centered or right-aligned text is wider than the region.
+    pub fn compute_horizontal_extent(&self, alignment: HorizontalAlignment, region_width: f32) -> (f32, f32) {
+        let line_ranges = self.lines.iter().map(|line| {
+            let free_space = region_width - line.width;
+            match alignment {
+                HorizontalAlignment::Left => (0.0, line.width),
+                HorizontalAlignment::Center => (free_space / 2.0, free_space / 2.0 + line.width),
+                HorizontalAlignment::Right => (free_space, region_width),
+                HorizontalAlignment::Justify => match self.is_stretched(line, region_width) {
+                    true => (0.0, region_width),
+                    false => (0.0, line.width)
+                }
+            }
+        });
+        line_ranges.reduce(|(min_x, max_x), (line_min_x, line_max_x)| (min_x.min(line_min_x), max_x.max(line_max_x))).unwrap_or((0.0, 0.0))
+    }
+
+    /// Gets the indices of all quads, sorted by the atlas page of their character. The vertex data is stored in this order,
+    /// so that all quads of the same page can be drawn at once.
+    pub fn get_draw_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.quads.len()).collect();
+        order.sort_by_key(|quad_index| self.quads[*quad_index].character.get_page());
+        order
+    }
+
+    /// Gets the atlas pages that are used by this layout, together with the number of quads that use each page. The pages
+    /// are listed in the same order as their quads are stored in the vertex data.
+    pub fn get_page_groups(&self) -> Vec<(usize, usize)> {
+        let mut groups: Vec<(usize, usize)> = Vec::new();
+        for quad_index in self.get_draw_order() {
+            let page = self.quads[quad_index].character.get_page();
+            match groups.last_mut() {
+                Some((last_page, quad_count)) if *last_page == page => *quad_count += 1,
+                _ => groups.push((page, 1))
+            };
+        }
+        groups
+    }
+
+    /// Creates the vertex data for a TextModel with this layout, where every line is aligned with the given alignment
+    /// within a region that is region_width wide (in model space). The region_width is ignored for left-aligned text. The
+    /// texture coordinates will be computed for atlas pages with the given sizes (width, height) in pixels.
+    ///
+    /// Every character will use 4 vertices, which should be drawn with the indices of QUAD_INDICES. The vertices are
+    /// interleaved: every vertex consists of its position (2 floats) followed by its texture coordinates. If
+    /// compact_texture_coords is true, the texture coordinates are stored as 2 normalized u16's. Otherwise, they are stored
+    /// as 2 floats. All values are stored in little-endian byte order, see get_vertex_size. The characters are grouped by
+    /// atlas page (see get_page_groups).
+    pub fn create_vertex_data(&self, alignment: HorizontalAlignment, region_width: f32, page_sizes: &[(u32, u32)], compact_texture_coords: bool) -> Vec<u8> {
+        let offsets = self.compute_offsets(alignment, region_width);
+
+        let mut vertex_data = Vec::with_capacity(4 * get_vertex_size(compact_texture_coords) * self.quads.len());
+        for quad_index in self.get_draw_order() {
+            let quad = &self.quads[quad_index];
+            let texture_char = quad.character;
+            let (texture_width, texture_height) = page_sizes[texture_char.get_page()];
+            let left_u = texture_char.get_left_u(texture_width);
+            let bottom_v = texture_char.get_bottom_v(texture_height);
+            let right_u = texture_char.get_right_u(texture_width);
+            let top_v = texture_char.get_top_v(texture_height);
+
+            let min_x = quad.min_x + offsets[quad_index];
+            let max_x = quad.max_x + offsets[quad_index];
+            let corners = [
+                (min_x, quad.min_y, left_u, bottom_v),
+                (max_x, quad.min_y, right_u, bottom_v),
+                (max_x, quad.max_y, right_u, top_v),
+                (min_x, quad.max_y, left_u, top_v)
+            ];
+            for (x, y, u, v) in corners.iter() {
+                vertex_data.extend_from_slice(&x.to_le_bytes());
+                vertex_data.extend_from_slice(&y.to_le_bytes());
+                if compact_texture_coords {
+                    vertex_data.extend_from_slice(&normalize_texture_coord(*u).to_le_bytes());
+                    vertex_data.extend_from_slice(&normalize_texture_coord(*v).to_le_bytes());
+                } else {
+                    vertex_data.extend_from_slice(&u.to_le_bytes());
+                    vertex_data.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+
+        vertex_data
+    }
+
+    /// Creates the instance data for a TextModel with this layout, for Font's with an instanced TextProgram. The alignment,
+    /// region_width and page_sizes have the same meaning as for create_vertex_data.
+    ///
+    /// Every character will use 1 instance of 8 floats: its position (min_x, min_y, max_x, max_y) followed by its texture
+    /// coordinates (left_u, bottom_v, right_u, top_v). The characters are grouped by atlas page (see get_page_groups).
+    pub fn create_instance_data(&self, alignment: HorizontalAlignment, region_width: f32, page_sizes: &[(u32, u32)]) -> Vec<f32> {
+        let offsets = self.compute_offsets(alignment, region_width);
+
+        let mut instance_data = Vec::with_capacity(FLOATS_PER_INSTANCE * self.quads.len());
+        for quad_index in self.get_draw_order() {
+            let quad = &self.quads[quad_index];
+            let texture_char = quad.character;
+            let (texture_width, texture_height) = page_sizes[texture_char.get_page()];
+            instance_data.extend_from_slice(&[
+                quad.min_x + offsets[quad_index], quad.min_y, quad.max_x + offsets[quad_index], quad.max_y,
+                texture_char.get_left_u(texture_width), texture_char.get_bottom_v(texture_height),
+                texture_char.get_right_u(texture_width), texture_char.get_top_v(texture_height)
+            ]);
+        }
+        instance_data
+    }
+}
+
+/// The indices of the 2 triangles of a single quad of the vertex data of a TextLayout, relative to the first vertex of the
+/// quad. The triangles are (min_x, min_y) -> (max_x, min_y) -> (max_x, max_y) and (max_x, max_y) -> (min_x, max_y) ->
+/// (min_x, min_y).
+pub(super) const QUAD_INDICES: [u16; 6] = [0, 1, 2, 2, 3, 0];
+
+/// Gets the size (in bytes) of a single vertex of the vertex data of a TextLayout (see create_vertex_data)
+pub(super) fn get_vertex_size(compact_texture_coords: bool) -> usize {
+    match compact_texture_coords {
+        true => 12,
+        false => 16
+    }
+}
+
+/// Converts the given texture coordinate to a normalized u16, where 0 corresponds to 0.0 and u16::MAX to 1.0
+fn normalize_texture_coord(coordinate: f32) -> u16 {
+    (coordinate.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+}
+
+fn contains_word(characters: &[LineChar]) -> bool {
+    characters.iter().any(|line_char| !line_char.text_char.is_whitespace())
+}
+
+fn total_width(characters: &[LineChar]) -> f32 {
+    characters.iter().map(|line_char| line_char.get_advance()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use super::super::metrics::GlyphMetrics;
+    use super::super::rasterizer::{
+        GlyphBitmap,
+        compute_ink_box
+    };
+
+    /// Creates a LineChar with the given metrics (in pixels), whose quad covers the ink of the character
+    fn create_line_char(text_char: char, metrics: GlyphMetrics, pixel_size: f32) -> LineChar {
+        let (offset_x, offset_y, width, height) = compute_ink_box(&metrics, 8.0, 1);
+        let bitmap = GlyphBitmap::empty(width, height, offset_x, offset_y, metrics);
+        LineChar { text_char, character: Character::new(0, 0, 0, &bitmap), span: 0, kerning: 0.0, pixel_size, baseline_offset: 0.0 }
+    }
+
+    /// Converts the given text to a line where every character has an advance of 1.0 in model space
+    fn create_line(text: &str) -> Vec<LineChar> {
+        let bitmap = GlyphBitmap::empty(1, 1, 0, 0, GlyphMetrics::new(1.0, 0.0, 0.0, 0.0, 0.0));
+        text.chars().map(|text_char| LineChar { text_char, character: Character::new(0, 0, 0, &bitmap), span: 0, kerning: 0.0, pixel_size: 1.0, baseline_offset: 0.0 }).collect()
+    }
+
+    fn to_strings(lines: &[Vec<LineChar>]) -> Vec<String> {
+        lines.iter().map(|line| line.iter().map(|line_char| line_char.text_char).collect()).collect()
+    }
+
+    fn wrap(text: &str, max_width: f32) -> Vec<String> {
+        to_strings(&TextLayout::wrap_line(create_line(text), max_width))
+    }
+
+    #[test]
+    fn lines_are_filled_greedily() {
+        assert_eq!(wrap("aa bb cc dd", 5.0), vec!["aa bb", "cc dd"]);
+        assert_eq!(wrap("aa bb cc dd", 4.0), vec!["aa", "bb", "cc", "dd"]);
+        assert_eq!(wrap("aaa bbbb c", 5.0), vec!["aaa", "bbbb", "c"]);
+        assert_eq!(wrap("aa bb", 100.0), vec!["aa bb"]);
+    }
+
+    #[test]
+    fn long_words_are_broken() {
+        assert_eq!(wrap("abcdefgh", 3.0), vec!["abc", "def", "gh"]);
+        assert_eq!(wrap("ab cdefgh", 3.0), vec!["ab", "cde", "fgh"]);
+
+        // Every line needs at least 1 character, even if it doesn't fit
+        assert_eq!(wrap("abc", 0.0), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn whitespace_at_the_start_and_end() {
+
+        // The whitespace at the start is kept if the first word fits after it, or if it doesn't fit on a line anyway
+        assert_eq!(wrap("  ab cd", 4.0), vec!["  ab", "cd"]);
+        assert_eq!(wrap("   abcd", 4.0), vec!["abcd"]);
+        assert_eq!(wrap("  abcdefgh", 4.0), vec!["  ab", "cdef", "gh"]);
+
+        // The whitespace at the end and at the line breaks is dropped
+        assert_eq!(wrap("ab   ", 10.0), vec!["ab"]);
+        assert_eq!(wrap("ab   cd", 3.0), vec!["ab", "cd"]);
+        assert_eq!(wrap("", 3.0), vec![""]);
+    }
+
+    #[test]
+    fn kerning_of_the_first_character_is_ignored() {
+        let mut line = create_line("ab cd");
+        line[3].kerning = 0.5;
+
+        // With its kerning, "cd" would be 2.5 wide, but the kerning doesn't count at the start of a line
+        let lines = TextLayout::wrap_line(line, 2.0);
+        assert_eq!(to_strings(&lines), vec!["ab", "cd"]);
+
+        let layout = TextLayout::from_lines(lines.into_iter().map(|line| (line, false)).collect(), 0.5);
+        assert_eq!(layout.lines.len(), 2);
+        let widths: Vec<f32> = layout.lines.iter().map(|line| line.width).collect();
+        assert_eq!(widths, vec![2.0, 2.0]);
+        assert_eq!(layout.height, 2.5);
+    }
+
+    #[test]
+    fn line_widths_include_kerning() {
+        let mut line = create_line("AVA");
+        line[1].kerning = -0.25;
+        line[2].kerning = -0.25;
+        let layout = TextLayout::from_lines(vec![(line, true)], 0.0);
+        assert_eq!(layout.lines[0].width, 2.5);
+        assert_eq!(layout.width, 2.5);
+        assert_eq!(layout.quads[1].advance_min_x, 0.75);
+        assert_eq!(layout.quads[2].advance_max_x, 2.5);
+    }
+
+    #[test]
+    fn quads_cover_the_ink_of_their_character() {
+
+        // The ink of the second character starts 2 pixels before its origin and ends 3 pixels after its advance
+        let pixel_size = 0.1;
+        let overhanging = GlyphMetrics::new(10.0, -2.0, -3.0, 6.0, 1.0);
+        let line = vec![create_line_char('x', GlyphMetrics::new(5.0, 1.0, 1.0, 4.0, 0.0), pixel_size), create_line_char('f', overhanging, pixel_size)];
+        let layout = TextLayout::from_lines(vec![(line, true)], 0.0);
+        let quad = &layout.quads[1];
+        assert_eq!(quad.advance_min_x, 0.5);
+        assert_eq!(quad.advance_max_x, 1.5);
+
+        // The quad has a margin of 1 pixel around the ink (see create_line_char)
+        let ink_min_x = quad.advance_min_x + overhanging.get_left_bearing() * pixel_size;
+        let ink_max_x = quad.advance_max_x - overhanging.get_right_bearing() * pixel_size;
+        assert!((quad.min_x - (ink_min_x - pixel_size)).abs() < 0.0001);
+        assert!((quad.max_x - (ink_max_x + pixel_size)).abs() < 0.0001);
+
+        // The baseline is 8 pixels below the top of the line
+        assert!((quad.max_y - (1.0 - (8.0 - 6.0 - 1.0) * pixel_size)).abs() < 0.0001);
+        assert!((quad.min_y - (1.0 - (8.0 + 1.0 + 1.0) * pixel_size)).abs() < 0.0001);
+        assert_eq!(layout.width, 1.5);
+    }
+
+    #[test]
+    fn wrapped_lines_have_their_own_width() {
+        let lines = TextLayout::wrap_line(create_line("aa bbb"), 4.0).into_iter().map(|line| (line, false)).collect();
+        let layout = TextLayout::from_lines(lines, 0.25);
+        assert_eq!(layout.lines[0].width, 2.0);
+        assert_eq!(layout.lines[1].width, 3.0);
+        assert_eq!(layout.width, 3.0);
+        assert_eq!(layout.height, 2.25);
+
+        // The first line is at the top
+        assert_eq!(layout.quads[0].min_y - layout.quads[4].min_y, 1.25);
+    }
+}
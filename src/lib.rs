@@ -1,24 +1,76 @@
 use web_sys::WebGlRenderingContext;
-use web_sys::WebGlRenderingContext as GL;
+#[cfg(feature = "webgl2")]
+use web_sys::WebGl2RenderingContext;
 use web_sys::HtmlCanvasElement;
 
 use wasm_bindgen::JsCast;
 
+use wasmuri_core::TextColors;
+
 use std::rc::Rc;
 use std::cell::{
     Cell,
     RefCell
 };
 
+mod error;
+mod backend;
+mod webgl;
+#[cfg(feature = "webgl2")]
+mod webgl2;
+mod recording;
+mod rasterizer;
+mod outline;
+mod canvas;
+mod ttf;
+mod distance;
+mod packer;
+mod atlas;
 mod character;
+mod layout;
+mod alignment;
+mod decoration;
+mod shadow;
+mod metrics;
 mod shaders;
 mod model;
+mod batch;
+mod rich;
+mod markup;
+mod immediate;
 mod font;
-
+#[cfg(test)]
+mod testing;
+
+pub use error::*;
+pub use backend::*;
+pub use webgl::*;
+#[cfg(feature = "webgl2")]
+pub use webgl2::*;
+pub use recording::*;
 pub use model::*;
+pub use batch::*;
+pub use rich::*;
+pub use markup::*;
+pub use alignment::*;
+pub use decoration::*;
+pub use shadow::*;
+pub use metrics::*;
 pub use font::*;
 
-use shaders::TextProgram;
+use shaders::{
+    TextProgram,
+    BatchProgram,
+    RichTextProgram
+};
+use immediate::ImmediateText;
+use layout::TextLayout;
+use rich::collect_span_fonts;
+use rasterizer::GlyphRasterizer;
+use canvas::CanvasRasterizer;
+use ttf::TtfRasterizer;
+use distance::*;
+use shadow::ShadowRasterizer;
 
 /// The TextRenderer is the main struct of this crate. Instances of TextRenderer can create Font's, which can create TextModel's
 /// to perform the actual text rendering. You will need an instance of TextRenderer for each canvas you wish to draw text on with 
@@ -39,9 +91,17 @@ use shaders::TextProgram;
 /// method of the TextModel to finally draw the text.
 /// 
 /// Every method mentioned above has its own more detailed description.
+/// 
+/// All rendering goes through the TextBackend of the TextRenderer. The from_... functions that take a webgl context will use
+/// a WebGlBackend. If you want to use another backend (for instance a RecordingBackend for unit tests), you can use the
+/// from_backend function.
+/// 
+/// The from_... functions and the add_... methods will panic if something goes wrong (for instance when the text shaders
+/// can't be compiled). Every one of them has a try_... counterpart that returns a TextError instead, which is useful if
+/// you would like to show a fallback message rather than aborting.
 pub struct TextRenderer {
 
-    gl: Rc<WebGlRenderingContext>,
+    backend: Rc<dyn TextBackend>,
     fonts: Vec<Rc<Font>>,
 
     /// The font_size that will be used to draw the backing textures of the characters. Changing this value
@@ -78,37 +138,143 @@ pub struct TextRenderer {
     /// and all special characters I could find on my keyboard. If you need to draw characters not in this string, you will need to 
     /// modify it before adding fonts. It will usually not be necessary, but I might have missed some characters or you might need 
     /// for instance Chinese characters. Please note that more characters means more memory usage.
+    /// 
+    /// If dynamic_atlas is true, the fonts will also be able to draw characters that are not in this string: such characters
+    /// will be added to the backing texture of the font the first time they are needed.
     pub all_chars: String,
 
+    /// The dynamic_atlas determines whether the next fonts will add characters that are not in all_chars to their backing
+    /// texture the first time such a character is used (in create_text_model or measure for instance). If this is false,
+    /// such characters will simply be skipped. Changing this value will only affect the fonts that are added after changing
+    /// the value.
+    /// 
+    /// The default value is true. Dynamic fonts need to keep their rasterizer (which uses a hidden canvas for fonts that were
+    /// added with add_font or add_fonts) alive and their backing texture will grow when new characters are added, so you
+    /// may want to disable this if you know all characters you will need in advance.
+    pub dynamic_atlas: bool,
+
+    /// The atlas_options determine how the characters of the next fonts will be packed in their backing texture, for
+    /// instance the padding between the characters and whether the size of the texture should be a power of 2. Changing
+    /// this value will only affect the fonts that are added after changing the value. See AtlasOptions for more information.
+    pub atlas_options: AtlasOptions,
+
+    /// The fallback_char will be drawn by the next fonts in place of characters they can't draw (because the character is
+    /// not in all_chars and the font doesn't have a dynamic atlas, or because the font doesn't have a glyph for it). Common
+    /// choices are '?' and '\u{FFFD}'. Changing this value will only affect the fonts that are added after changing the value.
+    /// 
+    /// The default value is None, which means that such characters will simply be skipped.
+    pub fallback_char: Option<char>,
+
+    /// The line_spacing determines the amount of empty space between the lines of multi-line text models of the next fonts.
+    /// The value is a fraction of the height of a single line, so a value of 0.5 would put half a line of empty space between
+    /// every 2 lines. Changing this value will only affect the fonts that are added after changing the value.
+    /// 
+    /// The default value is 0, which puts the lines right below each other. Since the height of a line already includes some
+    /// space above and below the characters, this usually looks fine.
+    pub line_spacing: f32,
+
+    /// The atlas_mode determines how the characters of the next fonts will be stored in their backing texture. Changing this
+    /// value will only affect the fonts that are added after changing the value, just like the font_size.
+    /// 
+    /// The default value is AtlasMode::Bitmap, which will draw the characters at the font_size (and with the line_width) of
+    /// this TextRenderer. With AtlasMode::DistanceField, the fonts will store signed distance fields instead, which will keep
+    /// the text crisp at any scale, needs much less memory and allows the line_width to be chosen when rendering text. See
+    /// the description of AtlasMode for more information.
+    pub atlas_mode: AtlasMode,
+
+    /// The compact_texture_coords determines whether the next fonts will store the texture coordinates of their TextModel's
+    /// as normalized 16-bit integers rather than 32-bit floats. This reduces the size of the vertex buffers of the
+    /// TextModel's by a quarter, at the cost of some precision (which is still much finer than a texel of any atlas that
+    /// fits in a texture). Changing this value will only affect the fonts that are added after changing the value.
+    /// 
+    /// The default value is false. This value doesn't have any effect on backends that support instancing, because those
+    /// store every character as a single instance rather than 4 vertices.
+    pub compact_texture_coords: bool,
+
+    /// The shadow_blur determines how far the next fonts with a bitmap atlas can blur the shadows of their text (see
+    /// TextShadow). The value is a fraction of the font_size, just like the line_width. Changing this value will only affect
+    /// the fonts that are added after changing the value.
+    /// 
+    /// The default value is 0, which means that the shadows of the next bitmap fonts will always be sharp. A value larger
+    /// than 0 will store a blurred copy of every character in the alpha channel of the backing texture, and every character
+    /// will get an empty margin of the blur width, which makes the backing texture larger. Fonts with a distance field
+    /// atlas can blur their shadows without this, so they ignore this value.
+    pub shadow_blur: f64,
+
     selected_font: Rc<Cell<Option<FontID>>>,
 
-    shader_program: Rc<RefCell<TextProgram>>
+    shader_program: Rc<RefCell<TextProgram>>,
+    distance_field_program: Option<Rc<RefCell<TextProgram>>>,
+
+    batch_program: Option<Rc<BatchProgram>>,
+    distance_field_batch_program: Option<Rc<BatchProgram>>,
+
+    rich_program: Option<Rc<RichTextProgram>>,
+    distance_field_rich_program: Option<Rc<RichTextProgram>>,
+
+    immediate_text: ImmediateText
 }
 
 pub const DEFAULT_FONT_SIZE: usize = 250;
 pub const DEFAULT_LINE_WIDTH: f64 = 0.02;
+pub const DEFAULT_LINE_SPACING: f32 = 0.0;
 pub const DEFAULT_CHARS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZáçéíóúýÁÇÉÍÓÚÝ 0123456789!@#$%^&*?<>:\"';[]{}()|\\/.,-_=+€`~";
 
 impl TextRenderer {
 
-    /// This function will create a TextRenderer instance from the given reference counter. This function is convenient if
-    /// you are already using an Rc to store your webgl context. The created TextRenderer won't have any fonts yet, read the
-    /// description of TextRenderer for more information about this.
-    pub fn from_rc(gl: Rc<WebGlRenderingContext>) -> TextRenderer {
-        let shader_program = Rc::new(RefCell::new(TextProgram::create_instance(Rc::clone(&gl))));
+    /// This function will create a TextRenderer instance that will do all its rendering with the given backend. This is
+    /// useful if you don't want to render with WebGl directly, for instance when you want to test your text code outside a
+    /// browser with a RecordingBackend. The created TextRenderer won't have any fonts yet, read the description of
+    /// TextRenderer for more information about this.
+    pub fn from_backend(backend: Rc<dyn TextBackend>) -> TextRenderer {
+        TextRenderer::try_from_backend(backend).expect("Should be able to create the text shader program")
+    }
+
+    /// Like from_backend, but returns an error rather than panicking if the text shader program can't be created.
+    pub fn try_from_backend(backend: Rc<dyn TextBackend>) -> Result<TextRenderer, TextError> {
+        let shader_program = Rc::new(RefCell::new(TextProgram::create_instance(Rc::clone(&backend))?));
         let fonts = Vec::new();
+        let immediate_text = ImmediateText::new(Rc::clone(&backend));
 
-        TextRenderer {
-            gl,
+        Ok(TextRenderer {
+            backend,
             fonts,
 
             font_size: DEFAULT_FONT_SIZE,
             line_width: DEFAULT_LINE_WIDTH,
             all_chars: DEFAULT_CHARS.to_string(),
+            dynamic_atlas: true,
+            atlas_options: AtlasOptions::default(),
+            fallback_char: None,
+            line_spacing: DEFAULT_LINE_SPACING,
+            atlas_mode: AtlasMode::Bitmap,
+            compact_texture_coords: false,
+            shadow_blur: 0.0,
 
             selected_font: Rc::new(Cell::new(None)),
-            shader_program
-        }
+            shader_program,
+            distance_field_program: None,
+
+            batch_program: None,
+            distance_field_batch_program: None,
+
+            rich_program: None,
+            distance_field_rich_program: None,
+
+            immediate_text
+        })
+    }
+
+    /// This function will create a TextRenderer instance from the given reference counter. This function is convenient if
+    /// you are already using an Rc to store your webgl context. The created TextRenderer won't have any fonts yet, read the
+    /// description of TextRenderer for more information about this.
+    pub fn from_rc(gl: Rc<WebGlRenderingContext>) -> TextRenderer {
+        TextRenderer::from_backend(Rc::new(WebGlBackend::new(gl)))
+    }
+
+    /// Like from_rc, but returns an error rather than panicking if the text shader program can't be created.
+    pub fn try_from_rc(gl: Rc<WebGlRenderingContext>) -> Result<TextRenderer, TextError> {
+        TextRenderer::try_from_backend(Rc::new(WebGlBackend::new(gl)))
     }
 
     /// This function will create a TextRenderer instance for the given webgl rendering context. The created TextRenderer 
@@ -117,11 +283,64 @@ impl TextRenderer {
         TextRenderer::from_rc(Rc::new(gl))
     }
 
+    /// Like from_gl, but returns an error rather than panicking if the text shader program can't be created.
+    pub fn try_from_gl(gl: WebGlRenderingContext) -> Result<TextRenderer, TextError> {
+        TextRenderer::try_from_rc(Rc::new(gl))
+    }
+
     /// This function will create a TextRenderer instance for the given canvas. This method will panic if no webgl context
     /// can be created for the canvas. Even though browsers that support WebAssembly generally support WebGl, only 1 type of
     /// context can be created for each canvas, so this would fail if the canvas has created a 2d context before.
     pub fn from_canvas(canvas: &HtmlCanvasElement) -> TextRenderer {
-        TextRenderer::from_gl(canvas.get_context("webgl").expect("should have get_context").expect("should be able to obtain webgl context").dyn_into::<WebGlRenderingContext>().expect("getContext'webgl' should give a webgl rendering context"))
+        TextRenderer::try_from_canvas(canvas).expect("Should be able to create a TextRenderer for the canvas")
+    }
+
+    /// Like from_canvas, but returns an error rather than panicking if no webgl context can be created for the canvas or
+    /// the text shader program can't be created.
+    pub fn try_from_canvas(canvas: &HtmlCanvasElement) -> Result<TextRenderer, TextError> {
+        let context = canvas.get_context("webgl").ok().flatten().ok_or(TextError::NoWebGlContext)?;
+        TextRenderer::try_from_gl(context.dyn_into::<WebGlRenderingContext>().map_err(|_| TextError::NoWebGlContext)?)
+    }
+
+    /// This function will create a TextRenderer instance that renders with the given webgl2 context, see from_rc. Unlike
+    /// the TextRenderer's created by from_rc, this TextRenderer will draw text with instancing and vertex array objects,
+    /// which needs less vertex memory and fewer webgl calls.
+    #[cfg(feature = "webgl2")]
+    pub fn from_rc_gl2(gl: Rc<WebGl2RenderingContext>) -> TextRenderer {
+        TextRenderer::from_backend(Rc::new(WebGl2Backend::new(gl)))
+    }
+
+    /// Like from_rc_gl2, but returns an error rather than panicking if the text shader program can't be created.
+    #[cfg(feature = "webgl2")]
+    pub fn try_from_rc_gl2(gl: Rc<WebGl2RenderingContext>) -> Result<TextRenderer, TextError> {
+        TextRenderer::try_from_backend(Rc::new(WebGl2Backend::new(gl)))
+    }
+
+    /// This function will create a TextRenderer instance for the given webgl2 rendering context, see from_rc_gl2.
+    #[cfg(feature = "webgl2")]
+    pub fn from_gl2(gl: WebGl2RenderingContext) -> TextRenderer {
+        TextRenderer::from_rc_gl2(Rc::new(gl))
+    }
+
+    /// Like from_gl2, but returns an error rather than panicking if the text shader program can't be created.
+    #[cfg(feature = "webgl2")]
+    pub fn try_from_gl2(gl: WebGl2RenderingContext) -> Result<TextRenderer, TextError> {
+        TextRenderer::try_from_rc_gl2(Rc::new(gl))
+    }
+
+    /// This function will create a TextRenderer instance that renders with a webgl2 context of the given canvas, see
+    /// from_canvas and from_rc_gl2. This method will panic if no webgl2 context can be created for the canvas.
+    #[cfg(feature = "webgl2")]
+    pub fn from_canvas_gl2(canvas: &HtmlCanvasElement) -> TextRenderer {
+        TextRenderer::try_from_canvas_gl2(canvas).expect("Should be able to create a TextRenderer for the canvas")
+    }
+
+    /// Like from_canvas_gl2, but returns an error rather than panicking if no webgl2 context can be created for the canvas
+    /// or the text shader program can't be created.
+    #[cfg(feature = "webgl2")]
+    pub fn try_from_canvas_gl2(canvas: &HtmlCanvasElement) -> Result<TextRenderer, TextError> {
+        let context = canvas.get_context("webgl2").ok().flatten().ok_or(TextError::NoWebGlContext)?;
+        TextRenderer::try_from_gl2(context.dyn_into::<WebGl2RenderingContext>().map_err(|_| TextError::NoWebGlContext)?)
     }
 
     /// Adds a Font for every FontDetails supplied to this method. After this method call, you can use the get_font_by_details
@@ -134,11 +353,19 @@ impl TextRenderer {
     /// will keep those values even if the values of this TextRenderer would be changed after this call. For more information
     /// about any of the three properties, see their description.
     pub fn add_fonts(&mut self, fonts: Vec<FontDetails>){
+        self.try_add_fonts(fonts).expect("Should be able to create the fonts");
+    }
+
+    /// Like add_fonts, but returns an error rather than panicking if one of the Font's can't be created. If that happens,
+    /// none of the Font's will be added.
+    pub fn try_add_fonts(&mut self, fonts: Vec<FontDetails>) -> Result<(), TextError> {
         let mut new_fonts = Vec::with_capacity(fonts.len());
         for font_details in fonts {
-            new_fonts.push(Self::create_font(&self.gl, &self.shader_program, FontID::new(self.fonts.len()), &self.selected_font, self.font_size, self.line_width, font_details, &self.all_chars));
+            let rasterizer = self.create_rasterizer(|font_size, line_width| Ok(Box::new(CanvasRasterizer::new(&font_details, font_size, line_width)?)))?;
+            new_fonts.push(self.create_font(FontID::new(self.fonts.len() + new_fonts.len()), font_details, rasterizer)?);
         }
         self.fonts.append(&mut new_fonts);
+        Ok(())
     }
 
     /// Adds a single Font with the given FontDetails. A reference to the newly created Font will be returned by this method. You
@@ -151,13 +378,191 @@ impl TextRenderer {
     /// will keep those values even if the values of this TextRenderer would be changed after this call. For more information
     /// about any of the three properties, see their description.
     pub fn add_font(&mut self, font_details: FontDetails) -> Rc<Font> {
-        let font = Self::create_font(&self.gl, &self.shader_program, FontID::new(self.fonts.len()), &self.selected_font, self.font_size, self.line_width, font_details, &self.all_chars);
+        self.try_add_font(font_details).expect("Should be able to create the font")
+    }
+
+    /// Like add_font, but returns an error rather than panicking if the Font can't be created.
+    pub fn try_add_font(&mut self, font_details: FontDetails) -> Result<Rc<Font>, TextError> {
+        let rasterizer = self.create_rasterizer(|font_size, line_width| Ok(Box::new(CanvasRasterizer::new(&font_details, font_size, line_width)?)))?;
+        let font = self.create_font(FontID::new(self.fonts.len()), font_details, rasterizer)?;
         self.fonts.push(font);
-        Rc::clone(&self.fonts[self.fonts.len() - 1])
+        Ok(Rc::clone(&self.fonts[self.fonts.len() - 1]))
+    }
+
+    /// Adds a single Font that will be rasterized from the given TrueType or OpenType font file rather than with a canvas. A
+    /// reference to the newly created Font will be returned by this method. The font_details will only be used to identify 
+    /// the Font, so you can retrieve it later with the get_font_by_details method of this TextRenderer.
+    /// 
+    /// Unlike the add_font method, this method doesn't need a DOM, so it also works in web workers and outside the browser.
+    /// Also, the rasterized characters will be exactly the same in every browser.
+    /// 
+    /// This method will use the current font_size, line_width and all_chars values of this TextRenderer, just like the 
+    /// add_font method. This method will panic if font_data is not a valid TrueType or OpenType font.
+    pub fn add_ttf_font(&mut self, font_details: FontDetails, font_data: Vec<u8>) -> Rc<Font> {
+        self.try_add_ttf_font(font_details, font_data).expect("Should be able to create the font")
     }
 
-    fn create_font(gl: &Rc<WebGlRenderingContext>, shader_program: &Rc<RefCell<TextProgram>>, font_id: FontID, selected_font: &Rc<Cell<Option<FontID>>>, font_size: usize, line_width: f64, font_details: FontDetails, all_chars: &str) -> Rc<Font> {
-        Rc::new(Font::new(Rc::clone(gl), Rc::clone(shader_program), font_id, Rc::clone(selected_font), font_size, line_width, font_details, all_chars))
+    /// Like add_ttf_font, but returns an error rather than panicking if font_data is not a valid font or the Font can't be
+    /// created for another reason.
+    pub fn try_add_ttf_font(&mut self, font_details: FontDetails, font_data: Vec<u8>) -> Result<Rc<Font>, TextError> {
+        let rasterizer = self.create_rasterizer(|font_size, line_width| Ok(Box::new(TtfRasterizer::new(font_data, font_size, line_width).map_err(|_| TextError::InvalidFont)?)))?;
+        let font = self.create_font(FontID::new(self.fonts.len()), font_details, rasterizer)?;
+        self.fonts.push(font);
+        Ok(Rc::clone(&self.fonts[self.fonts.len() - 1]))
+    }
+
+    /// Creates the rasterizer for the next font, using the current atlas_mode. The create_source function should create the
+    /// rasterizer with the given font size and line width.
+    fn create_rasterizer<F: FnOnce(usize, f64) -> Result<Box<dyn GlyphRasterizer>, TextError>>(&self, create_source: F) -> Result<Box<dyn GlyphRasterizer>, TextError> {
+        match self.atlas_mode {
+            AtlasMode::Bitmap => {
+                let source = create_source(self.font_size, self.line_width)?;
+                match self.get_shadow_blur_radius() {
+                    0 => Ok(source),
+                    radius => Ok(Box::new(ShadowRasterizer::new(source, radius)))
+                }
+            },
+            AtlasMode::DistanceField { font_size, spread } => {
+
+                // The stroke will be handled by the shader, so the source rasterizer shouldn't draw it
+                let source = create_source(font_size * DISTANCE_FIELD_SUPERSAMPLING as usize, 0.0)?;
+                Ok(Box::new(DistanceFieldRasterizer::new(source, DISTANCE_FIELD_SUPERSAMPLING, (spread * font_size as f64) as f32)))
+            }
+        }
+    }
+
+    /// Gets the radius (in pixels of the backing texture) of the blurred shadows of the next bitmap fonts, or 0 if their
+    /// shadows can't be blurred
+    fn get_shadow_blur_radius(&self) -> u32 {
+        (self.shadow_blur.max(0.0) * self.font_size as f64).ceil() as u32
+    }
+
+    fn create_font(&mut self, font_id: FontID, font_details: FontDetails, rasterizer: Box<dyn GlyphRasterizer>) -> Result<Rc<Font>, TextError> {
+        let (shader_program, distance_field) = match self.atlas_mode {
+            AtlasMode::Bitmap => (Rc::clone(&self.shader_program), None),
+            AtlasMode::DistanceField { font_size, spread } => {
+                let program = match &self.distance_field_program {
+                    Some(program) => Rc::clone(program),
+                    None => {
+                        let program = Rc::new(RefCell::new(TextProgram::create_distance_field_instance(Rc::clone(&self.backend))?));
+                        self.distance_field_program = Some(Rc::clone(&program));
+                        program
+                    }
+                };
+                (program, Some(DistanceFieldProperties {
+                    font_size: font_size as f32,
+                    spread: (spread * font_size as f64) as f32,
+                    line_width: self.line_width as f32
+                }))
+            }
+        };
+        let soft_shadows = match self.atlas_mode {
+            AtlasMode::Bitmap => self.get_shadow_blur_radius() > 0,
+            AtlasMode::DistanceField { .. } => true
+        };
+        Ok(Rc::new(Font::new(Rc::clone(&self.backend), shader_program, font_id, Rc::clone(&self.selected_font), font_details, rasterizer, self.dynamic_atlas, self.atlas_options, distance_field, self.line_spacing, &self.all_chars, self.fallback_char, self.compact_texture_coords, soft_shadows)?))
+    }
+
+    /// Creates an empty TextBatch for the given Font, which must have been created by this TextRenderer. A TextBatch can draw
+    /// many strings of the Font with only 1 draw call per atlas page, see the description of TextBatch for more information.
+    ///
+    /// This method will panic if the shader program or buffer of the batch can't be created. Use try_create_text_batch to
+    /// handle that case.
+    pub fn create_text_batch(&mut self, font: &Rc<Font>) -> TextBatch {
+        self.try_create_text_batch(font).expect("Should be able to create the text batch")
+    }
+
+    /// Creates an empty TextBatch for the given Font, just like create_text_batch, but returns an error rather than panicking
+    /// if the shader program or buffer of the batch can't be created.
+    pub fn try_create_text_batch(&mut self, font: &Rc<Font>) -> Result<TextBatch, TextError> {
+
+        // The batch programs are only created when they are needed for the first time
+        let distance_field = font.distance_field.is_some();
+        let existing_program = if distance_field { &self.distance_field_batch_program } else { &self.batch_program };
+        let program = match existing_program {
+            Some(program) => Rc::clone(program),
+            None => {
+                let program = Rc::new(match distance_field {
+                    true => BatchProgram::create_distance_field_instance(Rc::clone(&self.backend))?,
+                    false => BatchProgram::create_instance(Rc::clone(&self.backend))?
+                });
+                if distance_field {
+                    self.distance_field_batch_program = Some(Rc::clone(&program));
+                } else {
+                    self.batch_program = Some(Rc::clone(&program));
+                }
+                program
+            }
+        };
+        TextBatch::new(Rc::clone(font), program)
+    }
+
+    /// Creates a RichTextModel for the given spans of text, which will be drawn with the given Font. The Font must have been
+    /// created by this TextRenderer. Every span has its own colors, but the entire text can still be drawn with only 1 draw
+    /// call per atlas page, see the description of RichTextModel for more information.
+    ///
+    /// Just like for the create_text_model method of Font, the text of the spans can contain '\n' line breaks. Spans can
+    /// use another Font (like a bold variant of the given Font) than the given Font, see the font of TextSpan.
+    ///
+    /// This method will panic if the shader program or buffer of the model can't be created. Use try_create_rich_text_model
+    /// to handle that case.
+    pub fn create_rich_text_model(&mut self, font: &Rc<Font>, spans: &[TextSpan]) -> RichTextModel {
+        self.try_create_rich_text_model(font, spans).expect("Should be able to create the rich text model")
+    }
+
+    /// Creates a RichTextModel for the given spans, just like create_rich_text_model, but returns an error rather than
+    /// panicking if the shader program or buffer of the model can't be created.
+    pub fn try_create_rich_text_model(&mut self, font: &Rc<Font>, spans: &[TextSpan]) -> Result<RichTextModel, TextError> {
+        let layout = font.layout_spans(spans);
+        self.create_rich_model_from_layout(font, spans, layout)
+    }
+
+    /// Creates a RichTextModel for the given spans that will be at most max_width wide when it is rendered with the given
+    /// scale_y. The lines will be wrapped like the create_wrapped_text_model method of Font would do, even in the middle of
+    /// a span.
+    ///
+    /// This method will panic if the shader program or buffer of the model can't be created. Use
+    /// try_create_wrapped_rich_text_model to handle that case.
+    pub fn create_wrapped_rich_text_model(&mut self, font: &Rc<Font>, spans: &[TextSpan], max_width: f32, scale_y: f32) -> RichTextModel {
+        self.try_create_wrapped_rich_text_model(font, spans, max_width, scale_y).expect("Should be able to create the rich text model")
+    }
+
+    /// Creates a wrapped RichTextModel for the given spans, just like create_wrapped_rich_text_model, but returns an error
+    /// rather than panicking if the shader program or buffer of the model can't be created.
+    pub fn try_create_wrapped_rich_text_model(&mut self, font: &Rc<Font>, spans: &[TextSpan], max_width: f32, scale_y: f32) -> Result<RichTextModel, TextError> {
+        let layout = font.layout_wrapped_spans(spans, max_width, scale_y);
+        self.create_rich_model_from_layout(font, spans, layout)
+    }
+
+    fn create_rich_model_from_layout(&mut self, font: &Rc<Font>, spans: &[TextSpan], layout: TextLayout) -> Result<RichTextModel, TextError> {
+        let (fonts, span_fonts) = collect_span_fonts(font, spans);
+        let mut fonts_with_programs = Vec::with_capacity(fonts.len());
+        for span_font in fonts {
+            let program = self.get_rich_program(&span_font)?;
+            fonts_with_programs.push((span_font, program));
+        }
+        RichTextModel::new(fonts_with_programs, span_fonts, layout, spans)
+    }
+
+    /// Gets the shader program for the RichTextModel's of the given Font. Just like the batch programs, the rich text
+    /// programs are only created when they are needed for the first time.
+    fn get_rich_program(&mut self, font: &Font) -> Result<Rc<RichTextProgram>, TextError> {
+        let distance_field = font.distance_field.is_some();
+        let existing_program = if distance_field { &self.distance_field_rich_program } else { &self.rich_program };
+        if let Some(program) = existing_program {
+            return Ok(Rc::clone(program));
+        }
+
+        let program = Rc::new(match distance_field {
+            true => RichTextProgram::create_distance_field_instance(Rc::clone(&self.backend))?,
+            false => RichTextProgram::create_instance(Rc::clone(&self.backend))?
+        });
+        if distance_field {
+            self.distance_field_rich_program = Some(Rc::clone(&program));
+        } else {
+            self.rich_program = Some(Rc::clone(&program));
+        }
+        Ok(program)
     }
 
     /// Gets a previously created Font (with add_font or add_fonts) by its FontDetails. It will return the reference to the first
@@ -181,27 +586,45 @@ impl TextRenderer {
     /// TextRenderer has taken place. With external, I mean any rendering that wasn't done by this crate.
     pub fn start_rendering(&mut self){
 
-        let gl = &self.gl;
-        let maybe_bound_canvas = gl.canvas();
+        let backend = &self.backend;
+        let maybe_surface_size = backend.get_surface_size();
 
         // We don't know what happened before the GUI rendering, so let's not make any assumptions about our current font
         self.selected_font.set(None);
 
         // If there is no canvas bound to it anymore, don't bother rendering
-        if maybe_bound_canvas.is_some() {
+        if let Some((width, height)) = maybe_surface_size {
 
-            // The fonts need to know the aspect ratio for nice text rendering
-            let bound_canvas = maybe_bound_canvas.unwrap().dyn_into::<HtmlCanvasElement>().expect("The bound webgl canvas should be a canvas element");
-            let aspect_ratio = bound_canvas.width() as f32 / bound_canvas.height() as f32;
+            // The fonts need to know the aspect ratio (and distance field fonts need to know the height) for nice text rendering
             for font in &self.fonts {
-                font.set_aspect_ratio(aspect_ratio);
+                font.set_surface_size(width, height);
             }
 
             // And finally actually start rendering
             let shader = self.shader_program.borrow();
             shader.use_program();
-            gl.enable(GL::BLEND);
-            gl.blend_func_separate(GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA, GL::ONE, GL::ONE_MINUS_SRC_ALPHA);
+            backend.enable_blending();
+
+            // The external rendering might have left its own vertex array object bound
+            if backend.supports_instancing() {
+                backend.bind_vertex_array(None);
+            }
         }
     }
+
+    /// Draws the given text with the given Font without the need to create a TextModel, which is convenient for text that
+    /// changes every frame, like debug overlays. The parameters have the same meaning as for the render method of TextModel.
+    ///
+    /// The text won't be drawn immediately: it will be queued until the end_rendering method of this TextRenderer is called,
+    /// so end_rendering should be called at the end of every frame in which this method was used. All text of a frame will
+    /// be drawn from a single streaming buffer that is reused every frame.
+    pub fn draw_text(&mut self, font: &Rc<Font>, text: &str, offset_x: f32, offset_y: f32, scale_y: f32, colors: TextColors){
+        self.immediate_text.push(font, text, offset_x, offset_y, scale_y, colors);
+    }
+
+    /// Draws all text that was queued with the draw_text method since the last call to this method. This should be called
+    /// at the end of every frame, after start_rendering.
+    pub fn end_rendering(&mut self){
+        self.immediate_text.flush();
+    }
 }
\ No newline at end of file
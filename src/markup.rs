@@ -0,0 +1,278 @@
+use wasmuri_core::*;
+
+use super::decoration::TextDecorations;
+use super::error::TextError;
+use super::rich::TextSpan;
+
+/// The colors and style of the text at some point of the markup
+#[derive(Clone,Copy)]
+struct MarkupStyle {
+
+    colors: TextColors,
+    bold: bool,
+    italic: bool,
+    decorations: TextDecorations
+}
+
+/// A tag that has been opened, but not yet closed
+struct OpenTag<'a> {
+
+    name: &'a str,
+
+    /// The index (in bytes) of the '[' of the tag in the markup
+    offset: usize,
+
+    /// The style before the tag was opened, which will be restored when the tag is closed
+    previous_style: MarkupStyle
+}
+
+/// Parses the given markup into a list of TextSpan's, which can be drawn with a RichTextModel (see the
+/// create_rich_text_model method of TextRenderer). The text outside of any tags will get the given colors.
+///
+/// The markup supports the following tags, which can be nested. Colors are written as #rrggbb or #rrggbbaa.
+///
+/// ```text
+/// [b]bold text[/b]
+/// [i]italic text[/i]
+/// [u]underlined text[/u]
+/// [s]text with a strikethrough line[/s]
+/// [color=#ff0000]text with a red fill color[/color]
+/// [stroke=#0000ff]text with a blue stroke color[/stroke]
+/// [background=#ffff0080]text with a transparent yellow background[/background]
+/// ```
+///
+/// Every tag must be closed by the matching closing tag, and the tags must be closed in the reverse order in which they
+/// were opened. A backslash escapes the next character, which must be a bracket or another backslash:
+///
+/// ```text
+/// \[not a tag\] and a backslash: \\
+/// ```
+///
+/// If the markup is invalid (for instance because of an unknown tag or a tag that is never closed), a TextError of the
+/// InvalidMarkup kind will be returned, whose offset is the index (in bytes) of the problem in the markup.
+pub fn parse_markup(markup: &str, colors: TextColors) -> Result<Vec<TextSpan>, TextError> {
+    let mut spans = Vec::new();
+    let mut open_tags: Vec<OpenTag> = Vec::new();
+    let mut style = MarkupStyle {
+        colors,
+        bold: false,
+        italic: false,
+        decorations: TextDecorations::NONE
+    };
+    let mut text = String::new();
+
+    let mut index = 0;
+    while let Some(current) = markup[index..].chars().next() {
+        let offset = index;
+        index += current.len_utf8();
+        match current {
+            '\\' => {
+                match markup[index..].chars().next() {
+                    Some(escaped) if escaped == '[' || escaped == ']' || escaped == '\\' => text.push(escaped),
+                    Some(escaped) => return Err(invalid_markup(offset, format!("Unknown escape sequence \\{}", escaped))),
+                    None => return Err(invalid_markup(offset, "The markup ends in the middle of an escape sequence".to_string()))
+                };
+                index += 1;
+            },
+            '[' => {
+                let tag_length = markup[index..].find(']').ok_or_else(|| invalid_markup(offset, "The tag doesn't have a closing ']'".to_string()))?;
+                let tag = &markup[index..index + tag_length];
+                index += tag_length + 1;
+
+                // The text before the tag still has the style from before the tag
+                if !text.is_empty() {
+                    spans.push(create_span(std::mem::take(&mut text), style));
+                }
+
+                match tag.strip_prefix('/') {
+                    Some(name) => {
+                        let open_tag = open_tags.pop().ok_or_else(|| invalid_markup(offset, format!("The tag [/{}] doesn't close any tag", name)))?;
+                        if open_tag.name != name {
+                            return Err(invalid_markup(offset, format!("Expected [/{}], but found [/{}]", open_tag.name, name)));
+                        }
+                        style = open_tag.previous_style;
+                    }, None => {
+                        let previous_style = style;
+                        let (name, value) = match tag.split_once('=') {
+                            Some((name, value)) => (name, Some(value)),
+                            None => (tag, None)
+                        };
+
+                        // The value starts after the '[', the name and the '='
+                        let value_offset = offset + 1 + name.len() + 1;
+                        match (name, value) {
+                            ("b", None) => style.bold = true,
+                            ("i", None) => style.italic = true,
+                            ("u", None) => style.decorations.underline = true,
+                            ("s", None) => style.decorations.strikethrough = true,
+                            ("color", Some(value)) => style.colors.fill_color = parse_color(value, value_offset)?,
+                            ("stroke", Some(value)) => style.colors.stroke_color = parse_color(value, value_offset)?,
+                            ("background", Some(value)) => style.colors.background_color = parse_color(value, value_offset)?,
+                            ("b", Some(_)) | ("i", Some(_)) | ("u", Some(_)) | ("s", Some(_)) => return Err(invalid_markup(offset, format!("The tag [{}] doesn't have a value", name))),
+                            ("color", None) | ("stroke", None) | ("background", None) => {
+                                return Err(invalid_markup(offset, format!("The tag [{}] needs a color, like [{}=#ff0000]", name, name)));
+                            },
+                            _ => return Err(invalid_markup(offset, format!("Unknown tag [{}]", tag)))
+                        };
+                        open_tags.push(OpenTag {
+                            name,
+                            offset,
+                            previous_style
+                        });
+                    }
+                };
+            },
+            ']' => return Err(invalid_markup(offset, "Unexpected ']', use \\] to write a literal ']'".to_string())),
+            _ => text.push(current)
+        };
+    }
+
+    if let Some(open_tag) = open_tags.last() {
+        return Err(invalid_markup(open_tag.offset, format!("The tag [{}] is never closed", open_tag.name)));
+    }
+    if !text.is_empty() {
+        spans.push(create_span(text, style));
+    }
+    Ok(spans)
+}
+
+fn create_span(text: String, style: MarkupStyle) -> TextSpan {
+    TextSpan {
+        text,
+        colors: style.colors,
+        bold: style.bold,
+        italic: style.italic,
+        decorations: style.decorations,
+        font: None
+    }
+}
+
+/// Parses a color of the form #rrggbb or #rrggbbaa. The offset is the index (in bytes) of the color in the markup.
+fn parse_color(value: &str, offset: usize) -> Result<Color, TextError> {
+    let invalid_color = || invalid_markup(offset, format!("Invalid color {}, expected #rrggbb or #rrggbbaa", value));
+    let hex = value.strip_prefix('#').ok_or_else(invalid_color)?;
+    if (hex.len() != 6 && hex.len() != 8) || !hex.chars().all(|digit| digit.is_ascii_hexdigit()) {
+        return Err(invalid_color());
+    }
+
+    let component = |index: usize| u8::from_str_radix(&hex[2 * index..2 * index + 2], 16).map_err(|_| invalid_color());
+    let alpha = if hex.len() == 8 { component(3)? } else { 255 };
+    Ok(Color::from_rgba(component(0)?, component(1)?, component(2)?, alpha))
+}
+
+fn invalid_markup(offset: usize, message: String) -> TextError {
+    TextError::InvalidMarkup {
+        offset,
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use super::super::shaders::get_color_components;
+
+    fn colors() -> TextColors {
+        TextColors {
+            fill_color: Color::from_rgb(0, 0, 0),
+            stroke_color: Color::from_rgb(255, 255, 255),
+            background_color: Color::from_rgba(0, 0, 0, 0)
+        }
+    }
+
+    /// Parses the given markup, which must be invalid, and returns the offset of the error
+    fn error_offset(markup: &str) -> usize {
+        match parse_markup(markup, colors()) {
+            Err(TextError::InvalidMarkup { offset, .. }) => offset,
+            Err(error) => panic!("Expected InvalidMarkup for {}, but got {}", markup, error),
+            Ok(_) => panic!("Expected {} to be invalid", markup)
+        }
+    }
+
+    #[test]
+    fn plain_text_is_a_single_span() {
+        let spans = parse_markup("Hello, world!", colors()).unwrap();
+        assert_eq!(1, spans.len());
+        assert_eq!("Hello, world!", spans[0].text);
+        assert!(!spans[0].bold && !spans[0].italic && spans[0].decorations.is_empty());
+        assert_eq!(get_color_components(colors().fill_color), get_color_components(spans[0].colors.fill_color));
+        assert!(parse_markup("", colors()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn nested_tags_combine_and_restore_the_style() {
+        let spans = parse_markup("a[b]b[i]c[color=#ff000080]d[/color][/i][u][s]e[/s][/u][/b]f", colors()).unwrap();
+        let texts: Vec<&str> = spans.iter().map(|span| span.text.as_str()).collect();
+        assert_eq!(vec!["a", "b", "c", "d", "e", "f"], texts);
+
+        let styles: Vec<(bool, bool)> = spans.iter().map(|span| (span.bold, span.italic)).collect();
+        assert_eq!(vec![(false, false), (true, false), (true, true), (true, true), (true, false), (false, false)], styles);
+
+        assert_eq!([1.0, 0.0, 0.0, 128.0 / 255.0], get_color_components(spans[3].colors.fill_color));
+        assert_eq!(get_color_components(colors().fill_color), get_color_components(spans[2].colors.fill_color));
+        assert_eq!(get_color_components(colors().fill_color), get_color_components(spans[4].colors.fill_color));
+        assert_eq!(TextDecorations { underline: true, strikethrough: true, overline: false }, spans[4].decorations);
+        assert!(spans[5].decorations.is_empty());
+    }
+
+    #[test]
+    fn stroke_and_background_tags_change_their_colors() {
+        let spans = parse_markup("[stroke=#00ff00][background=#0000FF]x[/background][/stroke]", colors()).unwrap();
+        assert_eq!(1, spans.len());
+        assert_eq!([0.0, 1.0, 0.0, 1.0], get_color_components(spans[0].colors.stroke_color));
+        assert_eq!([0.0, 0.0, 1.0, 1.0], get_color_components(spans[0].colors.background_color));
+        assert_eq!(get_color_components(colors().fill_color), get_color_components(spans[0].colors.fill_color));
+    }
+
+    #[test]
+    fn escapes_are_written_literally() {
+        let spans = parse_markup("\\[b\\] \\\\ [b]\\]é[/b]", colors()).unwrap();
+        assert_eq!(2, spans.len());
+        assert_eq!("[b] \\ ", spans[0].text);
+        assert!(!spans[0].bold);
+        assert_eq!("]é", spans[1].text);
+        assert!(spans[1].bold);
+    }
+
+    #[test]
+    fn errors_point_to_the_problem() {
+        // Unknown escape sequence and an escape at the end of the markup
+        assert_eq!(2, error_offset("ab\\n"));
+        assert_eq!(2, error_offset("ab\\"));
+
+        // A tag without a closing bracket
+        assert_eq!(1, error_offset("a[b"));
+
+        // A closing tag without an opening tag, and tags that are closed in the wrong order
+        assert_eq!(1, error_offset("a[/b]"));
+        assert_eq!(8, error_offset("[b]x[i]y[/b][/i]"));
+
+        // Tags that don't expect a value, tags that need a color and unknown tags
+        assert_eq!(1, error_offset("x[b=#ffffff]y[/b]"));
+        assert_eq!(1, error_offset("x[color]y[/color]"));
+        assert_eq!(1, error_offset("x[q]y[/q]"));
+
+        // Invalid colors point to the start of the value
+        assert_eq!(8, error_offset("x[color=red]y[/color]"));
+        assert_eq!(9, error_offset("x[stroke=#fff]y[/stroke]"));
+        assert_eq!(13, error_offset("x[background=#gg0000]y[/background]"));
+
+        // An unescaped closing bracket
+        assert_eq!(3, error_offset("abc]"));
+
+        // A tag that is never closed points to the opening tag
+        assert_eq!(0, error_offset("[b]x[i]y[/i]"));
+    }
+
+    #[test]
+    fn offsets_are_byte_indices_in_non_ascii_markup() {
+        assert_eq!(2, error_offset("é["));
+        assert_eq!(0, error_offset("\\é"));
+        assert_eq!(2, error_offset("é\\é"));
+        assert_eq!(8, error_offset("x[color=#12345é]y[/color]"));
+        assert_eq!(9, error_offset("é[color=#12345é]y[/color]"));
+        assert_eq!(2, error_offset("é]"));
+        assert_eq!(2, error_offset("é[b]x"));
+    }
+}
@@ -0,0 +1,284 @@
+/// The TextMetrics describe the size of a string when it would be rendered with a Font. They can be obtained with the
+/// measure method of Font, which doesn't need to create any TextModel or buffer. All values are expressed in the OpenGL
+/// coordinate space, just like the results of the get_render_width and get_render_height methods of TextModel.
+#[derive(PartialEq,Clone,Debug)]
+pub struct TextMetrics {
+
+    width: f32,
+    height: f32,
+
+    ascent: f32,
+    descent: f32,
+
+    advances: Vec<f32>
+}
+
+impl TextMetrics {
+
+    pub(super) fn new(width: f32, height: f32, ascent: f32, descent: f32, advances: Vec<f32>) -> TextMetrics {
+        TextMetrics {
+            width,
+            height,
+            ascent,
+            descent,
+            advances
+        }
+    }
+
+    /// Gets the width of the measured text. For text with multiple lines, this is the width of the longest line. This is
+    /// the same as the get_render_width method of a TextModel for the same text would return.
+    pub fn get_width(&self) -> f32 {
+        self.width
+    }
+
+    /// Gets the height of the measured text, including the spacing between its lines. This is the same as the
+    /// get_render_height method of a TextModel for the same text would return.
+    pub fn get_height(&self) -> f32 {
+        self.height
+    }
+
+    /// Gets the distance from the top of the first line to the baseline of the characters on that line.
+    pub fn get_ascent(&self) -> f32 {
+        self.ascent
+    }
+
+    /// Gets the distance from the baseline of the characters on the last line to the bottom of that line.
+    pub fn get_descent(&self) -> f32 {
+        self.descent
+    }
+
+    /// Gets the horizontal advance of every char of the measured text, in the same order as the chars of the text. Line
+    /// breaks and chars that the Font can't draw have an advance of 0.0. The kerning between a char and the char before it
+    /// is included in the advance of the char, so the sum of the advances of the chars on a line is the width of that line.
+    pub fn get_advances(&self) -> &[f32] {
+        &self.advances
+    }
+}
+
+/// The GlyphMetrics describe the shape of a single character of a Font. They can be obtained with the get_glyph_metrics
+/// method of Font. All values are expressed in the OpenGL coordinate space for the scale_y that was passed to that method,
+/// just like the values of TextMetrics.
+/// 
+/// The metrics are relative to the origin of the character, which is the point on the baseline where the character starts.
+/// The 'ink' of the character is the part of the character that is actually drawn.
+#[derive(PartialEq,Copy,Clone,Debug,Default)]
+pub struct GlyphMetrics {
+
+    advance: f32,
+
+    left_bearing: f32,
+    right_bearing: f32,
+
+    ascent: f32,
+    descent: f32
+}
+
+impl GlyphMetrics {
+
+    pub(super) fn new(advance: f32, left_bearing: f32, right_bearing: f32, ascent: f32, descent: f32) -> GlyphMetrics {
+        GlyphMetrics {
+            advance,
+            left_bearing,
+            right_bearing,
+            ascent,
+            descent
+        }
+    }
+
+    /// Scales all horizontal values of these metrics by scale_x and all vertical values by scale_y
+    pub(super) fn scale(&self, scale_x: f32, scale_y: f32) -> GlyphMetrics {
+        GlyphMetrics {
+            advance: scale_x * self.advance,
+            left_bearing: scale_x * self.left_bearing,
+            right_bearing: scale_x * self.right_bearing,
+            ascent: scale_y * self.ascent,
+            descent: scale_y * self.descent
+        }
+    }
+
+    /// Gets the horizontal distance from the origin of this character to the origin of the next character (without
+    /// kerning).
+    pub fn get_advance(&self) -> f32 {
+        self.advance
+    }
+
+    /// Gets the horizontal distance from the origin of this character to the left edge of its ink. This is negative if the
+    /// ink starts left of the origin, which happens for some italic characters.
+    pub fn get_left_bearing(&self) -> f32 {
+        self.left_bearing
+    }
+
+    /// Gets the horizontal distance from the right edge of the ink of this character to the origin of the next character.
+    /// This is negative if the ink exceeds the advance, like the top of an italic 'f'.
+    pub fn get_right_bearing(&self) -> f32 {
+        self.right_bearing
+    }
+
+    /// Gets the distance from the baseline to the top of the ink of this character.
+    pub fn get_ascent(&self) -> f32 {
+        self.ascent
+    }
+
+    /// Gets the distance from the baseline to the bottom of the ink of this character. This is positive for characters
+    /// that go below the baseline, like 'g' and 'y'.
+    pub fn get_descent(&self) -> f32 {
+        self.descent
+    }
+
+    /// Gets the width of the ink of this character.
+    pub fn get_ink_width(&self) -> f32 {
+        self.advance - self.left_bearing - self.right_bearing
+    }
+}
+
+/// The FontMetrics describe the vertical metrics that all characters of a Font share. They can be obtained with the
+/// get_font_metrics method of Font. All values are expressed in the OpenGL coordinate space for the scale_y that was
+/// passed to that method.
+#[derive(PartialEq,Copy,Clone,Debug,Default)]
+pub struct FontMetrics {
+
+    ascent: f32,
+    descent: f32,
+    line_gap: f32,
+
+    underline_position: f32,
+    underline_thickness: f32,
+
+    strikethrough_position: f32,
+    strikethrough_thickness: f32
+}
+
+impl FontMetrics {
+
+    /// Creates FontMetrics with the given ascent, descent and line gap. Fonts that don't specify the position and thickness
+    /// of their underline and strikethrough get decoration metrics that are derived from their ascent and descent. Use
+    /// with_underline and with_strikethrough to use the metrics of the font instead.
+    pub(super) fn new(ascent: f32, descent: f32, line_gap: f32) -> FontMetrics {
+        let thickness = (ascent + descent) / 16.0;
+        FontMetrics {
+            ascent,
+            descent,
+            line_gap,
+            underline_position: 0.4 * descent,
+            underline_thickness: thickness,
+            strikethrough_position: 0.3 * ascent,
+            strikethrough_thickness: thickness
+        }
+    }
+
+    /// Replaces the underline metrics. The position is the distance from the baseline to the top of the underline, which
+    /// is positive when the underline is below the baseline.
+    pub(super) fn with_underline(self, position: f32, thickness: f32) -> FontMetrics {
+        FontMetrics {
+            underline_position: position,
+            underline_thickness: thickness,
+            ..self
+        }
+    }
+
+    /// Replaces the strikethrough metrics. The position is the distance from the baseline to the top of the strikethrough
+    /// line, which is positive when the line is above the baseline.
+    pub(super) fn with_strikethrough(self, position: f32, thickness: f32) -> FontMetrics {
+        FontMetrics {
+            strikethrough_position: position,
+            strikethrough_thickness: thickness,
+            ..self
+        }
+    }
+
+    /// Scales all values of these metrics by scale_y
+    pub(super) fn scale(&self, scale_y: f32) -> FontMetrics {
+        FontMetrics {
+            ascent: scale_y * self.ascent,
+            descent: scale_y * self.descent,
+            line_gap: scale_y * self.line_gap,
+            underline_position: scale_y * self.underline_position,
+            underline_thickness: scale_y * self.underline_thickness,
+            strikethrough_position: scale_y * self.strikethrough_position,
+            strikethrough_thickness: scale_y * self.strikethrough_thickness
+        }
+    }
+
+    /// Gets the distance from the baseline to the top of the highest characters of the font, as specified by the font.
+    pub fn get_ascent(&self) -> f32 {
+        self.ascent
+    }
+
+    /// Gets the distance from the baseline to the bottom of the lowest characters of the font, as specified by the font.
+    /// This is positive.
+    pub fn get_descent(&self) -> f32 {
+        self.descent
+    }
+
+    /// Gets the extra space the font recommends between the descent of a line and the ascent of the next line.
+    pub fn get_line_gap(&self) -> f32 {
+        self.line_gap
+    }
+
+    /// Gets the distance from the baseline to the top of the underline. This is positive when the underline is below the
+    /// baseline, which is the case for (almost) all fonts.
+    pub fn get_underline_position(&self) -> f32 {
+        self.underline_position
+    }
+
+    /// Gets the thickness of the underline. Overlines have the same thickness.
+    pub fn get_underline_thickness(&self) -> f32 {
+        self.underline_thickness
+    }
+
+    /// Gets the distance from the baseline to the top of the strikethrough line. This is positive.
+    pub fn get_strikethrough_position(&self) -> f32 {
+        self.strikethrough_position
+    }
+
+    /// Gets the thickness of the strikethrough line.
+    pub fn get_strikethrough_thickness(&self) -> f32 {
+        self.strikethrough_thickness
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn glyph_metrics_are_scaled_per_axis() {
+        let metrics = GlyphMetrics::new(10.0, -1.0, 2.0, 7.0, 3.0);
+        assert_eq!(metrics.get_ink_width(), 9.0);
+
+        let scaled = metrics.scale(0.5, 2.0);
+        assert_eq!(scaled.get_advance(), 5.0);
+        assert_eq!(scaled.get_left_bearing(), -0.5);
+        assert_eq!(scaled.get_right_bearing(), 1.0);
+        assert_eq!(scaled.get_ascent(), 14.0);
+        assert_eq!(scaled.get_descent(), 6.0);
+        assert_eq!(scaled.get_ink_width(), 4.5);
+    }
+
+    #[test]
+    fn font_metrics_are_scaled() {
+        let metrics = FontMetrics::new(12.0, 4.0, 2.0).scale(0.25);
+        assert_eq!(metrics.get_ascent(), 3.0);
+        assert_eq!(metrics.get_descent(), 1.0);
+        assert_eq!(metrics.get_line_gap(), 0.5);
+    }
+
+    #[test]
+    fn decoration_metrics_can_be_replaced() {
+
+        // Without decoration metrics from the font, they are derived from the ascent and descent
+        let metrics = FontMetrics::new(12.0, 4.0, 2.0);
+        assert!((metrics.get_underline_position() - 1.6).abs() < 0.0001);
+        assert_eq!(metrics.get_underline_thickness(), 1.0);
+        assert!((metrics.get_strikethrough_position() - 3.6).abs() < 0.0001);
+        assert_eq!(metrics.get_strikethrough_thickness(), 1.0);
+
+        let metrics = metrics.with_underline(2.0, 0.5).with_strikethrough(5.0, 0.75).scale(2.0);
+        assert_eq!(metrics.get_underline_position(), 4.0);
+        assert_eq!(metrics.get_underline_thickness(), 1.0);
+        assert_eq!(metrics.get_strikethrough_position(), 10.0);
+        assert_eq!(metrics.get_strikethrough_thickness(), 1.5);
+        assert_eq!(metrics.get_ascent(), 24.0);
+    }
+}
@@ -1,52 +1,198 @@
-use web_sys::WebGlBuffer;
-use web_sys::WebGlRenderingContext;
-use web_sys::WebGlRenderingContext as GL;
-
 use wasmuri_core::*;
 
-use super::shaders::TextProgram;
+use super::alignment::*;
+use super::backend::{
+    AttributeType,
+    BufferHandle,
+    BufferUsage
+};
+use super::decoration::{
+    DecorationLine,
+    TextDecorations,
+    compute_decoration_lines
+};
+use super::layout::{
+    TextLayout,
+    get_vertex_size
+};
+use super::shaders::{
+    FLOATS_PER_INSTANCE,
+    InstanceArray,
+    MAX_QUADS_PER_DRAW,
+    TextProgram
+};
+use super::shadow::TextShadow;
 use super::Font;
 
+use std::cell::{
+    Cell,
+    RefCell
+};
 use std::rc::Rc;
 
 /// Instances of TextModel can be used to draw text on their webgl context. They can be created with the create_text_model
 /// method of Font's.
 /// 
 /// To use an instance of TextModel, call its render method and read its description to see what all the parameters are for.
+/// 
+/// The text of a TextModel can be changed with its set_text method, which will reuse the buffer of the TextModel. This is
+/// much cheaper than creating a new TextModel for labels that change often.
+///
+/// Use set_decorations to underline, strike through or overline the text of a TextModel, and set_shadow to give it a
+/// drop shadow.
 pub struct TextModel {
 
     font: Rc<Font>,
 
-    buffer: WebGlBuffer,
+    buffer: BufferHandle,
+
+    /// The vertex array object of this TextModel, or None if the shader program of the Font is not instanced
+    instance_array: Option<InstanceArray>,
+
+    /// The number of bytes the buffer can hold
+    buffer_capacity: Cell<usize>,
+
+    /// The usage hint of the buffer. This will become Dynamic once the text of this TextModel is changed.
+    buffer_usage: BufferUsage,
+
+    layout: TextLayout,
+
+    /// The atlas page, first quad and quad count of every draw call that is needed to render this TextModel
+    page_draws: Vec<(usize, i32, i32)>,
+
+    decorations: TextDecorations,
+    shadow: Option<TextShadow>,
+
+    /// The arrangement that was used to create the current content of the buffer, or None if the buffer doesn't contain
+    /// the vertex data of the current layout
+    arrangement: RefCell<Option<Arrangement>>
+}
+
+/// The horizontal alignment, region width (in model space) and atlas page sizes of the Font that determine the content of
+/// the buffer of a TextModel
+#[derive(PartialEq)]
+struct Arrangement {
 
-    vertex_count: i32,
-    total_width: f32
+    alignment: HorizontalAlignment,
+    region_width: f32,
+    page_sizes: Vec<(u32, u32)>
 }
 
 impl TextModel {
 
-    pub(super) fn new(font: Rc<Font>, buffer: WebGlBuffer, char_count: usize, total_width: f32) -> TextModel {
+    /// Creates a TextModel with the given layout. The buffer should contain the left-aligned buffer data of the layout (see
+    /// the create_buffer_data method of Font), which consists of buffer_capacity bytes.
+    pub(super) fn new(font: Rc<Font>, buffer: BufferHandle, instance_array: Option<InstanceArray>, layout: TextLayout, buffer_capacity: usize) -> TextModel {
+        let page_sizes = font.get_page_sizes();
         TextModel {
             font,
             buffer,
-            vertex_count: (char_count * 6) as i32,
-            total_width
+            instance_array,
+            buffer_capacity: Cell::new(buffer_capacity),
+            buffer_usage: BufferUsage::Static,
+            page_draws: compute_page_draws(&layout),
+            layout,
+            decorations: TextDecorations::NONE,
+            shadow: None,
+            arrangement: RefCell::new(Some(Arrangement {
+                alignment: HorizontalAlignment::Left,
+                region_width: 0.0,
+                page_sizes
+            }))
         }
     }
 
-    pub(super) fn bind(&self, shader_program: &TextProgram){
-        let gl = &self.get_font().gl;
+    /// Replaces the text of this TextModel with the given text. Just like for the create_text_model method of Font, the
+    /// text can consist of multiple lines, separated by '\n' characters.
+    /// 
+    /// Unlike creating a new TextModel, this will reuse the buffer of this TextModel. The buffer will only be reallocated
+    /// when the new text has more characters than the buffer can hold. Once the text of a TextModel has been changed, its
+    /// buffer will be treated as a dynamic buffer, so this method can be called every frame.
+    pub fn set_text(&mut self, text: &str){
+        let layout = self.font.layout_text(text);
+        self.set_layout(layout);
+    }
+
+    /// Replaces the text of this TextModel with the given text, which will be wrapped like the create_wrapped_text_model
+    /// method of Font would do. See set_text for more information.
+    pub fn set_wrapped_text(&mut self, text: &str, max_width: f32, scale_y: f32){
+        let layout = self.font.layout_wrapped_text(text, max_width, scale_y);
+        self.set_layout(layout);
+    }
 
-        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.buffer));
+    /// Changes the decoration lines that will be drawn along the text of this TextModel. The decoration lines will have the
+    /// fill color that is passed to the render methods, and every line of the text gets its own decoration lines. Changing
+    /// the decorations doesn't require updating the buffer of this TextModel.
+    pub fn set_decorations(&mut self, decorations: TextDecorations){
+        self.decorations = decorations;
+    }
 
-        let num_components = 2;
+    pub fn get_decorations(&self) -> TextDecorations {
+        self.decorations
+    }
 
-        gl.vertex_attrib_pointer_with_i32(shader_program.get_relative_position() as u32, num_components, WebGlRenderingContext::FLOAT, false, 0, 0);
-        gl.enable_vertex_attrib_array(shader_program.get_relative_position() as u32);
+    /// Changes the shadow that will be drawn behind the text of this TextModel, or removes the shadow if it is None. The
+    /// shadow will be drawn by the same render call as the text, right before the characters (but after the background).
+    /// Just like the decorations, changing the shadow doesn't require updating the buffer of this TextModel.
+    pub fn set_shadow(&mut self, shadow: Option<TextShadow>){
+        self.shadow = shadow;
+    }
 
-        let f32_size = 4;
-        gl.vertex_attrib_pointer_with_i32(shader_program.get_texture_coords() as u32, num_components, WebGlRenderingContext::FLOAT, false, 0, f32_size * num_components * self.vertex_count);
-        gl.enable_vertex_attrib_array(shader_program.get_texture_coords() as u32);
+    pub fn get_shadow(&self) -> Option<TextShadow> {
+        self.shadow
+    }
+
+    /// Changes the layout of this TextModel. The buffer will be updated the next time this TextModel is rendered.
+    fn set_layout(&mut self, layout: TextLayout){
+
+        // The buffer was uploaded with the Static usage hint, so it must be reallocated before it can be used as dynamic buffer
+        if self.buffer_usage == BufferUsage::Static {
+            self.buffer_capacity.set(0);
+        }
+        self.buffer_usage = BufferUsage::Dynamic;
+        self.page_draws = compute_page_draws(&layout);
+        self.layout = layout;
+        self.arrangement.replace(None);
+    }
+
+    /// Writes the given vertex data to the buffer of this TextModel. Dynamic buffers will only be reallocated if the data
+    /// doesn't fit in them, in which case their capacity will be (at least) doubled to avoid frequent reallocations.
+    fn write_buffer(&self, data: &[u8]){
+        let backend = &self.get_font().backend;
+        let capacity = self.buffer_capacity.get();
+        match self.buffer_usage {
+            BufferUsage::Static => {
+                backend.upload_buffer(self.buffer, data);
+                self.buffer_capacity.set(data.len());
+            }, BufferUsage::Dynamic => {
+                if data.len() > capacity {
+                    let new_capacity = data.len().max(2 * capacity);
+                    backend.allocate_buffer(self.buffer, new_capacity, BufferUsage::Dynamic);
+                    self.buffer_capacity.set(new_capacity);
+                }
+                backend.update_buffer(self.buffer, 0, data);
+            }
+        };
+    }
+
+    /// Makes sure the buffer of this TextModel contains the vertex data for the given alignment within a region of the given
+    /// width (in model space). The buffer will only be updated if the alignment or region width changed since the last time,
+    /// or if an atlas page of the Font has grown (which changes the texture coordinates of the characters).
+    fn arrange(&self, alignment: HorizontalAlignment, region_width: f32){
+
+        // The region width doesn't affect left-aligned text, so there is no need to update the buffer when it changes
+        let region_width = if alignment == HorizontalAlignment::Left { 0.0 } else { region_width };
+        let new_arrangement = Arrangement {
+            alignment,
+            region_width,
+            page_sizes: self.get_font().get_page_sizes()
+        };
+        let mut arrangement = self.arrangement.borrow_mut();
+        if arrangement.as_ref() != Some(&new_arrangement) {
+            let buffer_data = self.get_font().create_buffer_data(&self.layout, alignment, region_width, &new_arrangement.page_sizes);
+            self.write_buffer(&buffer_data);
+            *arrangement = Some(new_arrangement);
+        }
     }
 
     /// Renders this TextModel at the given position with the given size and colors. The start_rendering
@@ -64,9 +210,11 @@ impl TextModel {
     /// 
     /// The parameters offset_x and offset_y determine the bottom-left corner of the render space.
     /// 
-    /// The scale_y parameter determines the height of the render space (in OpenGL coordinate space), so a scale_y of 2.0 with 
-    /// an offset_y of -1.0 would claim the full height of the canvas. The width of the text will depend on both the width of
-    /// the string and scale_y. You can find the width in advance using the get_render_width method of this TextModel.
+    /// The scale_y parameter determines the height of a single line of the render space (in OpenGL coordinate space), so a 
+    /// scale_y of 2.0 with an offset_y of -1.0 would claim the full height of the canvas for a single line of text. The width 
+    /// of the text will depend on both the width of the (longest line of the) string and scale_y. You can find the width in 
+    /// advance using the get_render_width method of this TextModel. If the text has multiple lines, the height of the render 
+    /// space will be larger than scale_y, use get_render_height to find it.
     /// 
     /// The fill_color will determine the color of the interior of the rendered text. If you make it transparent, you will see
     /// the background_color instead.
@@ -78,35 +226,86 @@ impl TextModel {
     /// 
     /// The background_color will determine the color of the render space wherever no text is drawn (or the text is (partially)
     /// transparent). If it is transparent, the text will be drawn over whatever the previous color was.
+    /// 
+    /// If the Font uses a distance field atlas, the line_width of the TextRenderer at the time the Font was created will be
+    /// used as stroke width. Use render_with_line_width to choose another stroke width.
     pub fn render(&self, offset_x: f32, offset_y: f32, scale_y: f32, colors: TextColors){
-        let need_set_font;
-        let my_font = self.get_font();
-        {
-            let selected_font = my_font.selected_font.get();
-            match selected_font {
-                Some(font_id) => need_set_font = font_id != my_font.id,
-                None => need_set_font = true
-            };
-        }
+        self.render_with_line_width(offset_x, offset_y, scale_y, colors, self.get_default_line_width());
+    }
 
-        if need_set_font {
-            my_font.set_current();
-            my_font.selected_font.set(Some(my_font.id));
-        }
+    /// Renders this TextModel like the render method, but with the given line_width for the stroke. The line_width is a
+    /// fraction of the font size, just like the line_width of the TextRenderer.
+    /// 
+    /// The line_width will only be used if the Font uses a distance field atlas (see AtlasMode). Font's with a bitmap
+    /// atlas have their stroke baked in their backing texture, so they will ignore the line_width.
+    pub fn render_with_line_width(&self, offset_x: f32, offset_y: f32, scale_y: f32, colors: TextColors, line_width: f32){
+        self.arrange(HorizontalAlignment::Left, 0.0);
+        self.draw(offset_x, offset_y, scale_y, colors, line_width);
+    }
 
-        let scale_x = scale_y / my_font.aspect_ratio.get();
+    /// Renders this TextModel within the given region, with the given alignment. The region is expressed in the OpenGL
+    /// coordinate system, just like the parameters of the render method.
+    /// 
+    /// The horizontal alignment determines where every line will be placed within the region. Use HorizontalAlignment::Justify
+    /// to stretch every line (except the last line of every paragraph) to the full width of the region. The vertical alignment
+    /// determines where the block of text will be placed within the region.
+    /// 
+    /// The scale_y has the same meaning as for the render method, so the text will NOT be scaled to fit the region. If the text
+    /// is wider or higher than the region, it will exceed the region. Like the render method, this method will fill the space
+    /// behind the text with the background_color of the colors, but not the rest of the region.
+    /// 
+    /// Changing the horizontal alignment or the width of the region requires updating the vertex buffer of this TextModel, so
+    /// try to avoid changing them every frame.
+    pub fn render_in_region(&self, region: Region, horizontal: HorizontalAlignment, vertical: VerticalAlignment, scale_y: f32, colors: TextColors){
+        self.render_in_region_with_line_width(region, horizontal, vertical, scale_y, colors, self.get_default_line_width());
+    }
+
+    /// Renders this TextModel like the render_in_region method, but with the given line_width for the stroke. See the
+    /// render_with_line_width method for more information about the line_width.
+    pub fn render_in_region_with_line_width(&self, region: Region, horizontal: HorizontalAlignment, vertical: VerticalAlignment, scale_y: f32, colors: TextColors, line_width: f32){
+        let scale_x = scale_y / self.get_font().aspect_ratio.get();
+        self.arrange(horizontal, region.get_float_width() / scale_x);
+
+        let free_height = region.get_float_height() - self.get_render_height(scale_y);
+        let offset_y = match vertical {
+            VerticalAlignment::Top => region.get_float_min_y() + free_height,
+            VerticalAlignment::Center => region.get_float_min_y() + free_height / 2.0,
+            VerticalAlignment::Bottom => region.get_float_min_y()
+        };
+        self.draw(region.get_float_min_x(), offset_y, scale_y, colors, line_width);
+    }
 
-        let mut shader = my_font.shader_program.borrow_mut();
-        shader.set_background_color(colors.background_color);
-        shader.set_fill_color(colors.fill_color);
-        shader.set_stroke_color(colors.stroke_color);
-        shader.set_screen_position(offset_x, offset_y);
-        shader.set_scale(scale_x, scale_y);
-        self.bind(&shader);
-        my_font.gl.draw_arrays(GL::TRIANGLES, 0, self.vertex_count);
+    fn get_default_line_width(&self) -> f32 {
+        self.get_font().get_default_line_width()
     }
 
-    /// This method can be used to predict the width of the text drawn with the render method.
+    fn draw(&self, offset_x: f32, offset_y: f32, scale_y: f32, colors: TextColors, line_width: f32){
+        let (alignment, region_width) = self.arrangement.borrow().as_ref().map_or((HorizontalAlignment::Left, 0.0), |arrangement| {
+            (arrangement.alignment, arrangement.region_width)
+        });
+
+        // The decoration lines must follow the aligned characters, so they are computed for the current arrangement
+        let decoration_lines = match self.decorations.is_empty() {
+            true => Vec::new(),
+            false => compute_decoration_lines(self.get_font(), &self.layout, alignment, region_width, |_span| self.decorations)
+        };
+        let (block_min_x, block_max_x) = self.layout.compute_horizontal_extent(alignment, region_width);
+        let text = BufferedText {
+            buffer: self.buffer,
+            instance_array: self.instance_array.as_ref(),
+            first_byte: 0,
+            page_draws: &self.page_draws,
+            block_min_x,
+            block_width: block_max_x - block_min_x,
+            block_height: self.layout.height,
+            decoration_lines: &decoration_lines,
+            shadow: self.shadow
+        };
+        draw_buffered_text(self.get_font(), &text, offset_x, offset_y, scale_y, colors, line_width);
+    }
+
+    /// This method can be used to predict the width of the text drawn with the render method. For text with multiple lines,
+    /// this will be the width of the longest line.
     /// 
     /// The scale_y parameter should be the same as the scale_y you are planning to pass to the render method.
     /// 
@@ -115,7 +314,36 @@ impl TextModel {
     pub fn get_render_width(&self, scale_y: f32) -> f32 {
         let my_font = self.get_font();
         let scale_x = scale_y / my_font.aspect_ratio.get();
-        scale_x * self.total_width
+        scale_x * self.layout.width
+    }
+
+    /// This method can be used to predict the height of the text drawn with the render method. For text with only 1 line,
+    /// this will simply be scale_y. For text with multiple lines, this will be the total height of all lines and the
+    /// spacing between them.
+    /// 
+    /// The scale_y parameter should be the same as the scale_y you are planning to pass to the render method.
+    /// 
+    /// The result of this method will be given in the OpenGL coordinate space, so a return value of 2.0 
+    /// means the text would span the entire canvas height (if the offset_y would be -1.0).
+    pub fn get_render_height(&self, scale_y: f32) -> f32 {
+        scale_y * self.layout.height
+    }
+
+    /// Gets the number of lines of this TextModel. This will be 1 plus the number of line breaks, including the line breaks
+    /// that were inserted by create_wrapped_text_model.
+    pub fn get_line_count(&self) -> usize {
+        self.layout.lines.len()
+    }
+
+    /// Gets the render width of every line of this TextModel, from top to bottom. The scale_y and the result have the same
+    /// meaning as for the get_render_width method.
+    pub fn get_line_widths(&self, scale_y: f32) -> Vec<f32> {
+        let scale_x = scale_y / self.get_font().aspect_ratio.get();
+        self.layout.lines.iter().map(|line| scale_x * line.width).collect()
+    }
+
+    pub(super) fn get_layout(&self) -> &TextLayout {
+        &self.layout
     }
 
     pub fn get_font(&self) -> &Rc<Font> {
@@ -126,6 +354,446 @@ impl TextModel {
 impl Drop for TextModel {
 
     fn drop(&mut self){
-        self.get_font().gl.delete_buffer(Some(&self.buffer));
+        self.get_font().backend.delete_buffer(self.buffer);
     }
-}
\ No newline at end of file
+}
+
+/// Text whose buffer data (see the create_buffer_data method of Font) is stored in a buffer, ready to be drawn with the
+/// TextProgram of its Font
+pub(super) struct BufferedText<'a> {
+
+    pub buffer: BufferHandle,
+
+    /// The vertex array object that should be used to draw the instances in the buffer, or None if the shader program of
+    /// the Font is not instanced
+    pub instance_array: Option<&'a InstanceArray>,
+
+    /// The index of the first byte of the buffer data of the text in the buffer
+    pub first_byte: usize,
+
+    /// The atlas page, first quad and quad count of every draw call that is needed to draw the text
+    pub page_draws: &'a [(usize, i32, i32)],
+
+    /// The left edge and size (in model space) of the block behind the text that will be filled with the background color
+    pub block_min_x: f32,
+    pub block_width: f32,
+    pub block_height: f32,
+
+    /// The decoration lines that will be drawn over the text with the fill color
+    pub decoration_lines: &'a [DecorationLine],
+
+    /// The shadow that will be drawn behind the text, if any
+    pub shadow: Option<TextShadow>
+}
+
+/// Lets the attributes of the given (non-instanced) shader program read the vertices of quad_count quads from the vertex
+/// data in the given buffer, starting at the given offset (in bytes), and binds the index buffer for those quads
+fn bind_quads(font: &Font, shader_program: &TextProgram, buffer: BufferHandle, offset: i32, quad_count: usize){
+    let backend = &font.backend;
+
+    let num_components = 2;
+    let f32_size = 4;
+    let compact_texture_coords = font.has_compact_texture_coords();
+    let stride = get_vertex_size(compact_texture_coords) as i32;
+
+    backend.set_vertex_attribute(buffer, shader_program.get_relative_position(), num_components, AttributeType::Float, stride, offset);
+
+    let texture_coords_type = if compact_texture_coords { AttributeType::NormalizedUnsignedShort } else { AttributeType::Float };
+    backend.set_vertex_attribute(buffer, shader_program.get_texture_coords(), num_components, texture_coords_type, stride, offset + f32_size * num_components);
+
+    shader_program.bind_quad_indices(quad_count);
+}
+
+/// Draws the given text with the given Font. The parameters have the same meaning as for the render_with_line_width method
+/// of TextModel.
+pub(super) fn draw_buffered_text(font: &Font, text: &BufferedText, offset_x: f32, offset_y: f32, scale_y: f32, colors: TextColors, line_width: f32){
+
+    // Without any characters, there is no page to draw with
+    let first_page = match text.page_draws.first() {
+        Some((page, _first_quad, _quad_count)) => *page,
+        None => return
+    };
+    let scale_x = scale_y / font.aspect_ratio.get();
+
+    // The quads of neighbouring characters can overlap, so the background must be drawn before (rather than together
+    // with) the characters. Otherwise, the background of a quad could cover a part of the previous character.
+    if colors.background_color.get_alpha_float() > 0.0 {
+        font.fill_area(offset_x + scale_x * text.block_min_x, offset_y, scale_x * text.block_width, scale_y * text.block_height, colors.background_color);
+    }
+    font.prepare_rendering(first_page);
+
+    let mut shader = font.shader_program.borrow_mut();
+    shader.set_background_color(Color::from_rgba(0, 0, 0, 0));
+    shader.set_scale(scale_x, scale_y);
+    if font.distance_field.is_some() {
+        shader.set_stroke_width(font.get_stroke_width(line_width));
+    }
+
+    // The shadow is a copy of the characters (including their stroke) in the shadow color, which must be drawn first
+    if let Some(shadow) = text.shadow {
+        let softness = if font.has_soft_shadows() { shadow.softness.clamp(0.0, 1.0) } else { 0.0 };
+        shader.set_fill_color(shadow.color);
+        shader.set_stroke_color(shadow.color);
+        shader.set_screen_position(offset_x + scale_x * shadow.offset_x, offset_y + scale_y * shadow.offset_y);
+
+        // The softest distance field shadow fades out over the entire spread of the distance field
+        match font.distance_field {
+            Some(_) => shader.set_smoothing(font.get_smoothing(scale_y) + 0.5 * softness),
+            None => shader.set_shadow_softness(softness)
+        };
+        drop(shader);
+        draw_pages(font, text);
+        shader = font.shader_program.borrow_mut();
+        shader.set_shadow_softness(0.0);
+    }
+
+    shader.set_fill_color(colors.fill_color);
+    shader.set_stroke_color(colors.stroke_color);
+    shader.set_screen_position(offset_x, offset_y);
+    if font.distance_field.is_some() {
+        shader.set_smoothing(font.get_smoothing(scale_y));
+    }
+    drop(shader);
+    draw_pages(font, text);
+
+    // Don't let other code change the vertex array object of the text by accident
+    if text.instance_array.is_some() {
+        font.backend.bind_vertex_array(None);
+    }
+
+    for line in text.decoration_lines {
+        let width = line.max_x - line.min_x;
+        let height = line.max_y - line.min_y;
+        font.fill_area(offset_x + scale_x * line.min_x, offset_y + scale_y * line.min_y, scale_x * width, scale_y * height, colors.fill_color);
+    }
+}
+
+/// Draws the characters of the given text with the current uniform variables of the shader program of the given Font. The
+/// characters of every atlas page will be drawn with the texture of that page.
+fn draw_pages(font: &Font, text: &BufferedText){
+    let f32_size = 4;
+    let quad_size = match text.instance_array {
+        Some(_) => f32_size * FLOATS_PER_INSTANCE,
+        None => 4 * get_vertex_size(font.has_compact_texture_coords())
+    };
+
+    for (page, first_quad, quad_count) in text.page_draws {
+        font.prepare_rendering(*page);
+        let page_offset = text.first_byte + quad_size * *first_quad as usize;
+        match text.instance_array {
+            Some(instance_array) => {
+                instance_array.bind(&font.shader_program.borrow(), text.buffer, page_offset as i32);
+                font.backend.draw_instanced_triangles(0, 6, *quad_count);
+            }, None => {
+
+                // The u16 indices can only refer to a limited number of quads, so large pages are drawn in parts
+                let mut drawn_quads = 0;
+                while drawn_quads < *quad_count as usize {
+                    let part_quads = (*quad_count as usize - drawn_quads).min(MAX_QUADS_PER_DRAW);
+                    let part_offset = page_offset + quad_size * drawn_quads;
+                    bind_quads(font, &font.shader_program.borrow(), text.buffer, part_offset as i32, part_quads);
+                    font.backend.draw_indexed_triangles(0, (6 * part_quads) as i32);
+                    drawn_quads += part_quads;
+                }
+            }
+        };
+    }
+}
+
+/// Computes the atlas page, first quad and quad count of every draw call that is needed to draw the given layout
+pub(super) fn compute_page_draws(layout: &TextLayout) -> Vec<(usize, i32, i32)> {
+    let mut page_draws = Vec::new();
+    let mut first_quad = 0;
+    for (page, quad_count) in layout.get_page_groups() {
+        page_draws.push((page, first_quad, quad_count as i32));
+        first_quad += quad_count as i32;
+    }
+    page_draws
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use super::super::*;
+    use super::super::testing::*;
+
+    /// Reads the little-endian u16 at the given byte index of the given data
+    fn read_u16(data: &[u8], index: usize) -> u16 {
+        u16::from_le_bytes([data[index], data[index + 1]])
+    }
+
+    #[test]
+    fn render_draws_all_characters_with_one_call(){
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        let model = Rc::clone(&font).create_text_model("Hello");
+
+        renderer.start_rendering();
+        backend.clear_calls();
+        model.render(-0.5, 0.0, 0.1, test_colors(Color::from_rgb(255, 0, 0)));
+        assert_eq!(backend.get_draw_count(), 1);
+        assert!(backend.get_calls().contains(&BackendCall::DrawIndexedTriangles { first: 0, count: 30 }));
+    }
+
+    #[test]
+    fn render_sets_uniforms(){
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        let model = Rc::clone(&font).create_text_model("Hello");
+
+        renderer.start_rendering();
+        model.render(-0.5, 0.25, 0.1, test_colors(Color::from_rgb(255, 0, 0)));
+        let program = backend.get_current_program().expect("A program should be in use");
+        assert_eq!(backend.get_uniform_value(program, "fillColor"), Some(UniformValue::Vec4(1.0, 0.0, 0.0, 1.0)));
+        assert_eq!(backend.get_uniform_value(program, "strokeColor"), Some(UniformValue::Vec4(0.0, 0.0, 0.0, 1.0)));
+        assert_eq!(backend.get_uniform_value(program, "screenPosition"), Some(UniformValue::Vec2(-0.5, 0.25)));
+
+        // The surface of the RecordingBackend is square, so the scale is the same in both directions
+        assert_eq!(backend.get_uniform_value(program, "scale"), Some(UniformValue::Vec2(0.1, 0.1)));
+    }
+
+    #[test]
+    fn programs_keep_their_own_uniforms(){
+        let (backend, mut renderer) = create_test_renderer(false);
+        let bitmap_font = add_test_font(&mut renderer);
+        renderer.atlas_mode = AtlasMode::DistanceField { font_size: TEST_FONT_SIZE, spread: DEFAULT_DISTANCE_FIELD_SPREAD };
+        let distance_font = add_test_font(&mut renderer);
+        let bitmap_model = Rc::clone(&bitmap_font).create_text_model("Hi");
+        let distance_model = Rc::clone(&distance_font).create_text_model("Hi");
+
+        renderer.start_rendering();
+        bitmap_model.render(0.0, 0.0, 0.1, test_colors(Color::from_rgb(255, 0, 0)));
+        let bitmap_program = backend.get_current_program().expect("A program should be in use");
+        distance_model.render(0.0, 0.0, 0.1, test_colors(Color::from_rgb(0, 0, 255)));
+        let distance_program = backend.get_current_program().expect("A program should be in use");
+
+        assert_ne!(bitmap_program, distance_program);
+        assert_eq!(backend.get_uniform_value(bitmap_program, "fillColor"), Some(UniformValue::Vec4(1.0, 0.0, 0.0, 1.0)));
+        assert_eq!(backend.get_uniform_value(distance_program, "fillColor"), Some(UniformValue::Vec4(0.0, 0.0, 1.0, 1.0)));
+        assert!(backend.get_uniform_value(distance_program, "smoothing").is_some());
+        assert_eq!(backend.get_uniform_value(bitmap_program, "smoothing"), None);
+    }
+
+    #[test]
+    fn vertex_data_follows_the_characters(){
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        let model = Rc::clone(&font).create_text_model("Hello");
+        let vertices = backend.get_buffer_floats(model.buffer).expect("The model should have a buffer");
+
+        // 4 vertices per character, with 4 floats per vertex
+        assert_eq!(vertices.len(), 5 * 4 * 4);
+        let quads: Vec<&[f32]> = vertices.chunks_exact(16).collect();
+        for quad in &quads {
+            let (min_x, min_y, max_x, max_y) = (quad[0], quad[1], quad[8], quad[9]);
+            assert!(min_x < max_x && min_y < max_y);
+            assert!(quad.chunks_exact(4).all(|vertex| (0.0..=1.0).contains(&vertex[2]) && (0.0..=1.0).contains(&vertex[3])));
+        }
+
+        // All characters are on the same page, so the quads are in the order of the text
+        assert!(quads.windows(2).all(|pair| pair[0][0] < pair[1][0]));
+
+        // The two l's must use the same part of the atlas
+        assert_eq!(quads[2][2..4], quads[3][2..4]);
+        assert_eq!(quads[2][10..12], quads[3][10..12]);
+    }
+
+    #[test]
+    fn compact_texture_coords_are_normalized_u16s(){
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        renderer.compact_texture_coords = true;
+        let compact_font = add_test_font(&mut renderer);
+
+        let model = Rc::clone(&font).create_text_model("Hello");
+        let compact_model = Rc::clone(&compact_font).create_text_model("Hello");
+        let vertices = backend.get_buffer_floats(model.buffer).expect("The model should have a buffer");
+        let compact_data = backend.get_buffer_data(compact_model.buffer).expect("The compact model should have a buffer");
+
+        // Every vertex consists of 2 floats and 2 u16's
+        assert_eq!(compact_data.len(), 5 * 4 * 12);
+        for (vertex, compact_vertex) in vertices.chunks_exact(4).zip(compact_data.chunks_exact(12)) {
+            assert_eq!(vertex[0].to_le_bytes(), compact_vertex[0..4]);
+            assert_eq!(vertex[1].to_le_bytes(), compact_vertex[4..8]);
+            let u = read_u16(compact_vertex, 8) as f32 / u16::MAX as f32;
+            let v = read_u16(compact_vertex, 10) as f32 / u16::MAX as f32;
+            assert!((u - vertex[2]).abs() < 1.0 / u16::MAX as f32);
+            assert!((v - vertex[3]).abs() < 1.0 / u16::MAX as f32);
+        }
+
+        renderer.start_rendering();
+        backend.clear_calls();
+        compact_model.render(0.0, 0.0, 0.1, test_colors(Color::from_rgb(255, 0, 0)));
+        let texture_coords = compact_font.shader_program.borrow().get_texture_coords();
+        assert!(backend.get_calls().contains(&BackendCall::SetVertexAttribute {
+            buffer: compact_model.buffer,
+            location: texture_coords,
+            num_components: 2,
+            attribute_type: AttributeType::NormalizedUnsignedShort,
+            stride: 12,
+            offset: 8
+        }));
+    }
+
+    #[test]
+    fn instanced_render_draws_one_instance_per_character(){
+        let (backend, mut renderer) = create_test_renderer(true);
+        let font = add_test_font(&mut renderer);
+        let model = Rc::clone(&font).create_text_model("Hello");
+        let instances = backend.get_buffer_floats(model.buffer).expect("The model should have a buffer");
+        assert_eq!(instances.len(), 5 * FLOATS_PER_INSTANCE);
+
+        renderer.start_rendering();
+        backend.clear_calls();
+        model.render(0.0, 0.0, 0.1, test_colors(Color::from_rgb(255, 0, 0)));
+        assert_eq!(backend.get_draw_count(), 1);
+        assert!(backend.get_calls().contains(&BackendCall::DrawInstancedTriangles { first: 0, count: 6, instance_count: 5 }));
+    }
+
+    #[test]
+    fn background_only_covers_the_aligned_text(){
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        let model = Rc::clone(&font).create_text_model("Hello");
+        let colors = TextColors {
+            background_color: Color::from_rgb(0, 0, 255),
+            ..test_colors(Color::from_rgb(255, 0, 0))
+        };
+        renderer.start_rendering();
+
+        // Gets the left edge and width of the background when the model is drawn with the given alignment. Every draw uses
+        // another offset_x, since the TextProgram won't set uniforms to the value they already have.
+        let draw_background = |offset_x: f32, alignment: HorizontalAlignment, region_width: f32| {
+            model.arrange(alignment, region_width);
+            backend.clear_calls();
+            model.draw(offset_x, 0.0, 1.0, colors, model.get_default_line_width());
+
+            // The background is drawn first, so the first values of the uniforms belong to the background
+            let calls = backend.get_calls();
+            let first_value = |name: &str| calls.iter().find_map(|call| match call {
+                BackendCall::SetUniform { name: uniform_name, value, .. } if uniform_name == name => Some(*value),
+                _ => None
+            });
+            match (first_value("screenPosition"), first_value("scale")) {
+                (Some(UniformValue::Vec2(min_x, _)), Some(UniformValue::Vec2(width, _))) => (min_x - offset_x, width),
+                _ => panic!("The background should have been drawn")
+            }
+        };
+        let assert_close = |actual: f32, expected: f32| assert!((actual - expected).abs() < 1e-5, "{} != {}", actual, expected);
+
+        let text_width = model.layout.width;
+        let (min_x, width) = draw_background(1.0, HorizontalAlignment::Right, 3.0 * text_width);
+        assert_close(min_x, 2.0 * text_width);
+        assert_close(width, text_width);
+
+        // Centered text that is wider than the region sticks out on both sides
+        let (min_x, width) = draw_background(2.0, HorizontalAlignment::Center, 0.5 * text_width);
+        assert_close(min_x, -0.25 * text_width);
+        assert_close(width, text_width);
+
+        // A single line is the last line of its paragraph, so it won't be stretched
+        let (min_x, width) = draw_background(3.0, HorizontalAlignment::Justify, 2.0 * text_width);
+        assert_close(min_x, 0.0);
+        assert_close(width, text_width);
+    }
+
+    #[test]
+    fn set_text_switches_to_a_dynamic_buffer(){
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        let mut model = Rc::clone(&font).create_text_model("Hello");
+        renderer.start_rendering();
+
+        // The new text is shorter than the old text, but the buffer must still be reallocated to make it dynamic
+        model.set_text("Hi");
+        backend.clear_calls();
+        model.render(0.0, 0.0, 0.1, test_colors(Color::from_rgb(255, 0, 0)));
+        let byte_count = 2 * 4 * 16;
+        assert_eq!(backend.get_calls()[0..2], [
+            BackendCall::AllocateBuffer { buffer: model.buffer, byte_count, usage: BufferUsage::Dynamic },
+            BackendCall::UpdateBuffer { buffer: model.buffer, offset: 0, data: backend.get_buffer_data(model.buffer).unwrap() }
+        ]);
+
+        // Later changes can reuse the dynamic buffer as long as the text fits
+        model.set_text("Ha");
+        backend.clear_calls();
+        model.render(0.0, 0.0, 0.1, test_colors(Color::from_rgb(255, 0, 0)));
+        assert!(matches!(backend.get_calls()[0], BackendCall::UpdateBuffer { .. }));
+        assert!(!backend.get_calls().iter().any(|call| matches!(call, BackendCall::AllocateBuffer { .. })));
+    }
+
+    #[test]
+    fn background_is_drawn_before_the_characters(){
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        let model = Rc::clone(&font).create_text_model("Hello");
+        let colors = TextColors {
+            background_color: Color::from_rgb(0, 0, 255),
+            ..test_colors(Color::from_rgb(255, 0, 0))
+        };
+
+        renderer.start_rendering();
+        backend.clear_calls();
+        model.render(0.0, 0.0, 0.1, colors);
+        assert_eq!(backend.get_draw_count(), 2);
+        let calls = backend.get_calls();
+        let background = calls.iter().position(|call| *call == BackendCall::DrawTriangles { first: 0, count: 6 });
+        let characters = calls.iter().position(|call| matches!(call, BackendCall::DrawIndexedTriangles { .. }));
+        assert!(background.expect("The background should be drawn") < characters.expect("The characters should be drawn"));
+    }
+
+    #[test]
+    fn shadow_is_drawn_before_the_characters(){
+        let (backend, mut renderer) = create_test_renderer(false);
+        renderer.shadow_blur = 0.1;
+        let font = add_test_font(&mut renderer);
+        let mut model = Rc::clone(&font).create_text_model("Hello");
+        model.set_shadow(Some(TextShadow::soft(0.5, -0.5, Color::from_rgb(0, 0, 255), 0.4)));
+
+        renderer.start_rendering();
+        backend.clear_calls();
+        model.render(0.25, 0.0, 0.1, test_colors(Color::from_rgb(255, 0, 0)));
+
+        // Remember the values of the uniform variables at the time of every draw call
+        let mut uniforms = std::collections::HashMap::new();
+        let mut draws = Vec::new();
+        for call in backend.get_calls() {
+            match call {
+                BackendCall::SetUniform { name, value, .. } => {
+                    uniforms.insert(name, value);
+                },
+                BackendCall::DrawIndexedTriangles { .. } => draws.push(uniforms.clone()),
+                _ => {}
+            };
+        }
+        assert_eq!(draws.len(), 2);
+
+        let shadow = &draws[0];
+        assert_eq!(shadow["fillColor"], UniformValue::Vec4(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(shadow["strokeColor"], UniformValue::Vec4(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(shadow["screenPosition"], UniformValue::Vec2(0.25 + 0.1 * 0.5, 0.1 * -0.5));
+        assert_eq!(shadow["shadowSoftness"], UniformValue::Float(0.4));
+
+        let characters = &draws[1];
+        assert_eq!(characters["fillColor"], UniformValue::Vec4(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(characters["strokeColor"], UniformValue::Vec4(0.0, 0.0, 0.0, 1.0));
+        assert_eq!(characters["screenPosition"], UniformValue::Vec2(0.25, 0.0));
+        assert_eq!(characters["shadowSoftness"], UniformValue::Float(0.0));
+    }
+
+    #[test]
+    fn shadow_of_font_without_soft_shadows_is_sharp(){
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        assert!(!font.has_soft_shadows());
+        let mut model = Rc::clone(&font).create_text_model("Hello");
+        model.set_shadow(Some(TextShadow::soft(0.5, -0.5, Color::from_rgb(0, 0, 255), 0.4)));
+
+        renderer.start_rendering();
+        backend.clear_calls();
+        model.render(0.0, 0.0, 0.1, test_colors(Color::from_rgb(255, 0, 0)));
+        assert_eq!(backend.get_draw_count(), 2);
+        assert!(!backend.get_calls().iter().any(|call| matches!(call, BackendCall::SetUniform { name, .. } if name == "shadowSoftness")));
+    }
+}
@@ -0,0 +1,176 @@
+/// The number of sub-rows per pixel row that will be used to compute the fill coverage
+const FILL_SUBSAMPLES: u32 = 4;
+
+/// The maximum distance (in pixels) between a curve and the line segments that approximate it
+const FLATNESS: f32 = 0.25;
+
+/// The outline of a glyph, approximated by line segments. All coordinates are in pixel space, with the y-axis pointing
+/// downwards (like in the rows of a GlyphBitmap).
+pub(super) struct Outline {
+
+    segments: Vec<Segment>
+}
+
+#[derive(Copy,Clone)]
+struct Segment {
+
+    x0: f32,
+    y0: f32,
+    x1: f32,
+    y1: f32
+}
+
+impl Segment {
+
+    fn distance_to(&self, x: f32, y: f32) -> f32 {
+        let dx = self.x1 - self.x0;
+        let dy = self.y1 - self.y0;
+        let length_squared = dx * dx + dy * dy;
+        let t = if length_squared > 0.0 {
+            (((x - self.x0) * dx + (y - self.y0) * dy) / length_squared).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let closest_x = self.x0 + t * dx;
+        let closest_y = self.y0 + t * dy;
+        ((x - closest_x) * (x - closest_x) + (y - closest_y) * (y - closest_y)).sqrt()
+    }
+}
+
+fn subdivisions(length: f32) -> u32 {
+    ((length / FLATNESS).sqrt().ceil() as u32).clamp(1, 100)
+}
+
+fn distance(x0: f32, y0: f32, x1: f32, y1: f32) -> f32 {
+    ((x1 - x0) * (x1 - x0) + (y1 - y0) * (y1 - y0)).sqrt()
+}
+
+impl Outline {
+
+    pub fn new() -> Outline {
+        Outline {
+            segments: Vec::new()
+        }
+    }
+
+    pub fn add_line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32){
+        self.segments.push(Segment { x0, y0, x1, y1 });
+    }
+
+    pub fn add_quad(&mut self, x0: f32, y0: f32, cx: f32, cy: f32, x1: f32, y1: f32){
+        let steps = subdivisions(distance(x0, y0, cx, cy) + distance(cx, cy, x1, y1));
+        let mut prev_x = x0;
+        let mut prev_y = y0;
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            let u = 1.0 - t;
+            let x = u * u * x0 + 2.0 * u * t * cx + t * t * x1;
+            let y = u * u * y0 + 2.0 * u * t * cy + t * t * y1;
+            self.add_line(prev_x, prev_y, x, y);
+            prev_x = x;
+            prev_y = y;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_cubic(&mut self, x0: f32, y0: f32, cx0: f32, cy0: f32, cx1: f32, cy1: f32, x1: f32, y1: f32){
+        let steps = subdivisions(distance(x0, y0, cx0, cy0) + distance(cx0, cy0, cx1, cy1) + distance(cx1, cy1, x1, y1));
+        let mut prev_x = x0;
+        let mut prev_y = y0;
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            let u = 1.0 - t;
+            let x = u * u * u * x0 + 3.0 * u * u * t * cx0 + 3.0 * u * t * t * cx1 + t * t * t * x1;
+            let y = u * u * u * y0 + 3.0 * u * u * t * cy0 + 3.0 * u * t * t * cy1 + t * t * t * y1;
+            self.add_line(prev_x, prev_y, x, y);
+            prev_x = x;
+            prev_y = y;
+        }
+    }
+
+    /// Computes the fraction of every pixel (in a grid of width * height pixels) that is inside this outline, using the
+    /// non-zero winding rule. The result is stored row by row.
+    pub fn fill_coverage(&self, width: u32, height: u32) -> Vec<f32> {
+        let mut coverage = vec![0.0; (width * height) as usize];
+        let weight = 1.0 / FILL_SUBSAMPLES as f32;
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+
+        for y in 0..height {
+            let row = &mut coverage[(y * width) as usize..((y + 1) * width) as usize];
+            for sub_row in 0..FILL_SUBSAMPLES {
+                let sample_y = y as f32 + (sub_row as f32 + 0.5) * weight;
+
+                crossings.clear();
+                for segment in &self.segments {
+                    let (min_y, max_y, direction) = if segment.y0 < segment.y1 {
+                        (segment.y0, segment.y1, 1)
+                    } else {
+                        (segment.y1, segment.y0, -1)
+                    };
+                    if sample_y >= min_y && sample_y < max_y {
+                        let x = segment.x0 + (sample_y - segment.y0) * (segment.x1 - segment.x0) / (segment.y1 - segment.y0);
+                        crossings.push((x, direction));
+                    }
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                let mut winding = 0;
+                let mut span_start = 0.0;
+                for &(x, direction) in &crossings {
+                    if winding == 0 {
+                        span_start = x;
+                    }
+                    winding += direction;
+                    if winding == 0 {
+                        add_span(row, span_start, x, weight);
+                    }
+                }
+            }
+        }
+
+        coverage
+    }
+
+    /// Computes the distance from the center of every pixel (in a grid of width * height pixels) to the nearest point on
+    /// this outline. Distances larger than max_distance will be clamped to max_distance. The result is stored row by row.
+    pub fn distances(&self, width: u32, height: u32, max_distance: f32) -> Vec<f32> {
+        let mut distances = vec![max_distance; (width * height) as usize];
+
+        // Only the pixels near a segment can be affected by it, so there is no need to check all pixels for all segments
+        for segment in &self.segments {
+            let min_x = (segment.x0.min(segment.x1) - max_distance).floor().max(0.0) as u32;
+            let min_y = (segment.y0.min(segment.y1) - max_distance).floor().max(0.0) as u32;
+            let max_x = ((segment.x0.max(segment.x1) + max_distance).ceil().max(0.0) as u32).min(width);
+            let max_y = ((segment.y0.max(segment.y1) + max_distance).ceil().max(0.0) as u32).min(height);
+
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    let index = (x + y * width) as usize;
+                    let distance = segment.distance_to(x as f32 + 0.5, y as f32 + 0.5);
+                    if distance < distances[index] {
+                        distances[index] = distance;
+                    }
+                }
+            }
+        }
+
+        distances
+    }
+}
+
+/// Adds weight * the fraction of every pixel of the row that is covered by the horizontal span from start_x to end_x
+fn add_span(row: &mut [f32], start_x: f32, end_x: f32, weight: f32){
+    let start_x = start_x.max(0.0);
+    let end_x = end_x.min(row.len() as f32);
+    if end_x <= start_x {
+        return;
+    }
+
+    let first_pixel = start_x.floor() as usize;
+    let last_pixel = (end_x.ceil() as usize).min(row.len()) - 1;
+    for (pixel, coverage) in row.iter_mut().enumerate().take(last_pixel + 1).skip(first_pixel) {
+        let pixel_start = (pixel as f32).max(start_x);
+        let pixel_end = (pixel as f32 + 1.0).min(end_x);
+        *coverage += weight * (pixel_end - pixel_start);
+    }
+}
@@ -0,0 +1,202 @@
+/// A rectangle packer that uses the skyline bottom-left algorithm. It keeps track of the 'skyline': the top of the highest
+/// rectangle at every x-coordinate. New rectangles will be placed on the skyline, at the position where their top edge
+/// would be as low as possible.
+///
+/// The y-axis points downwards, so 'low' means close to y = 0 here (which is the top of the atlas texture).
+pub(super) struct SkylinePacker {
+
+    width: u32,
+    height: u32,
+
+    /// The segments of the skyline, sorted by x. Together, they always cover the full width of the packer.
+    skyline: Vec<SkylineNode>
+}
+
+#[derive(Copy,Clone,Debug)]
+struct SkylineNode {
+
+    x: u32,
+    y: u32,
+    width: u32
+}
+
+impl SkylinePacker {
+
+    pub fn new(width: u32, height: u32) -> SkylinePacker {
+        SkylinePacker {
+            width,
+            height,
+            skyline: vec![SkylineNode { x: 0, y: 0, width }]
+        }
+    }
+
+    /// Attempts to find room for a rectangle of the given size. If there is room, the rectangle will be claimed and the
+    /// position (min_x, min_y) of its top-left corner will be returned. If there is no room, None will be returned.
+    pub fn pack(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let mut best: Option<(usize, u32)> = None;
+
+        for index in 0..self.skyline.len() {
+            if let Some(y) = self.fit(index, width, height) {
+
+                // Prefer the lowest position, and the narrowest skyline node when there is a tie
+                let is_better = match best {
+                    Some((best_index, best_y)) => y < best_y || (y == best_y && self.skyline[index].width < self.skyline[best_index].width),
+                    None => true
+                };
+                if is_better {
+                    best = Some((index, y));
+                }
+            }
+        }
+
+        let (index, y) = best?;
+        let x = self.skyline[index].x;
+        self.claim(index, x, y + height, width);
+        Some((x, y))
+    }
+
+    /// Determines the y-coordinate at which a rectangle with the given size could be placed if its left edge would be at
+    /// the start of the skyline node with the given index, or None if the rectangle wouldn't fit there.
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.skyline[index].x;
+        if x + width > self.width {
+            return None;
+        }
+
+        let mut y = 0;
+        let mut remaining_width = width as i64;
+        let mut current_index = index;
+        while remaining_width > 0 {
+            let node = self.skyline[current_index];
+            y = y.max(node.y);
+            if y + height > self.height {
+                return None;
+            }
+            remaining_width -= node.width as i64;
+            current_index += 1;
+        }
+        Some(y)
+    }
+
+    /// Raises the skyline from x to x + width to new_y
+    fn claim(&mut self, index: usize, x: u32, new_y: u32, width: u32){
+        self.skyline.insert(index, SkylineNode { x, y: new_y, width });
+
+        // Shrink or remove the nodes that are (partially) covered by the new node
+        let end_x = x + width;
+        let next_index = index + 1;
+        while next_index < self.skyline.len() {
+            let node = &mut self.skyline[next_index];
+            if node.x >= end_x {
+                break;
+            }
+            let node_end_x = node.x + node.width;
+            if node_end_x <= end_x {
+                self.skyline.remove(next_index);
+            } else {
+                node.width = node_end_x - end_x;
+                node.x = end_x;
+                break;
+            }
+        }
+
+        self.merge_nodes();
+    }
+
+    /// Merges neighbouring skyline nodes that have the same height
+    fn merge_nodes(&mut self){
+        let mut index = 0;
+        while index + 1 < self.skyline.len() {
+            if self.skyline[index].y == self.skyline[index + 1].y {
+                self.skyline[index].width += self.skyline[index + 1].width;
+                self.skyline.remove(index + 1);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// Changes the size of the area in which this packer will place rectangles. The new size must be large enough to
+    /// contain all rectangles that were already packed (see get_used_height).
+    pub fn resize(&mut self, new_width: u32, new_height: u32){
+        if new_width > self.width {
+            self.skyline.push(SkylineNode { x: self.width, y: 0, width: new_width - self.width });
+            self.merge_nodes();
+        }
+        self.width = new_width;
+        self.height = new_height;
+    }
+
+    /// Gets the y-coordinate of the highest point of the skyline: all packed rectangles are above this coordinate.
+    pub fn get_used_height(&self) -> u32 {
+        self.skyline.iter().map(|node| node.y).max().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// Packs all given rectangles (width, height) and checks that every packed rectangle lies within the packer and doesn't
+    /// overlap any other packed rectangle. Returns the number of packed pixels.
+    fn pack_all(packer: &mut SkylinePacker, rectangles: &[(u32, u32)]) -> u32 {
+        let mut occupied = vec![false; (packer.width * packer.height) as usize];
+        let mut packed_area = 0;
+        for (width, height) in rectangles {
+            if let Some((min_x, min_y)) = packer.pack(*width, *height) {
+                assert!(min_x + width <= packer.width && min_y + height <= packer.height);
+                assert!(min_y + height <= packer.get_used_height());
+                for y in min_y..min_y + height {
+                    for x in min_x..min_x + width {
+                        let index = (x + y * packer.width) as usize;
+                        assert!(!occupied[index], "({}, {}) is covered by 2 rectangles", x, y);
+                        occupied[index] = true;
+                    }
+                }
+                packed_area += width * height;
+            }
+        }
+        packed_area
+    }
+
+    #[test]
+    fn equal_squares_fill_the_packer(){
+        let mut packer = SkylinePacker::new(16, 16);
+        assert_eq!(pack_all(&mut packer, &[(4, 4); 16]), 16 * 16);
+        assert_eq!(packer.pack(1, 1), None);
+        assert_eq!(packer.get_used_height(), 16);
+    }
+
+    #[test]
+    fn random_rectangles_do_not_overlap(){
+
+        // A simple linear congruential generator keeps the test deterministic without extra dependencies
+        let mut state: u64 = 12345;
+        let mut next = |bound: u32| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            1 + ((state >> 33) % bound as u64) as u32
+        };
+        for _ in 0..20 {
+            let rectangles: Vec<(u32, u32)> = (0..200).map(|_| (next(30), next(30))).collect();
+            let mut packer = SkylinePacker::new(128, 128);
+            let packed_area = pack_all(&mut packer, &rectangles);
+
+            // The skyline algorithm wastes some space, but it should still fill most of the packer
+            assert!(packed_area > 128 * 128 / 2, "Only {} pixels were packed", packed_area);
+        }
+    }
+
+    #[test]
+    fn resize_makes_room_for_more_rectangles(){
+        let mut packer = SkylinePacker::new(8, 8);
+        assert_eq!(packer.pack(8, 8), Some((0, 0)));
+        assert_eq!(packer.pack(4, 4), None);
+
+        packer.resize(16, 8);
+        assert_eq!(packer.pack(8, 8), Some((8, 0)));
+        packer.resize(16, 12);
+        assert_eq!(packer.pack(16, 4), Some((0, 8)));
+        assert_eq!(packer.get_used_height(), 12);
+    }
+}
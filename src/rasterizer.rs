@@ -0,0 +1,158 @@
+use super::metrics::{
+    FontMetrics,
+    GlyphMetrics
+};
+
+/// The pixels of a single rasterized character, in the same format as the backing texture of a Font: red indicates empty
+/// space, green indicates the interior of the character and blue indicates the border (stroke) of the character. The pixels
+/// are stored row by row, with 4 bytes (red, green, blue and alpha) per pixel.
+/// 
+/// The bitmap only covers the ink of the character (plus a small margin), so it is usually not as wide as the advance of
+/// the character and not as high as a line. The offset tells where the bitmap should be placed.
+pub(super) struct GlyphBitmap {
+
+    pub width: u32,
+    pub height: u32,
+
+    /// The position (in pixels) of the top-left corner of the bitmap, relative to the origin of the character at the top
+    /// of the line. The y-axis points downwards, like the rows of the bitmap.
+    pub offset_x: i32,
+    pub offset_y: i32,
+
+    /// The metrics of the character, in pixels
+    pub metrics: GlyphMetrics,
+
+    pub pixels: Vec<u8>
+}
+
+impl GlyphBitmap {
+
+    /// Creates a GlyphBitmap with the given size and offset where all pixels indicate empty space
+    pub fn empty(width: u32, height: u32, offset_x: i32, offset_y: i32, metrics: GlyphMetrics) -> GlyphBitmap {
+        let mut pixels = Vec::with_capacity((4 * width * height) as usize);
+        for _ in 0..width * height {
+            pixels.extend_from_slice(&EMPTY_PIXEL);
+        }
+        GlyphBitmap {
+            width,
+            height,
+            offset_x,
+            offset_y,
+            metrics,
+            pixels
+        }
+    }
+
+    /// Stores the given fill and stroke coverage (both between 0.0 and 1.0) in the pixel at (x, y). The stroke will be
+    /// drawn over the fill, just like the canvas rasterizer does when it fills and then strokes the text.
+    pub fn set_coverage(&mut self, x: u32, y: u32, fill: f32, stroke: f32){
+        let index = (4 * (x + y * self.width)) as usize;
+        let fill = fill.clamp(0.0, 1.0);
+        let stroke = stroke.clamp(0.0, 1.0);
+        self.pixels[index] = (255.0 * (1.0 - fill) * (1.0 - stroke)).round() as u8;
+        self.pixels[index + 1] = (255.0 * fill * (1.0 - stroke)).round() as u8;
+        self.pixels[index + 2] = (255.0 * stroke).round() as u8;
+        self.pixels[index + 3] = 255;
+    }
+
+    /// Copies the pixels of this bitmap into the given atlas pixels, such that the top-left pixel of this bitmap will end up
+    /// at (min_x, min_y) of the atlas. The atlas must be large enough.
+    pub fn copy_to(&self, atlas: &mut [u8], atlas_width: u32, min_x: u32, min_y: u32){
+        let row_length = (4 * self.width) as usize;
+        for y in 0..self.height {
+            let source_index = (y * self.width * 4) as usize;
+            let dest_index = (4 * (min_x + (min_y + y) * atlas_width)) as usize;
+            atlas[dest_index..dest_index + row_length].copy_from_slice(&self.pixels[source_index..source_index + row_length]);
+        }
+    }
+}
+
+/// Computes the offset (offset_x, offset_y) and size (width, height) of a GlyphBitmap that covers the ink of a character
+/// with the given metrics (in pixels), with margin empty pixels around the ink. The baseline is the distance (in pixels)
+/// from the top of the line to the baseline.
+pub(super) fn compute_ink_box(metrics: &GlyphMetrics, baseline: f32, margin: u32) -> (i32, i32, u32, u32) {
+    let margin = margin as i32;
+    let min_x = metrics.get_left_bearing().floor() as i32 - margin;
+    let max_x = (metrics.get_advance() - metrics.get_right_bearing()).ceil() as i32 + margin;
+    let min_y = (baseline - metrics.get_ascent()).floor() as i32 - margin;
+    let max_y = (baseline + metrics.get_descent()).ceil() as i32 + margin;
+
+    // Even characters without ink (like spaces) need at least 1 pixel
+    (min_x, min_y, (max_x - min_x).max(1) as u32, (max_y - min_y).max(1) as u32)
+}
+
+/// The pixel value that indicates empty space. The alpha channel is 0 because it holds the blurred coverage of the
+/// characters of Font's with soft shadows (see ShadowRasterizer), and empty space doesn't cast any shadow.
+pub(super) const EMPTY_PIXEL: [u8; 4] = [255, 0, 0, 0];
+
+/// The pixel value that indicates empty space in a signed distance field atlas: the largest possible distance outside the
+/// characters
+pub(super) const EMPTY_DISTANCE_PIXEL: [u8; 4] = [0, 0, 0, 0];
+
+/// GlyphRasterizer's are responsible for drawing the characters of a Font. The Font will copy the rasterized characters
+/// into its backing texture.
+pub(super) trait GlyphRasterizer {
+
+    /// Gets the height (in pixels) of a single line of text.
+    fn get_line_height(&self) -> u32;
+
+    /// Gets the distance (in pixels) from the top of a line to the baseline of the characters on that line.
+    fn get_baseline(&self) -> f32;
+
+    /// Gets the ascent, descent, line gap and decoration metrics of the font, in pixels
+    fn get_font_metrics(&self) -> FontMetrics;
+
+    /// Gets the pixel value that indicates empty space in the glyphs of this rasterizer. The atlas of the Font will be
+    /// cleared to this value, so that the space between the characters (and the empty bottom-right corner that is used to
+    /// fill areas) shows the background color.
+    fn get_empty_pixel(&self) -> [u8; 4] {
+        EMPTY_PIXEL
+    }
+
+    /// Rasterizes the given character. Returns None if this rasterizer is unable to draw the character.
+    fn rasterize(&self, character: char) -> Option<GlyphBitmap>;
+
+    /// Gets the advance (in pixels) of the given character without rasterizing it, which should be the same as the advance
+    /// of the metrics of its rasterized glyph. Returns None if this rasterizer is unable to draw the character.
+    fn get_advance(&self, character: char) -> Option<f32>;
+
+    /// Gets the kerning (in pixels) between the given pair of characters: the distance that should be added to the advance
+    /// of the left character when it is directly followed by the right character. This is usually 0 or negative.
+    fn get_kerning(&self, left: char, right: char) -> f32;
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn ink_box_covers_the_ink_and_the_margin() {
+
+        // The ink goes from x = 1.5 to x = 8.25, and from 6.5 above the baseline to 2.25 below it
+        let metrics = GlyphMetrics::new(10.0, 1.5, 1.75, 6.5, 2.25);
+        let (offset_x, offset_y, width, height) = compute_ink_box(&metrics, 8.0, 2);
+        assert_eq!((offset_x, offset_y), (-1, -1));
+        assert_eq!((width, height), (12, 14));
+    }
+
+    #[test]
+    fn ink_box_includes_overhang() {
+
+        // The ink starts 2 pixels before the origin and ends 3 pixels after the advance
+        let metrics = GlyphMetrics::new(10.0, -2.0, -3.0, 6.0, 0.0);
+        let (offset_x, offset_y, width, height) = compute_ink_box(&metrics, 8.0, 0);
+        assert_eq!((offset_x, offset_y), (-2, 2));
+        assert_eq!((width, height), (15, 6));
+    }
+
+    #[test]
+    fn ink_box_of_characters_without_ink_is_not_empty() {
+        let metrics = GlyphMetrics::new(5.0, 0.0, 5.0, 0.0, 0.0);
+        let (_offset_x, _offset_y, width, height) = compute_ink_box(&metrics, 8.0, 0);
+        assert_eq!((width, height), (1, 1));
+
+        let bitmap = GlyphBitmap::empty(width, height, 0, 0, metrics);
+        assert_eq!(bitmap.pixels, EMPTY_PIXEL.to_vec());
+    }
+}
@@ -0,0 +1,410 @@
+use std::cell::{
+    Cell,
+    RefCell
+};
+use std::collections::HashMap;
+
+use super::backend::*;
+use super::error::TextError;
+
+/// The value that was assigned to a uniform variable of a RecordingBackend
+#[derive(PartialEq,Copy,Clone,Debug)]
+pub enum UniformValue {
+
+    Int(i32),
+    Float(f32),
+    Vec2(f32, f32),
+    Vec4(f32, f32, f32, f32)
+}
+
+/// Every method call on a RecordingBackend that changes state or draws something will be recorded as a BackendCall.
+#[derive(PartialEq,Clone,Debug)]
+pub enum BackendCall {
+
+    CreateTexture(TextureHandle),
+    UploadTexture { texture: TextureHandle, width: u32, height: u32 },
+    BindTexture { texture_unit: u32, texture: TextureHandle },
+    DeleteTexture(TextureHandle),
+
+    CreateBuffer(BufferHandle),
+    UploadBuffer { buffer: BufferHandle, data: Vec<u8> },
+    AllocateBuffer { buffer: BufferHandle, byte_count: usize, usage: BufferUsage },
+    UpdateBuffer { buffer: BufferHandle, offset: usize, data: Vec<u8> },
+    DeleteBuffer(BufferHandle),
+    SetVertexAttribute { buffer: BufferHandle, location: i32, num_components: i32, attribute_type: AttributeType, stride: i32, offset: i32 },
+    DisableVertexAttribute(i32),
+    UploadIndexBuffer { buffer: BufferHandle, indices: Vec<u16> },
+    BindIndexBuffer(BufferHandle),
+
+    CreateVertexArray(VertexArrayHandle),
+    BindVertexArray(Option<VertexArrayHandle>),
+    DeleteVertexArray(VertexArrayHandle),
+    SetAttributeDivisor { location: i32, divisor: u32 },
+
+    CreateProgram(ProgramHandle),
+    UseProgram(ProgramHandle),
+    DeleteProgram(ProgramHandle),
+    SetUniform { program: ProgramHandle, name: String, value: UniformValue },
+
+    DrawTriangles { first: i32, count: i32 },
+    DrawInstancedTriangles { first: i32, count: i32, instance_count: i32 },
+    DrawIndexedTriangles { first: i32, count: i32 },
+    EnableBlending
+}
+
+pub const DEFAULT_RECORDING_MAX_TEXTURE_SIZE: u32 = 4096;
+
+struct RecordedTexture {
+
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>
+}
+
+/// A TextBackend that doesn't render anything, but only records the calls that were made on it. It also remembers the
+/// content of its buffers and textures and the current values of its uniform variables. This backend doesn't need a
+/// browser, so it can be used to test code that uses this crate with a plain cargo test.
+///
+/// Attribute locations are assigned in the order they are requested, and uniform handles are remembered by their program
+/// and name so that their values can be queried with get_uniform_value. Like in WebGl, every program has its own uniform
+/// variables, even if the programs use the same names.
+pub struct RecordingBackend {
+
+    calls: RefCell<Vec<BackendCall>>,
+
+    next_handle: Cell<u32>,
+    surface_size: Cell<Option<(u32, u32)>>,
+    max_texture_size: Cell<u32>,
+    supports_instancing: Cell<bool>,
+
+    textures: RefCell<HashMap<TextureHandle, RecordedTexture>>,
+    buffers: RefCell<HashMap<BufferHandle, Vec<u8>>>,
+
+    attrib_names: RefCell<Vec<String>>,
+    uniforms: RefCell<Vec<(ProgramHandle, String)>>,
+    uniform_values: RefCell<HashMap<(ProgramHandle, String), UniformValue>>,
+    current_program: Cell<Option<ProgramHandle>>
+}
+
+impl RecordingBackend {
+
+    /// Creates a new RecordingBackend that hasn't recorded any calls yet. The size of the imaginary surface of this
+    /// backend will be 800 by 800 pixels until it is changed with set_surface_size. The maximum texture size will be
+    /// DEFAULT_RECORDING_MAX_TEXTURE_SIZE until it is changed with set_max_texture_size. The backend won't support instancing
+    /// until set_supports_instancing is called.
+    pub fn new() -> RecordingBackend {
+        RecordingBackend {
+            calls: RefCell::new(Vec::new()),
+
+            next_handle: Cell::new(1),
+            surface_size: Cell::new(Some((800, 800))),
+            max_texture_size: Cell::new(DEFAULT_RECORDING_MAX_TEXTURE_SIZE),
+            supports_instancing: Cell::new(false),
+
+            textures: RefCell::new(HashMap::new()),
+            buffers: RefCell::new(HashMap::new()),
+
+            attrib_names: RefCell::new(Vec::new()),
+            uniforms: RefCell::new(Vec::new()),
+            uniform_values: RefCell::new(HashMap::new()),
+            current_program: Cell::new(None)
+        }
+    }
+
+    fn next_handle(&self) -> u32 {
+        let handle = self.next_handle.get();
+        self.next_handle.set(handle + 1);
+        handle
+    }
+
+    fn record(&self, call: BackendCall){
+        self.calls.borrow_mut().push(call);
+    }
+
+    fn set_uniform(&self, uniform: UniformHandle, value: UniformValue){
+        let (program, name) = self.uniforms.borrow()[uniform.get_value() as usize].clone();
+        self.uniform_values.borrow_mut().insert((program, name.clone()), value);
+        self.record(BackendCall::SetUniform { program, name, value });
+    }
+
+    /// Changes the surface size that will be returned by get_surface_size. Use None to simulate a missing canvas.
+    pub fn set_surface_size(&self, surface_size: Option<(u32, u32)>){
+        self.surface_size.set(surface_size);
+    }
+
+    /// Changes the maximum texture size that will be returned by get_max_texture_size.
+    pub fn set_max_texture_size(&self, max_texture_size: u32){
+        self.max_texture_size.set(max_texture_size);
+    }
+
+    /// Changes the value that will be returned by supports_instancing. This should be called before the TextRenderer is
+    /// created, since the TextRenderer will decide whether to use instancing when it is created.
+    pub fn set_supports_instancing(&self, supports_instancing: bool){
+        self.supports_instancing.set(supports_instancing);
+    }
+
+    /// Gets a copy of all calls that were recorded since the creation of this backend or the last call to clear_calls.
+    pub fn get_calls(&self) -> Vec<BackendCall> {
+        self.calls.borrow().clone()
+    }
+
+    /// Forgets all calls that were recorded so far. The content of the buffers and textures and the values of the uniforms
+    /// will be remembered.
+    pub fn clear_calls(&self){
+        self.calls.borrow_mut().clear();
+    }
+
+    /// Gets the number of draw calls (including instanced and indexed draw calls) that were recorded since the creation of
+    /// this backend or the last call to clear_calls.
+    pub fn get_draw_count(&self) -> usize {
+        self.calls.borrow().iter().filter(|call| matches!(call, BackendCall::DrawTriangles { .. } |
+                BackendCall::DrawInstancedTriangles { .. } | BackendCall::DrawIndexedTriangles { .. })).count()
+    }
+
+    /// Gets a copy of the current content of the given buffer, or None if there is no such buffer (anymore).
+    pub fn get_buffer_data(&self, buffer: BufferHandle) -> Option<Vec<u8>> {
+        self.buffers.borrow().get(&buffer).cloned()
+    }
+
+    /// Gets the current content of the given buffer interpreted as little-endian floats, or None if there is no such buffer
+    /// (anymore). This is only meaningful for buffers that contain nothing but floats.
+    pub fn get_buffer_floats(&self, buffer: BufferHandle) -> Option<Vec<f32>> {
+        self.buffers.borrow().get(&buffer).map(|data| {
+            data.chunks_exact(4).map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect()
+        })
+    }
+
+    /// Gets the number of buffers that have been created, but not yet deleted.
+    pub fn get_buffer_count(&self) -> usize {
+        self.buffers.borrow().len()
+    }
+
+    /// Gets the width, height and a copy of the pixels that were most recently uploaded to the given texture. Returns None
+    /// if there is no such texture or nothing has been uploaded to it yet.
+    pub fn get_texture_data(&self, texture: TextureHandle) -> Option<(u32, u32, Vec<u8>)> {
+        self.textures.borrow().get(&texture).map(|recorded| (recorded.width, recorded.height, recorded.pixels.clone()))
+    }
+
+    /// Gets the value that was most recently assigned to the uniform variable with the given name of the given program, or
+    /// None if no value has been assigned to it yet.
+    pub fn get_uniform_value(&self, program: ProgramHandle, name: &str) -> Option<UniformValue> {
+        self.uniform_values.borrow().get(&(program, name.to_string())).copied()
+    }
+
+    /// Gets the program that was most recently passed to use_program, or None if no program has been used yet.
+    pub fn get_current_program(&self) -> Option<ProgramHandle> {
+        self.current_program.get()
+    }
+
+    /// Gets the name of the attribute with the given location, or None if no such location was handed out.
+    pub fn get_attrib_name(&self, location: i32) -> Option<String> {
+        self.attrib_names.borrow().get(location as usize).cloned()
+    }
+}
+
+impl TextBackend for RecordingBackend {
+
+    fn create_texture(&self) -> Result<TextureHandle, TextError> {
+        let texture = TextureHandle::new(self.next_handle());
+        self.record(BackendCall::CreateTexture(texture));
+        Ok(texture)
+    }
+
+    fn upload_texture(&self, texture: TextureHandle, width: u32, height: u32, pixels: &[u8]) -> Result<(), TextError> {
+        self.textures.borrow_mut().insert(texture, RecordedTexture {
+            width,
+            height,
+            pixels: pixels.to_vec()
+        });
+        self.record(BackendCall::UploadTexture { texture, width, height });
+        Ok(())
+    }
+
+    fn bind_texture(&self, texture_unit: u32, texture: TextureHandle){
+        self.record(BackendCall::BindTexture { texture_unit, texture });
+    }
+
+    fn delete_texture(&self, texture: TextureHandle){
+        self.textures.borrow_mut().remove(&texture);
+        self.record(BackendCall::DeleteTexture(texture));
+    }
+
+    fn get_max_texture_size(&self) -> u32 {
+        self.max_texture_size.get()
+    }
+
+    fn create_buffer(&self) -> Result<BufferHandle, TextError> {
+        let buffer = BufferHandle::new(self.next_handle());
+        self.buffers.borrow_mut().insert(buffer, Vec::new());
+        self.record(BackendCall::CreateBuffer(buffer));
+        Ok(buffer)
+    }
+
+    fn upload_buffer(&self, buffer: BufferHandle, data: &[u8]){
+        self.buffers.borrow_mut().insert(buffer, data.to_vec());
+        self.record(BackendCall::UploadBuffer { buffer, data: data.to_vec() });
+    }
+
+    fn allocate_buffer(&self, buffer: BufferHandle, byte_count: usize, usage: BufferUsage){
+
+        // The content of a freshly allocated buffer is undefined, but zeros are the most predictable choice
+        self.buffers.borrow_mut().insert(buffer, vec![0; byte_count]);
+        self.record(BackendCall::AllocateBuffer { buffer, byte_count, usage });
+    }
+
+    fn update_buffer(&self, buffer: BufferHandle, offset: usize, data: &[u8]){
+        if let Some(content) = self.buffers.borrow_mut().get_mut(&buffer) {
+            content[offset..offset + data.len()].copy_from_slice(data);
+        }
+        self.record(BackendCall::UpdateBuffer { buffer, offset, data: data.to_vec() });
+    }
+
+    fn delete_buffer(&self, buffer: BufferHandle){
+        self.buffers.borrow_mut().remove(&buffer);
+        self.record(BackendCall::DeleteBuffer(buffer));
+    }
+
+    fn set_vertex_attribute(&self, buffer: BufferHandle, location: i32, num_components: i32, attribute_type: AttributeType, stride: i32, offset: i32){
+        self.record(BackendCall::SetVertexAttribute { buffer, location, num_components, attribute_type, stride, offset });
+    }
+
+    fn disable_vertex_attribute(&self, location: i32){
+        self.record(BackendCall::DisableVertexAttribute(location));
+    }
+
+    fn upload_index_buffer(&self, buffer: BufferHandle, indices: &[u16]){
+        self.record(BackendCall::UploadIndexBuffer { buffer, indices: indices.to_vec() });
+    }
+
+    fn bind_index_buffer(&self, buffer: BufferHandle){
+        self.record(BackendCall::BindIndexBuffer(buffer));
+    }
+
+    fn draw_indexed_triangles(&self, first: i32, count: i32){
+        self.record(BackendCall::DrawIndexedTriangles { first, count });
+    }
+
+    fn supports_instancing(&self) -> bool {
+        self.supports_instancing.get()
+    }
+
+    fn create_vertex_array(&self) -> Result<VertexArrayHandle, TextError> {
+        let vertex_array = VertexArrayHandle::new(self.next_handle());
+        self.record(BackendCall::CreateVertexArray(vertex_array));
+        Ok(vertex_array)
+    }
+
+    fn bind_vertex_array(&self, vertex_array: Option<VertexArrayHandle>){
+        self.record(BackendCall::BindVertexArray(vertex_array));
+    }
+
+    fn delete_vertex_array(&self, vertex_array: VertexArrayHandle){
+        self.record(BackendCall::DeleteVertexArray(vertex_array));
+    }
+
+    fn set_attribute_divisor(&self, location: i32, divisor: u32){
+        self.record(BackendCall::SetAttributeDivisor { location, divisor });
+    }
+
+    fn draw_instanced_triangles(&self, first: i32, count: i32, instance_count: i32){
+        self.record(BackendCall::DrawInstancedTriangles { first, count, instance_count });
+    }
+
+    fn create_program(&self, _vertex_source: &str, _fragment_source: &str) -> Result<ProgramHandle, TextError> {
+        let program = ProgramHandle::new(self.next_handle());
+        self.record(BackendCall::CreateProgram(program));
+        Ok(program)
+    }
+
+    fn use_program(&self, program: ProgramHandle){
+        self.current_program.set(Some(program));
+        self.record(BackendCall::UseProgram(program));
+    }
+
+    fn delete_program(&self, program: ProgramHandle){
+        self.record(BackendCall::DeleteProgram(program));
+    }
+
+    fn get_attrib_location(&self, _program: ProgramHandle, name: &str) -> i32 {
+        let mut attrib_names = self.attrib_names.borrow_mut();
+        match attrib_names.iter().position(|existing| existing == name) {
+            Some(location) => location as i32,
+            None => {
+                attrib_names.push(name.to_string());
+                (attrib_names.len() - 1) as i32
+            }
+        }
+    }
+
+    fn get_uniform_location(&self, program: ProgramHandle, name: &str) -> Result<UniformHandle, TextError> {
+        let mut uniforms = self.uniforms.borrow_mut();
+        uniforms.push((program, name.to_string()));
+        Ok(UniformHandle::new((uniforms.len() - 1) as u32))
+    }
+
+    fn set_uniform_1i(&self, uniform: UniformHandle, value: i32){
+        self.set_uniform(uniform, UniformValue::Int(value));
+    }
+
+    fn set_uniform_1f(&self, uniform: UniformHandle, value: f32){
+        self.set_uniform(uniform, UniformValue::Float(value));
+    }
+
+    fn set_uniform_2f(&self, uniform: UniformHandle, x: f32, y: f32){
+        self.set_uniform(uniform, UniformValue::Vec2(x, y));
+    }
+
+    fn set_uniform_4f(&self, uniform: UniformHandle, x: f32, y: f32, z: f32, w: f32){
+        self.set_uniform(uniform, UniformValue::Vec4(x, y, z, w));
+    }
+
+    fn draw_triangles(&self, first: i32, count: i32){
+        self.record(BackendCall::DrawTriangles { first, count });
+    }
+
+    fn enable_blending(&self){
+        self.record(BackendCall::EnableBlending);
+    }
+
+    fn get_surface_size(&self) -> Option<(u32, u32)> {
+        self.surface_size.get()
+    }
+}
+
+impl Default for RecordingBackend {
+
+    fn default() -> RecordingBackend {
+        RecordingBackend::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn uniforms_are_remembered_per_program(){
+        let backend = RecordingBackend::new();
+        let first = backend.create_program("", "").expect("The program should be created");
+        let second = backend.create_program("", "").expect("The program should be created");
+        let first_color = backend.get_uniform_location(first, "fillColor").expect("The uniform should exist");
+        let second_color = backend.get_uniform_location(second, "fillColor").expect("The uniform should exist");
+
+        backend.set_uniform_4f(first_color, 1.0, 0.0, 0.0, 1.0);
+        backend.set_uniform_4f(second_color, 0.0, 0.0, 1.0, 1.0);
+        assert_eq!(backend.get_uniform_value(first, "fillColor"), Some(UniformValue::Vec4(1.0, 0.0, 0.0, 1.0)));
+        assert_eq!(backend.get_uniform_value(second, "fillColor"), Some(UniformValue::Vec4(0.0, 0.0, 1.0, 1.0)));
+        assert_eq!(backend.get_uniform_value(first, "strokeColor"), None);
+    }
+
+    #[test]
+    fn buffer_updates_are_applied_to_the_content(){
+        let backend = RecordingBackend::new();
+        let buffer = backend.create_buffer().expect("The buffer should be created");
+        backend.allocate_buffer(buffer, 12, BufferUsage::Dynamic);
+        backend.update_buffer(buffer, 4, &float_bytes(&[1.5, -2.0]));
+        assert_eq!(backend.get_buffer_floats(buffer), Some(vec![0.0, 1.5, -2.0]));
+        assert_eq!(backend.get_buffer_data(buffer).map(|data| data.len()), Some(12));
+    }
+}
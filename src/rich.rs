@@ -0,0 +1,570 @@
+use wasmuri_core::*;
+
+use super::alignment::HorizontalAlignment;
+use super::backend::{
+    BufferHandle,
+    float_bytes
+};
+use super::decoration::{
+    DecorationLine,
+    TextDecorations,
+    compute_decoration_lines
+};
+use super::error::TextError;
+use super::layout::TextLayout;
+use super::shaders::{
+    FLOATS_PER_RICH_VERTEX,
+    MAX_QUADS_PER_DRAW,
+    RichTextProgram,
+    get_color_components
+};
+use super::Font;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A piece of text with its own colors, style and (optionally) Font. A list of TextSpan's can be turned into a
+/// RichTextModel with the create_rich_text_model method of TextRenderer, and the parse_markup function can create a list
+/// of TextSpan's from markup. The text of a span can contain '\n' line breaks, and a line can consist of the text of
+/// multiple spans.
+#[derive(Clone)]
+pub struct TextSpan {
+
+    pub text: String,
+    pub colors: TextColors,
+
+    /// Whether the characters of this span should be drawn bold. The Font doesn't need a bold variant for this: every
+    /// character will be drawn twice, with a small horizontal offset. The characters will not take more space, so the
+    /// ink of bold characters will come a little closer to the next character. If the span has a bold Font, this should
+    /// be false.
+    pub bold: bool,
+
+    /// Whether the characters of this span should be drawn italic. The Font doesn't need an italic variant for this: the
+    /// characters will be slanted around the baseline, so the top of a character will lean towards the next character.
+    /// If the span has an italic Font, this should be false.
+    pub italic: bool,
+
+    /// The decoration lines that will be drawn along the characters of this span, with the fill color of this span. The
+    /// decoration lines are placed with the FontMetrics of the Font of the RichTextModel, so they will be continuous when
+    /// neighbouring spans have different Font's.
+    pub decorations: TextDecorations,
+
+    /// The Font that will draw the characters of this span, or None to use the Font of the RichTextModel. This Font must
+    /// have been created by the same TextRenderer as the Font of the RichTextModel. It will be scaled such that its line
+    /// height matches the line height of the Font of the RichTextModel, and its baseline will be aligned with the baseline
+    /// of the Font of the RichTextModel.
+    pub font: Option<Rc<Font>>
+}
+
+impl TextSpan {
+
+    /// Creates a TextSpan with the given text and colors, which is neither bold nor italic, doesn't have any decorations
+    /// and uses the Font of the RichTextModel
+    pub fn new(text: &str, colors: TextColors) -> TextSpan {
+        TextSpan {
+            text: text.to_string(),
+            colors,
+            bold: false,
+            italic: false,
+            decorations: TextDecorations::NONE,
+            font: None
+        }
+    }
+
+    /// Creates a TextSpan with the given text and colors, which will be drawn with the given Font. Use this for instance to
+    /// draw a single word with a bold variant of the Font of the RichTextModel.
+    pub fn with_font(text: &str, colors: TextColors, font: &Rc<Font>) -> TextSpan {
+        TextSpan {
+            font: Some(Rc::clone(font)),
+            ..TextSpan::new(text, colors)
+        }
+    }
+}
+
+/// The horizontal offset (in model space) of the second copy of every character of a bold span
+const BOLD_OFFSET: f32 = 0.03;
+
+/// The horizontal distance an italic character is shifted for every unit (in model space) above the baseline, which is
+/// the tangent of the slant angle (roughly 11 degrees)
+const ITALIC_SLANT: f32 = 0.2;
+
+/// A RichTextModel draws text that consists of multiple TextSpan's, each with its own fill color, stroke color, background
+/// color and (optionally) Font. Unlike a TextModel, the colors are stored in the vertex buffer rather than passed to the
+/// render method, so the entire text only needs 1 draw call per atlas page of every Font it uses, no matter how many
+/// spans it has. This makes it easy to highlight a single word of a sentence, like the name of the player in a chat
+/// message.
+///
+/// Instances of RichTextModel can be created with the create_rich_text_model and create_wrapped_rich_text_model methods
+/// of TextRenderer. Just like the render method of TextModel, the render method of RichTextModel should only be called
+/// after the start_rendering method of the TextRenderer.
+///
+/// The background color of a span will only fill the space behind the characters of that span (from the top to the
+/// bottom of their line), rather than the entire block of text. Just like the backgrounds of a TextBatch, all backgrounds
+/// are drawn before any of the characters. The decoration lines of the spans are drawn after all characters.
+///
+/// The characters are drawn grouped by Font and atlas page rather than in the order of the text, so every backing texture
+/// only needs to be bound once, even if the text switches between Font's many times.
+pub struct RichTextModel {
+
+    /// The Font's of this RichTextModel. The first Font is the Font that was passed to create_rich_text_model, the others
+    /// are the Font's of the spans.
+    fonts: Vec<RichFont>,
+
+    buffer: BufferHandle,
+
+    layout: TextLayout,
+    spans: Vec<TextSpan>,
+
+    /// The index (in fonts) of the Font of every span
+    span_fonts: Vec<usize>,
+
+    backgrounds: Vec<SpanBackground>,
+    decoration_lines: Vec<DecorationLine>,
+
+    /// The indices of all quads of the layout, sorted by Font and atlas page. The vertex data is stored in this order.
+    draw_order: Vec<usize>,
+
+    /// The Font index, atlas page, first quad and quad count of every draw call that is needed to render this
+    /// RichTextModel. The first draw call also draws the backgrounds, and the last draw call also draws the decoration
+    /// lines.
+    page_draws: Vec<(usize, usize, i32, i32)>
+}
+
+/// A Font that is used by a RichTextModel, together with the shader program for that Font
+struct RichFont {
+
+    font: Rc<Font>,
+    program: Rc<RichTextProgram>,
+
+    /// The sizes of the atlas pages of the Font at the time the texture coordinates in the buffer were computed
+    page_sizes: RefCell<Vec<(u32, u32)>>
+}
+
+/// The part of a single line that is covered by a single span, which will be filled with the background color of the span.
+/// The coordinates are in model space.
+struct SpanBackground {
+
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+
+    color: Color
+}
+
+impl RichTextModel {
+
+    /// Creates a RichTextModel with the given layout. The fonts are the Font's returned by collect_span_fonts, together with
+    /// their shader programs, and span_fonts is the index of the Font of every span.
+    pub(super) fn new(fonts: Vec<(Rc<Font>, Rc<RichTextProgram>)>, span_fonts: Vec<usize>, layout: TextLayout, spans: &[TextSpan]) -> Result<RichTextModel, TextError> {
+        let backgrounds = compute_backgrounds(&layout, spans);
+
+        // The decoration lines are placed on the baselines of the primary Font, which are shared by all Font's
+        let decoration_lines = compute_decoration_lines(&fonts[0].0, &layout, HorizontalAlignment::Left, 0.0, |span| spans[span].decorations);
+
+        let mut draw_order: Vec<usize> = (0..layout.quads.len()).collect();
+        draw_order.sort_by_key(|quad_index| {
+            let quad = &layout.quads[*quad_index];
+            (span_fonts[quad.span], quad.character.get_page())
+        });
+        let page_draws = compute_rich_page_draws(&layout, &draw_order, spans, &span_fonts, backgrounds.len(), decoration_lines.len());
+
+        let buffer = fonts[0].0.backend.create_buffer()?;
+        let model = RichTextModel {
+            fonts: fonts.into_iter().map(|(font, program)| RichFont {
+                page_sizes: RefCell::new(font.get_page_sizes()),
+                font,
+                program
+            }).collect(),
+            buffer,
+            layout,
+            spans: spans.to_vec(),
+            span_fonts,
+            backgrounds,
+            decoration_lines,
+            draw_order,
+            page_draws
+        };
+        model.get_font().backend.upload_buffer(model.buffer, &model.create_vertex_data());
+        Ok(model)
+    }
+
+    /// Creates the vertex data of this RichTextModel (see RichTextProgram), for the current page_sizes of its Font's. The
+    /// backgrounds come first, followed by the characters in the draw order and the decoration lines. The characters of bold
+    /// spans have 2 quads.
+    fn create_vertex_data(&self) -> Vec<u8> {
+        let transparent = [0.0; 4];
+        let quad_count: i32 = self.page_draws.iter().map(|(_font, _page, _first_quad, quad_count)| quad_count).sum();
+        let mut vertex_data = Vec::with_capacity(4 * FLOATS_PER_RICH_VERTEX * quad_count as usize);
+
+        // The bottom-right corner of every page is empty, so it will only show the background color
+        for background in &self.backgrounds {
+            let rect = [background.min_x, background.min_y, background.max_x, background.max_y];
+            push_quad(&mut vertex_data, get_corners(rect, 0.0, 0.0), [1.0; 4], [transparent, transparent, get_color_components(background.color)]);
+        }
+
+        // Italic characters are slanted around the baseline of their line (which is shared by all Font's)
+        let baseline_height = self.get_font().get_baseline_height();
+        let mut baselines = vec![0.0; self.layout.quads.len()];
+        for line in &self.layout.lines {
+            baselines[line.first_quad..line.first_quad + line.quad_count].fill(line.min_y + baseline_height);
+        }
+
+        let page_sizes: Vec<_> = self.fonts.iter().map(|rich_font| rich_font.page_sizes.borrow()).collect();
+        for quad_index in &self.draw_order {
+            let quad = &self.layout.quads[*quad_index];
+            let texture_char = quad.character;
+            let (texture_width, texture_height) = page_sizes[self.span_fonts[quad.span]][texture_char.get_page()];
+            let texture_rect = [
+                texture_char.get_left_u(texture_width), texture_char.get_bottom_v(texture_height),
+                texture_char.get_right_u(texture_width), texture_char.get_top_v(texture_height)
+            ];
+            let span = &self.spans[quad.span];
+            let colors = [get_color_components(span.colors.fill_color), get_color_components(span.colors.stroke_color), transparent];
+            let slant = if span.italic { ITALIC_SLANT } else { 0.0 };
+            let rect = [quad.min_x, quad.min_y, quad.max_x, quad.max_y];
+            push_quad(&mut vertex_data, get_corners(rect, slant, baselines[*quad_index]), texture_rect, colors);
+            if span.bold {
+                let bold_rect = [quad.min_x + BOLD_OFFSET, quad.min_y, quad.max_x + BOLD_OFFSET, quad.max_y];
+                push_quad(&mut vertex_data, get_corners(bold_rect, slant, baselines[*quad_index]), texture_rect, colors);
+            }
+        }
+
+        // Just like the backgrounds, the decoration lines only use the empty corner of the page
+        for line in &self.decoration_lines {
+            let color = get_color_components(self.spans[line.span].colors.fill_color);
+            let rect = [line.min_x, line.min_y, line.max_x, line.max_y];
+            push_quad(&mut vertex_data, get_corners(rect, 0.0, 0.0), [1.0; 4], [transparent, transparent, color]);
+        }
+
+        float_bytes(&vertex_data)
+    }
+
+    /// Renders this RichTextModel with the given bottom-left corner and line height. The parameters have the same meaning
+    /// as for the render method of TextModel, but the colors are determined by the spans of this RichTextModel.
+    ///
+    /// The characters of Font's that use a distance field atlas will use the line_width of the TextRenderer at the time
+    /// their Font was created as stroke width. Use render_with_line_width to choose another stroke width.
+    pub fn render(&self, offset_x: f32, offset_y: f32, scale_y: f32){
+        self.draw(offset_x, offset_y, scale_y, None);
+    }
+
+    /// Renders this RichTextModel like the render method, but with the given line_width for the stroke of all Font's. See
+    /// the render_with_line_width method of TextModel for more information about the line_width.
+    pub fn render_with_line_width(&self, offset_x: f32, offset_y: f32, scale_y: f32, line_width: f32){
+        self.draw(offset_x, offset_y, scale_y, Some(line_width));
+    }
+
+    /// Draws this RichTextModel with the given line_width, or the default line_width of every Font if it is None
+    fn draw(&self, offset_x: f32, offset_y: f32, scale_y: f32, line_width: Option<f32>){
+
+        // Just like the render method of TextModel, don't draw anything (not even the backgrounds) for empty text
+        if self.page_draws.is_empty() {
+            return;
+        }
+
+        let backend = &self.get_font().backend;
+
+        // The texture coordinates in the buffer are no longer correct when an atlas page has grown
+        let mut outdated = false;
+        for rich_font in &self.fonts {
+            rich_font.font.upload_pages();
+            let page_sizes = rich_font.font.get_page_sizes();
+            if *rich_font.page_sizes.borrow() != page_sizes {
+                rich_font.page_sizes.replace(page_sizes);
+                outdated = true;
+            }
+        }
+        if outdated {
+            backend.upload_buffer(self.buffer, &self.create_vertex_data());
+        }
+
+        let f32_size = 4;
+        let quad_size = f32_size * 4 * FLOATS_PER_RICH_VERTEX;
+        let scale_x = scale_y / self.get_font().aspect_ratio.get();
+        let mut current_font: Option<usize> = None;
+        for (font_index, page, first_quad, quad_count) in &self.page_draws {
+            let rich_font = &self.fonts[*font_index];
+            let font = &rich_font.font;
+            let program = &rich_font.program;
+
+            // The draw calls are sorted by Font, so every Font only needs to prepare its program once
+            if current_font != Some(*font_index) {
+                if let Some(previous_font) = current_font {
+                    self.fonts[previous_font].program.unbind();
+                }
+                program.use_program();
+                program.set_texture_sampler(0);
+                program.set_screen_position(offset_x, offset_y);
+                program.set_scale(scale_x, scale_y);
+                if font.distance_field.is_some() {
+                    program.set_smoothing(font.get_smoothing(scale_y));
+                    program.set_stroke_width(font.get_stroke_width(line_width.unwrap_or_else(|| font.get_default_line_width())));
+                }
+                current_font = Some(*font_index);
+            }
+            backend.bind_texture(0, font.get_page_texture(*page));
+
+            // The u16 indices can only refer to a limited number of quads, so large pages are drawn in parts
+            let mut drawn_quads = 0;
+            while drawn_quads < *quad_count as usize {
+                let part_quads = (*quad_count as usize - drawn_quads).min(MAX_QUADS_PER_DRAW);
+                let part_offset = quad_size * (*first_quad as usize + drawn_quads);
+                program.bind(self.buffer, part_offset as i32, part_quads);
+                backend.draw_indexed_triangles(0, (6 * part_quads) as i32);
+                drawn_quads += part_quads;
+            }
+        }
+        if let Some(last_font) = current_font {
+            self.fonts[last_font].program.unbind();
+        }
+
+        // The shader program of the Font's is no longer in use, so the next TextModel must select its Font again
+        self.get_font().selected_font.set(None);
+    }
+
+    /// Gets the width of this RichTextModel when it would be rendered with the given scale_y. See the get_render_width
+    /// method of TextModel.
+    pub fn get_render_width(&self, scale_y: f32) -> f32 {
+        scale_y / self.get_font().aspect_ratio.get() * self.layout.width
+    }
+
+    /// Gets the height of this RichTextModel when it would be rendered with the given scale_y. See the get_render_height
+    /// method of TextModel.
+    pub fn get_render_height(&self, scale_y: f32) -> f32 {
+        scale_y * self.layout.height
+    }
+
+    /// Gets the number of lines of this RichTextModel, including the line breaks that were inserted by wrapping
+    pub fn get_line_count(&self) -> usize {
+        self.layout.lines.len()
+    }
+
+    /// Gets the spans this RichTextModel was created from
+    pub fn get_spans(&self) -> &[TextSpan] {
+        &self.spans
+    }
+
+    /// Gets the Font that was passed to the create_rich_text_model method of TextRenderer. The spans of this RichTextModel
+    /// may use other Font's.
+    pub fn get_font(&self) -> &Rc<Font> {
+        &self.fonts[0].font
+    }
+}
+
+impl Drop for RichTextModel {
+
+    fn drop(&mut self){
+        self.get_font().backend.delete_buffer(self.buffer);
+    }
+}
+
+/// Collects the Font's that are needed to draw the given spans, where the given Font will be used for the spans without
+/// their own Font. Returns the distinct Font's (starting with the given Font) and the index of the Font of every span.
+pub(super) fn collect_span_fonts(font: &Rc<Font>, spans: &[TextSpan]) -> (Vec<Rc<Font>>, Vec<usize>) {
+    let mut fonts = vec![Rc::clone(font)];
+    let span_fonts = spans.iter().map(|span| {
+        let span_font = span.font.as_ref().unwrap_or(font);
+        match fonts.iter().position(|known_font| Rc::ptr_eq(known_font, span_font)) {
+            Some(index) => index,
+            None => {
+                fonts.push(Rc::clone(span_font));
+                fonts.len() - 1
+            }
+        }
+    }).collect();
+    (fonts, span_fonts)
+}
+
+/// Computes the backgrounds of the spans with a visible background color. Every line gets 1 background for every group of
+/// successive characters of the same span.
+fn compute_backgrounds(layout: &TextLayout, spans: &[TextSpan]) -> Vec<SpanBackground> {
+    let mut backgrounds = Vec::new();
+    for line in &layout.lines {
+        let line_quads = &layout.quads[line.first_quad..line.first_quad + line.quad_count];
+        for span_quads in line_quads.chunk_by(|left, right| left.span == right.span) {
+            let color = spans[span_quads[0].span].colors.background_color;
+            if color.get_alpha_float() > 0.0 {
+                backgrounds.push(SpanBackground {
+                    min_x: span_quads[0].advance_min_x,
+                    min_y: line.min_y,
+                    max_x: span_quads[span_quads.len() - 1].advance_max_x,
+                    max_y: line.min_y + 1.0,
+                    color
+                });
+            }
+        }
+    }
+    backgrounds
+}
+
+/// Computes the Font index, atlas page, first quad and quad count of every draw call that is needed to draw a
+/// RichTextModel with the given layout, draw order, spans, number of backgrounds and number of decoration lines. The
+/// characters of bold spans need 2 quads. The backgrounds are put in front of the characters of the first draw call, so
+/// they will be drawn before any character, and the decoration lines are put after the characters of the last draw call.
+fn compute_rich_page_draws(layout: &TextLayout, draw_order: &[usize], spans: &[TextSpan], span_fonts: &[usize], background_count: usize, decoration_count: usize) -> Vec<(usize, usize, i32, i32)> {
+    let mut page_draws: Vec<(usize, usize, i32, i32)> = Vec::new();
+    let mut next_quad = background_count as i32;
+    for quad_index in draw_order {
+        let quad = &layout.quads[*quad_index];
+        let font_index = span_fonts[quad.span];
+        let page = quad.character.get_page();
+        let quad_count = if spans[quad.span].bold { 2 } else { 1 };
+        match page_draws.last_mut() {
+            Some((last_font, last_page, _first_quad, last_quad_count)) if *last_font == font_index && *last_page == page => {
+                *last_quad_count += quad_count;
+            },
+            _ => page_draws.push((font_index, page, next_quad, quad_count))
+        };
+        next_quad += quad_count;
+    }
+    if let Some((_font, _page, first_quad, quad_count)) = page_draws.first_mut() {
+        *first_quad = 0;
+        *quad_count += background_count as i32;
+    }
+    if let Some((_font, _page, _first_quad, quad_count)) = page_draws.last_mut() {
+        *quad_count += decoration_count as i32;
+    }
+    page_draws
+}
+
+/// Gets the corners of the given rectangle (min_x, min_y, max_x, max_y) in the order of QUAD_INDICES. The corners will be
+/// shifted horizontally by slant for every unit they are above the given baseline.
+fn get_corners(rect: [f32; 4], slant: f32, baseline: f32) -> [(f32, f32); 4] {
+    let [min_x, min_y, max_x, max_y] = rect;
+    let bottom_shift = slant * (min_y - baseline);
+    let top_shift = slant * (max_y - baseline);
+    [
+        (min_x + bottom_shift, min_y),
+        (max_x + bottom_shift, min_y),
+        (max_x + top_shift, max_y),
+        (min_x + top_shift, max_y)
+    ]
+}
+
+/// Adds the 4 vertices of a quad with the given corners (see get_corners), texture coordinates (left_u, bottom_v, right_u,
+/// top_v) and colors (fill, stroke and background) to the given vertex data
+fn push_quad(vertex_data: &mut Vec<f32>, corners: [(f32, f32); 4], texture_rect: [f32; 4], colors: [[f32; 4]; 3]){
+    let [left_u, bottom_v, right_u, top_v] = texture_rect;
+    let texture_corners = [(left_u, bottom_v), (right_u, bottom_v), (right_u, top_v), (left_u, top_v)];
+    for ((x, y), (u, v)) in corners.iter().zip(texture_corners.iter()) {
+        vertex_data.extend_from_slice(&[*x, *y, *u, *v]);
+        for color in colors.iter() {
+            vertex_data.extend_from_slice(color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use super::super::*;
+    use super::super::testing::*;
+
+    fn create_spans(small_font: &Rc<Font>) -> Vec<TextSpan> {
+        let mut highlighted = TextSpan::new("Ab", test_colors(Color::from_rgb(255, 0, 0)));
+        highlighted.colors.background_color = Color::from_rgb(0, 0, 255);
+        let mut bold = TextSpan::with_font("cd", test_colors(Color::from_rgb(0, 255, 0)), small_font);
+        bold.bold = true;
+        bold.decorations = TextDecorations::UNDERLINE;
+        vec![highlighted, bold]
+    }
+
+    #[test]
+    fn backgrounds_and_decorations_are_added_to_the_first_and_last_draw() {
+        let (_backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        renderer.font_size = 20;
+        let small_font = add_test_font(&mut renderer);
+
+        let spans = create_spans(&small_font);
+        let model = renderer.create_rich_text_model(&font, &spans);
+        assert_eq!(model.fonts.len(), 2);
+        assert_eq!(model.backgrounds.len(), 1);
+        assert_eq!(model.decoration_lines.len(), 1);
+
+        // The bold characters of the small Font need 2 quads each
+        assert_eq!(model.page_draws, vec![(0, 0, 0, 1 + 2), (1, 0, 3, 2 * 2 + 1)]);
+
+        let page_draws = compute_rich_page_draws(&model.layout, &model.draw_order, &model.spans, &model.span_fonts, 0, 0);
+        assert_eq!(page_draws, vec![(0, 0, 0, 2), (1, 0, 2, 4)]);
+    }
+
+    #[test]
+    fn span_colors_are_stored_in_the_vertices() {
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        let spans = vec![create_spans(&font).remove(0), TextSpan::new("cd", test_colors(Color::from_rgb(0, 255, 0)))];
+        let model = renderer.create_rich_text_model(&font, &spans);
+        let floats = backend.get_buffer_floats(model.buffer).unwrap();
+        let quads: Vec<_> = floats.chunks_exact(4 * FLOATS_PER_RICH_VERTEX).collect();
+        assert_eq!(quads.len(), 5);
+
+        // The background only uses the empty bottom-right corner of the page and only has a background color
+        for vertex in quads[0].chunks_exact(FLOATS_PER_RICH_VERTEX) {
+            assert_eq!(&vertex[2..4], &[1.0, 1.0]);
+            assert_eq!(&vertex[4..12], &[0.0; 8]);
+            assert_eq!(&vertex[12..16], &[0.0, 0.0, 1.0, 1.0]);
+        }
+
+        // The characters have the fill color of their span and a transparent background
+        let expected_fill_colors = [[1.0, 0.0, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 1.0], [0.0, 1.0, 0.0, 1.0]];
+        for (quad, fill_color) in quads[1..].iter().zip(expected_fill_colors.iter()) {
+            for vertex in quad.chunks_exact(FLOATS_PER_RICH_VERTEX) {
+                assert_eq!(&vertex[4..8], fill_color);
+                assert_eq!(&vertex[12..16], &[0.0; 4]);
+            }
+        }
+    }
+
+    #[test]
+    fn bold_characters_are_drawn_twice() {
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        let spans = vec![TextSpan { bold: true, ..TextSpan::new("ab", test_colors(Color::from_rgb(0, 0, 0))) }];
+        let model = renderer.create_rich_text_model(&font, &spans);
+        assert_eq!(model.page_draws, vec![(0, 0, 0, 4)]);
+
+        // The second quad of every character is the first quad moved to the right
+        let floats = backend.get_buffer_floats(model.buffer).unwrap();
+        let quads: Vec<_> = floats.chunks_exact(4 * FLOATS_PER_RICH_VERTEX).collect();
+        assert_eq!(quads.len(), 4);
+        for pair in quads.chunks_exact(2) {
+            for (normal, bold) in pair[0].chunks_exact(FLOATS_PER_RICH_VERTEX).zip(pair[1].chunks_exact(FLOATS_PER_RICH_VERTEX)) {
+                assert!((bold[0] - normal[0] - BOLD_OFFSET).abs() < 0.0001);
+                assert_eq!(&bold[1..], &normal[1..]);
+            }
+        }
+
+        renderer.start_rendering();
+        backend.clear_calls();
+        model.render(0.0, 0.0, 0.1);
+        let draws: Vec<_> = backend.get_calls().into_iter().filter(|call| matches!(call, BackendCall::DrawIndexedTriangles { .. })).collect();
+        assert_eq!(draws, vec![BackendCall::DrawIndexedTriangles { first: 0, count: 6 * 4 }]);
+    }
+
+    #[test]
+    fn every_font_is_drawn_with_its_own_texture() {
+        let (backend, mut renderer) = create_test_renderer(false);
+        let font = add_test_font(&mut renderer);
+        renderer.font_size = 20;
+        let small_font = add_test_font(&mut renderer);
+        let model = renderer.create_rich_text_model(&font, &create_spans(&small_font));
+
+        renderer.start_rendering();
+        backend.clear_calls();
+        model.render(0.0, 0.0, 0.1);
+        let calls = backend.get_calls();
+        let draw_counts: Vec<_> = calls.iter().filter_map(|call| match call {
+            BackendCall::DrawIndexedTriangles { count, .. } => Some(*count),
+            _ => None
+        }).collect();
+        assert_eq!(draw_counts, vec![6 * 3, 6 * 5]);
+        let textures: Vec<_> = calls.iter().filter_map(|call| match call {
+            BackendCall::BindTexture { texture, .. } => Some(*texture),
+            _ => None
+        }).collect();
+        assert_eq!(textures, vec![font.get_page_texture(0), small_font.get_page_texture(0)]);
+    }
+}
@@ -14,6 +14,24 @@ void main(){
 }
 ";
 
+const INSTANCED_VERTEX_SOURCE: &str = "
+
+attribute vec2 corner;
+attribute vec4 glyphRect;
+attribute vec4 textureRect;
+
+varying vec2 passTextureCoords;
+
+uniform vec2 screenPosition;
+uniform vec2 scale;
+
+void main(){
+    vec2 relativePosition = mix(glyphRect.xy, glyphRect.zw, corner);
+    gl_Position = vec4(screenPosition + scale * relativePosition, 0.0, 1.0);
+    passTextureCoords = mix(textureRect.xy, textureRect.zw, corner);
+}
+";
+
 const FRAGMENT_SOURCE: &str = "
 
 precision mediump float;
@@ -26,100 +44,336 @@ uniform vec4 fillColor;
 uniform vec4 strokeColor;
 uniform vec4 backgroundColor;
 
+// Soft shadows use the blurred coverage in the alpha channel, with the fill color as shadow color
+uniform float shadowSoftness;
+
 void main(){
     vec4 texelColor = texture2D(textureSampler, passTextureCoords);
-    gl_FragColor = backgroundColor * texelColor.r + fillColor * texelColor.g + strokeColor * texelColor.b;
+    vec4 color = backgroundColor * texelColor.r + fillColor * texelColor.g + strokeColor * texelColor.b;
+    gl_FragColor = mix(color, fillColor * texelColor.a, shadowSoftness);
+}
+";
+
+/// The GLSL function that computes the color of a fragment from the distance field, which is shared by all distance field
+/// fragment shaders. It is a macro so that it can be put in their sources with concat!
+macro_rules! distance_field_color_function { () => { "
+vec4 computeDistanceFieldColor(float distance, float smoothing, float strokeWidth, vec4 fill, vec4 stroke, vec4 background){
+    float halfStroke = 0.5 * strokeWidth;
+    float outer = smoothstep(0.5 - halfStroke - smoothing, 0.5 - halfStroke + smoothing, distance);
+    float inner = smoothstep(0.5 + halfStroke - smoothing, 0.5 + halfStroke + smoothing, distance);
+    return background * (1.0 - outer) + stroke * (outer - inner) + fill * inner;
+}
+" } }
+
+const DISTANCE_FIELD_FRAGMENT_SOURCE: &str = concat!("
+
+precision mediump float;
+
+varying vec2 passTextureCoords;
+
+uniform sampler2D textureSampler;
+
+uniform vec4 fillColor;
+uniform vec4 strokeColor;
+uniform vec4 backgroundColor;
+
+uniform float smoothing;
+uniform float strokeWidth;
+", distance_field_color_function!(), "
+void main(){
+    float distance = texture2D(textureSampler, passTextureCoords).r;
+    gl_FragColor = computeDistanceFieldColor(distance, smoothing, strokeWidth, fillColor, strokeColor, backgroundColor);
+}
+");
+
+const BATCH_VERTEX_SOURCE: &str = "
+
+attribute vec2 position;
+attribute vec2 textureCoords;
+attribute vec4 fillColor;
+attribute vec4 strokeColor;
+attribute vec4 backgroundColor;
+
+varying vec2 passTextureCoords;
+varying vec4 passFillColor;
+varying vec4 passStrokeColor;
+varying vec4 passBackgroundColor;
+
+void main(){
+    gl_Position = vec4(position, 0.0, 1.0);
+    passTextureCoords = textureCoords;
+    passFillColor = fillColor;
+    passStrokeColor = strokeColor;
+    passBackgroundColor = backgroundColor;
+}
+";
+
+const BATCH_FRAGMENT_SOURCE: &str = "
+
+precision mediump float;
+
+varying vec2 passTextureCoords;
+varying vec4 passFillColor;
+varying vec4 passStrokeColor;
+varying vec4 passBackgroundColor;
+
+uniform sampler2D textureSampler;
+
+void main(){
+    vec4 texelColor = texture2D(textureSampler, passTextureCoords);
+    gl_FragColor = passBackgroundColor * texelColor.r + passFillColor * texelColor.g + passStrokeColor * texelColor.b;
+}
+";
+
+const BATCH_DISTANCE_FIELD_VERTEX_SOURCE: &str = "
+
+attribute vec2 position;
+attribute vec2 textureCoords;
+attribute vec4 fillColor;
+attribute vec4 strokeColor;
+attribute vec4 backgroundColor;
+attribute vec2 distanceParams;
+
+varying vec2 passTextureCoords;
+varying vec4 passFillColor;
+varying vec4 passStrokeColor;
+varying vec4 passBackgroundColor;
+varying vec2 passDistanceParams;
+
+void main(){
+    gl_Position = vec4(position, 0.0, 1.0);
+    passTextureCoords = textureCoords;
+    passFillColor = fillColor;
+    passStrokeColor = strokeColor;
+    passBackgroundColor = backgroundColor;
+    passDistanceParams = distanceParams;
+}
+";
+
+const BATCH_DISTANCE_FIELD_FRAGMENT_SOURCE: &str = concat!("
+
+precision mediump float;
+
+varying vec2 passTextureCoords;
+varying vec4 passFillColor;
+varying vec4 passStrokeColor;
+varying vec4 passBackgroundColor;
+
+// The x-coordinate is the smoothing and the y-coordinate is the stroke width
+varying vec2 passDistanceParams;
+
+uniform sampler2D textureSampler;
+", distance_field_color_function!(), "
+void main(){
+    float distance = texture2D(textureSampler, passTextureCoords).r;
+    gl_FragColor = computeDistanceFieldColor(distance, passDistanceParams.x, passDistanceParams.y, passFillColor, passStrokeColor, passBackgroundColor);
+}
+");
+
+const RICH_VERTEX_SOURCE: &str = "
+
+attribute vec2 relativePosition;
+attribute vec2 textureCoords;
+attribute vec4 fillColor;
+attribute vec4 strokeColor;
+attribute vec4 backgroundColor;
+
+varying vec2 passTextureCoords;
+varying vec4 passFillColor;
+varying vec4 passStrokeColor;
+varying vec4 passBackgroundColor;
+
+uniform vec2 screenPosition;
+uniform vec2 scale;
+
+void main(){
+    gl_Position = vec4(screenPosition + scale * relativePosition, 0.0, 1.0);
+    passTextureCoords = textureCoords;
+    passFillColor = fillColor;
+    passStrokeColor = strokeColor;
+    passBackgroundColor = backgroundColor;
 }
 ";
 
-use web_sys::WebGlRenderingContext as GL;
+const RICH_DISTANCE_FIELD_FRAGMENT_SOURCE: &str = concat!("
+
+precision mediump float;
 
-use web_sys::WebGlProgram;
-use web_sys::WebGlShader;
-use web_sys::WebGlUniformLocation;
+varying vec2 passTextureCoords;
+varying vec4 passFillColor;
+varying vec4 passStrokeColor;
+varying vec4 passBackgroundColor;
 
+uniform sampler2D textureSampler;
+
+uniform float smoothing;
+uniform float strokeWidth;
+", distance_field_color_function!(), "
+void main(){
+    float distance = texture2D(textureSampler, passTextureCoords).r;
+    gl_FragColor = computeDistanceFieldColor(distance, smoothing, strokeWidth, passFillColor, passStrokeColor, passBackgroundColor);
+}
+");
+
+use std::cell::Cell;
 use std::rc::Rc;
 
 use wasmuri_core::Color;
 
+use super::backend::*;
+use super::layout::QUAD_INDICES;
+use super::error::TextError;
+
 pub struct TextProgram {
 
-    gl: Rc<GL>,
+    backend: Rc<dyn TextBackend>,
 
-    program: WebGlProgram,
-    vertex_shader: WebGlShader,
-    fragment_shader: WebGlShader,
+    program: ProgramHandle,
 
     attrib_relative_position: i32,
     attrib_texture_coords: i32,
 
-    uniform_texture_sampler: WebGlUniformLocation,
+    instancing: Option<Instancing>,
+
+    /// The index buffer for the vertex data of TextModel's, or None if this program is instanced
+    quad_indices: Option<QuadIndices>,
+
+    uniform_texture_sampler: UniformHandle,
+
+    uniform_screen_position: UniformHandle,
+    uniform_scale: UniformHandle,
 
-    uniform_screen_position: WebGlUniformLocation,
-    uniform_scale: WebGlUniformLocation,
+    uniform_fill_color: UniformHandle,
+    uniform_stroke_color: UniformHandle,
+    uniform_background_color: UniformHandle,
 
-    uniform_fill_color: WebGlUniformLocation,
-    uniform_stroke_color: WebGlUniformLocation,
-    uniform_background_color: WebGlUniformLocation,
+    uniform_smoothing: Option<UniformHandle>,
+    uniform_stroke_width: Option<UniformHandle>,
+
+    /// Only programs for Font's with a bitmap atlas have this uniform
+    uniform_shadow_softness: Option<UniformHandle>,
 
     current_screen_position: (f32, f32),
     current_scale: (f32, f32),
 
     current_fill_color: Color,
     current_stroke_color: Color,
-    current_background_color: Color
+    current_background_color: Color,
+
+    current_smoothing: f32,
+    current_stroke_width: f32,
+    current_shadow_softness: f32
 }
 
-impl TextProgram {
+/// The vertex attributes of a TextProgram that draws every character as an instance of the same quad
+struct Instancing {
 
-    pub fn create_instance(gl: Rc<GL>) -> TextProgram {
+    /// The corner of the quad, which goes from (0, 0) to (1, 1)
+    attrib_corner: i32,
 
-        let vertex_shader = gl.create_shader(GL::VERTEX_SHADER).expect("Couldn't create vertex shader");
-        gl.shader_source(&vertex_shader, VERTEX_SOURCE);
-        gl.compile_shader(&vertex_shader);
-        if !gl.get_shader_parameter(&vertex_shader, GL::COMPILE_STATUS).as_bool().expect("Compile status of vertex shader was not a bool") {
-            panic!("Couldn't compile vertex text shader {}", gl.get_shader_info_log(&vertex_shader).expect("Couldn't get shader info log of vertex shader"));
-        }
+    /// The position (min_x, min_y, max_x, max_y) of the character in model space
+    attrib_glyph_rect: i32,
 
-        let fragment_shader = gl.create_shader(GL::FRAGMENT_SHADER).expect("Couldn't create fragment shader");
-        gl.shader_source(&fragment_shader, FRAGMENT_SOURCE);
-        gl.compile_shader(&fragment_shader);
-        if !gl.get_shader_parameter(&fragment_shader, GL::COMPILE_STATUS).as_bool().expect("Compile status of fragmetn shader was not a bool") {
-            panic!("Couldn't compile fragment text shader {}", gl.get_shader_info_log(&fragment_shader).expect("Couldn't get shader info log of fragment shader"));
-        }
+    /// The texture coordinates (left_u, bottom_v, right_u, top_v) of the character
+    attrib_texture_rect: i32,
 
-        let program = gl.create_program().expect("Couldn't create shader program");
+    /// The corners of the 2 triangles of the quad, which are shared by all instances
+    corner_buffer: BufferHandle
+}
 
-        gl.attach_shader(&program, &vertex_shader);
-        gl.attach_shader(&program, &fragment_shader);
-        gl.link_program(&program);
+/// The number of floats of a single instance of an instanced TextProgram
+pub(super) const FLOATS_PER_INSTANCE: usize = 8;
 
-        if !gl.get_program_parameter(&program, GL::LINK_STATUS).as_bool().expect("Couldn't get link status of text shader program") {
-            panic!("Couldn't link the text shader program {}", gl.get_program_info_log(&program).expect("Couldn't get program info log of text shader program"));
-        }
+/// An index buffer that contains the QUAD_INDICES of the first quad_count quads of the vertex data of a TextLayout
+struct QuadIndices {
 
-        let attrib_relative_position = gl.get_attrib_location(&program, "relativePosition");
-        let attrib_texture_coords = gl.get_attrib_location(&program, "textureCoords");
+    buffer: BufferHandle,
+    quad_count: Cell<usize>
+}
 
-        let uniform_texture_sampler = gl.get_uniform_location(&program, "textureSampler").expect("Couldn't get textureSampler uniform location");
+/// The maximum number of quads that can be drawn with a single indexed draw call. The indices are u16's, so they can only
+/// refer to the first 65536 vertices.
+pub(super) const MAX_QUADS_PER_DRAW: usize = (u16::MAX as usize + 1) / 4;
 
-        let uniform_screen_position = gl.get_uniform_location(&program, "screenPosition").expect("Couldn't get screenPosition uniform location");
-        let uniform_scale = gl.get_uniform_location(&program, "scale").expect("Couldn't get scale uniform location");
+impl TextProgram {
+
+    /// Creates the normal program for Font's with a bitmap atlas. If the backend supports instancing, the program will draw
+    /// every character as an instance, see is_instanced. Unlike the distance field program, this program has a uniform
+    /// variable for the softness of shadows.
+    pub fn create_instance(backend: Rc<dyn TextBackend>) -> Result<TextProgram, TextError> {
+        Self::create_with_source(backend, FRAGMENT_SOURCE, false)
+    }
+
+    /// Creates the program for Font's with a signed distance field atlas. Unlike the normal program, this program has
+    /// uniform variables for the smoothing and the stroke width.
+    pub fn create_distance_field_instance(backend: Rc<dyn TextBackend>) -> Result<TextProgram, TextError> {
+        Self::create_with_source(backend, DISTANCE_FIELD_FRAGMENT_SOURCE, true)
+    }
 
-        let uniform_fill_color = gl.get_uniform_location(&program, "fillColor").expect("Couldn't get fillColor uniform location");
-        let uniform_stroke_color = gl.get_uniform_location(&program, "strokeColor").expect("Couldn't get strokeColor uniform lcoation");
-        let uniform_background_color = gl.get_uniform_location(&program, "backgroundColor").expect("Couldn't get backgroundColor uniform location");
+    fn create_with_source(backend: Rc<dyn TextBackend>, fragment_source: &str, distance_field: bool) -> Result<TextProgram, TextError> {
 
-        TextProgram {
-            gl,
+        let vertex_source = if backend.supports_instancing() { INSTANCED_VERTEX_SOURCE } else { VERTEX_SOURCE };
+        let program = backend.create_program(vertex_source, fragment_source)?;
+
+        // Don't leak the program if one of its uniforms is missing
+        Self::create_with_program(Rc::clone(&backend), program, distance_field).inspect_err(|_| backend.delete_program(program))
+    }
+
+    fn create_with_program(backend: Rc<dyn TextBackend>, program: ProgramHandle, distance_field: bool) -> Result<TextProgram, TextError> {
+
+        let instanced = backend.supports_instancing();
+        let (attrib_relative_position, attrib_texture_coords) = match instanced {
+            true => (-1, -1),
+            false => (backend.get_attrib_location(program, "relativePosition"), backend.get_attrib_location(program, "textureCoords"))
+        };
+
+        let uniform_texture_sampler = backend.get_uniform_location(program, "textureSampler")?;
+
+        let uniform_screen_position = backend.get_uniform_location(program, "screenPosition")?;
+        let uniform_scale = backend.get_uniform_location(program, "scale")?;
+
+        let uniform_fill_color = backend.get_uniform_location(program, "fillColor")?;
+        let uniform_stroke_color = backend.get_uniform_location(program, "strokeColor")?;
+        let uniform_background_color = backend.get_uniform_location(program, "backgroundColor")?;
+
+        let uniform_smoothing;
+        let uniform_stroke_width;
+        let uniform_shadow_softness;
+        if distance_field {
+            uniform_smoothing = Some(backend.get_uniform_location(program, "smoothing")?);
+            uniform_stroke_width = Some(backend.get_uniform_location(program, "strokeWidth")?);
+            uniform_shadow_softness = None;
+        } else {
+            uniform_smoothing = None;
+            uniform_stroke_width = None;
+            uniform_shadow_softness = Some(backend.get_uniform_location(program, "shadowSoftness")?);
+        }
+
+        // The corner buffer or index buffer is created last, so that it won't be leaked when one of the uniforms is missing
+        let (instancing, quad_indices) = match instanced {
+            true => {
+                let corner_buffer = backend.create_buffer()?;
+                backend.upload_buffer(corner_buffer, &float_bytes(&[0.0,0.0, 1.0,0.0, 1.0,1.0, 1.0,1.0, 0.0,1.0, 0.0,0.0]));
+                (Some(Instancing {
+                    attrib_corner: backend.get_attrib_location(program, "corner"),
+                    attrib_glyph_rect: backend.get_attrib_location(program, "glyphRect"),
+                    attrib_texture_rect: backend.get_attrib_location(program, "textureRect"),
+                    corner_buffer
+                }), None)
+            }, false => (None, Some(QuadIndices::new(backend.as_ref())?))
+        };
+
+        Ok(TextProgram {
+            backend,
 
             program,
-            vertex_shader,
-            fragment_shader,
 
             attrib_relative_position,
             attrib_texture_coords,
 
+            instancing,
+            quad_indices,
+
             uniform_texture_sampler,
 
             uniform_screen_position,
@@ -129,62 +383,105 @@ impl TextProgram {
             uniform_stroke_color,
             uniform_background_color,
 
+            uniform_smoothing,
+            uniform_stroke_width,
+
+            uniform_shadow_softness,
+
             current_screen_position: (0.0, 0.0),
             current_scale: (0.0, 0.0),
 
             current_fill_color: Color::from_rgba(0, 0, 0, 0),
             current_stroke_color: Color::from_rgba(0, 0, 0, 0),
-            current_background_color: Color::from_rgba(0, 0, 0, 0)
-        }
+            current_background_color: Color::from_rgba(0, 0, 0, 0),
+
+            current_smoothing: 0.0,
+            current_stroke_width: 0.0,
+            current_shadow_softness: 0.0
+        })
     }
 
     pub fn use_program(&self){
-        self.gl.use_program(Some(&self.program));
+        self.backend.use_program(self.program);
     }
 
     pub fn set_texture_sampler(&self, texture_unit: i32){
-        self.gl.uniform1i(Some(&self.uniform_texture_sampler), texture_unit);
+        self.backend.set_uniform_1i(self.uniform_texture_sampler, texture_unit);
     }
 
     pub fn set_screen_position(&mut self, x: f32, y: f32){
         if self.current_screen_position != (x, y){
-            self.gl.uniform2f(Some(&self.uniform_screen_position), x, y);
+            self.backend.set_uniform_2f(self.uniform_screen_position, x, y);
             self.current_screen_position = (x, y);
         }
     }
 
     pub fn set_scale(&mut self, x: f32, y: f32){
         if self.current_scale != (x, y){
-            self.gl.uniform2f(Some(&self.uniform_scale), x, y);
+            self.backend.set_uniform_2f(self.uniform_scale, x, y);
             self.current_scale = (x, y);
         }
     }
 
-    fn set_color(&self, uniform: &WebGlUniformLocation, color: Color){
-        self.gl.uniform4f(Some(uniform), color.get_red_float(), color.get_green_float(), color.get_blue_float(), color.get_alpha_float());
+    fn set_color(&self, uniform: UniformHandle, color: Color){
+        let [red, green, blue, alpha] = get_color_components(color);
+        self.backend.set_uniform_4f(uniform, red, green, blue, alpha);
     }
 
     pub fn set_background_color(&mut self, background: Color){
         if self.current_background_color != background {
-            self.set_color(&self.uniform_background_color, background);
+            self.set_color(self.uniform_background_color, background);
             self.current_background_color = background;
         }
     }
 
     pub fn set_fill_color(&mut self, fill: Color){
         if self.current_fill_color != fill {
-            self.set_color(&self.uniform_fill_color, fill);
+            self.set_color(self.uniform_fill_color, fill);
             self.current_fill_color = fill;
         }
     }
 
     pub fn set_stroke_color(&mut self, stroke: Color){
         if self.current_stroke_color != stroke {
-            self.set_color(&self.uniform_stroke_color, stroke);
+            self.set_color(self.uniform_stroke_color, stroke);
             self.current_stroke_color = stroke;
         }
     }
 
+    /// Sets the smoothing of the edges of distance field characters. This method doesn't do anything if this is not
+    /// a distance field program.
+    pub fn set_smoothing(&mut self, smoothing: f32){
+        if let Some(uniform) = self.uniform_smoothing {
+            if self.current_smoothing != smoothing {
+                self.backend.set_uniform_1f(uniform, smoothing);
+                self.current_smoothing = smoothing;
+            }
+        }
+    }
+
+    /// Sets the stroke width of distance field characters. This method doesn't do anything if this is not a distance
+    /// field program.
+    pub fn set_stroke_width(&mut self, stroke_width: f32){
+        if let Some(uniform) = self.uniform_stroke_width {
+            if self.current_stroke_width != stroke_width {
+                self.backend.set_uniform_1f(uniform, stroke_width);
+                self.current_stroke_width = stroke_width;
+            }
+        }
+    }
+
+    /// Sets the softness of shadows of bitmap characters, which should be 0.0 for anything but soft shadows. This method
+    /// doesn't do anything if this is a distance field program.
+    pub fn set_shadow_softness(&mut self, softness: f32){
+        if let Some(uniform) = self.uniform_shadow_softness {
+            if self.current_shadow_softness != softness {
+                self.backend.set_uniform_1f(uniform, softness);
+                self.current_shadow_softness = softness;
+            }
+        }
+    }
+
     pub fn get_relative_position(&self) -> i32 {
         self.attrib_relative_position
     }
@@ -192,13 +489,417 @@ impl TextProgram {
     pub fn get_texture_coords(&self) -> i32 {
         self.attrib_texture_coords
     }
+
+    /// Checks whether this program draws every character as an instance of the same quad. Instanced programs read the
+    /// position and texture coordinates of every character from 1 instance of FLOATS_PER_INSTANCE floats, rather than from
+    /// 6 vertices. Their vertex attributes must be specified with an InstanceArray.
+    pub fn is_instanced(&self) -> bool {
+        self.instancing.is_some()
+    }
+
+    /// Binds the index buffer of this (non-instanced) program, and makes sure it contains the indices of at least quad_count
+    /// quads. The quad_count must not be larger than MAX_QUADS_PER_DRAW.
+    pub fn bind_quad_indices(&self, quad_count: usize){
+        let quad_indices = self.quad_indices.as_ref().expect("Only programs without instancing have quad indices");
+        quad_indices.bind(self.backend.as_ref(), quad_count);
+    }
+
+    /// Lets the vertex attributes of this (instanced) program read their instances from the given buffer, starting at the
+    /// given offset (in bytes). This will affect the currently bound vertex array object.
+    fn bind_instances(&self, buffer: BufferHandle, offset: i32){
+        let instancing = self.instancing.as_ref().expect("Only instanced programs can bind instances");
+        let backend = &self.backend;
+        let f32_size = 4;
+        let stride = f32_size * FLOATS_PER_INSTANCE as i32;
+
+        backend.set_vertex_attribute(instancing.corner_buffer, instancing.attrib_corner, 2, AttributeType::Float, 0, 0);
+        backend.set_attribute_divisor(instancing.attrib_corner, 0);
+        backend.set_vertex_attribute(buffer, instancing.attrib_glyph_rect, 4, AttributeType::Float, stride, offset);
+        backend.set_attribute_divisor(instancing.attrib_glyph_rect, 1);
+        backend.set_vertex_attribute(buffer, instancing.attrib_texture_rect, 4, AttributeType::Float, stride, offset + 4 * f32_size);
+        backend.set_attribute_divisor(instancing.attrib_texture_rect, 1);
+    }
+}
+
+impl QuadIndices {
+
+    fn new(backend: &dyn TextBackend) -> Result<QuadIndices, TextError> {
+        Ok(QuadIndices {
+            buffer: backend.create_buffer()?,
+            quad_count: Cell::new(0)
+        })
+    }
+
+    /// Binds this index buffer, and makes sure it contains the indices of at least quad_count quads. The quad_count must not
+    /// be larger than MAX_QUADS_PER_DRAW.
+    fn bind(&self, backend: &dyn TextBackend, quad_count: usize){
+        let current_quad_count = self.quad_count.get();
+        if quad_count > current_quad_count {
+
+            // Grow quickly to avoid frequent uploads, but don't exceed the range of the u16 indices
+            let new_quad_count = quad_count.max(2 * current_quad_count).min(MAX_QUADS_PER_DRAW);
+            let mut indices = Vec::with_capacity(6 * new_quad_count);
+            for quad in 0..new_quad_count {
+                let first_vertex = (4 * quad) as u16;
+                indices.extend(QUAD_INDICES.iter().map(|index| first_vertex + index));
+            }
+            backend.upload_index_buffer(self.buffer, &indices);
+            self.quad_count.set(new_quad_count);
+        } else {
+            backend.bind_index_buffer(self.buffer);
+        }
+    }
 }
 
 impl Drop for TextProgram {
 
     fn drop(&mut self){
-        self.gl.delete_program(Some(&self.program));
-        self.gl.delete_shader(Some(&self.vertex_shader));
-        self.gl.delete_shader(Some(&self.fragment_shader));
+        if let Some(instancing) = &self.instancing {
+            self.backend.delete_buffer(instancing.corner_buffer);
+        }
+        if let Some(quad_indices) = &self.quad_indices {
+            self.backend.delete_buffer(quad_indices.buffer);
+        }
+        self.backend.delete_program(self.program);
+    }
+}
+
+/// A vertex array object that lets an instanced TextProgram read its instances from a buffer. The vertex attributes will
+/// only be specified again when the program, the buffer or the offset of the first instance changes, so drawing the same
+/// instances again only needs to bind the vertex array object.
+pub(super) struct InstanceArray {
+
+    backend: Rc<dyn TextBackend>,
+
+    vertex_array: VertexArrayHandle,
+
+    /// The program, buffer and offset the vertex attributes were last specified for
+    bound_instances: Cell<Option<(ProgramHandle, BufferHandle, i32)>>
+}
+
+impl InstanceArray {
+
+    pub fn new(backend: Rc<dyn TextBackend>) -> Result<InstanceArray, TextError> {
+        let vertex_array = backend.create_vertex_array()?;
+        Ok(InstanceArray {
+            backend,
+            vertex_array,
+            bound_instances: Cell::new(None)
+        })
+    }
+
+    /// Binds this vertex array object and makes sure the given program will read its instances from the given buffer,
+    /// starting at the given offset (in bytes). The default vertex array object should be bound again after drawing, so
+    /// that other code won't change this vertex array object by accident.
+    pub fn bind(&self, program: &TextProgram, buffer: BufferHandle, offset: i32){
+        self.backend.bind_vertex_array(Some(self.vertex_array));
+        let instances = Some((program.program, buffer, offset));
+        if self.bound_instances.get() != instances {
+            program.bind_instances(buffer, offset);
+            self.bound_instances.set(instances);
+        }
+    }
+}
+
+impl Drop for InstanceArray {
+
+    fn drop(&mut self){
+        self.backend.delete_vertex_array(self.vertex_array);
+    }
+}
+
+/// The shader program that is used by TextBatch'es. Unlike the TextProgram, all colors (and the distance field parameters)
+/// of this program are vertex attributes rather than uniform variables, and the positions are already in the OpenGL
+/// coordinate space. This allows many strings with different positions and colors to be drawn with a single draw call.
+/// 
+/// The vertices are interleaved: every vertex consists of its position (2 floats), texture coordinates (2 floats), fill
+/// color (4 floats), stroke color (4 floats), background color (4 floats) and, for distance field programs only, the
+/// smoothing and stroke width (2 floats).
+pub struct BatchProgram {
+
+    backend: Rc<dyn TextBackend>,
+
+    program: ProgramHandle,
+
+    attrib_position: i32,
+    attrib_texture_coords: i32,
+    attrib_fill_color: i32,
+    attrib_stroke_color: i32,
+    attrib_background_color: i32,
+    attrib_distance_params: Option<i32>,
+
+    uniform_texture_sampler: UniformHandle
+}
+
+impl BatchProgram {
+
+    pub fn create_instance(backend: Rc<dyn TextBackend>) -> Result<BatchProgram, TextError> {
+        Self::create_with_source(backend, BATCH_VERTEX_SOURCE, BATCH_FRAGMENT_SOURCE, false)
+    }
+
+    /// Creates the batch program for Font's with a signed distance field atlas
+    pub fn create_distance_field_instance(backend: Rc<dyn TextBackend>) -> Result<BatchProgram, TextError> {
+        Self::create_with_source(backend, BATCH_DISTANCE_FIELD_VERTEX_SOURCE, BATCH_DISTANCE_FIELD_FRAGMENT_SOURCE, true)
     }
-}
\ No newline at end of file
+
+    fn create_with_source(backend: Rc<dyn TextBackend>, vertex_source: &str, fragment_source: &str, distance_field: bool) -> Result<BatchProgram, TextError> {
+        let program = backend.create_program(vertex_source, fragment_source)?;
+
+        // Don't leak the program if its uniform is missing
+        let uniform_texture_sampler = backend.get_uniform_location(program, "textureSampler").inspect_err(|_| backend.delete_program(program))?;
+
+        Ok(BatchProgram {
+            attrib_position: backend.get_attrib_location(program, "position"),
+            attrib_texture_coords: backend.get_attrib_location(program, "textureCoords"),
+            attrib_fill_color: backend.get_attrib_location(program, "fillColor"),
+            attrib_stroke_color: backend.get_attrib_location(program, "strokeColor"),
+            attrib_background_color: backend.get_attrib_location(program, "backgroundColor"),
+            attrib_distance_params: if distance_field { Some(backend.get_attrib_location(program, "distanceParams")) } else { None },
+
+            uniform_texture_sampler,
+
+            backend,
+            program
+        })
+    }
+
+    pub fn use_program(&self){
+        self.backend.use_program(self.program);
+    }
+
+    pub fn set_texture_sampler(&self, texture_unit: i32){
+        self.backend.set_uniform_1i(self.uniform_texture_sampler, texture_unit);
+    }
+
+    /// Gets the number of floats of a single vertex of this program
+    pub fn get_floats_per_vertex(&self) -> usize {
+        match self.attrib_distance_params {
+            Some(_) => 18,
+            None => 16
+        }
+    }
+
+    /// Gets the locations and number of components of all vertex attributes of this program, in the order in which they are
+    /// stored in a vertex
+    fn get_attributes(&self) -> Vec<(i32, i32)> {
+        let mut attributes = vec![
+            (self.attrib_position, 2),
+            (self.attrib_texture_coords, 2),
+            (self.attrib_fill_color, 4),
+            (self.attrib_stroke_color, 4),
+            (self.attrib_background_color, 4)
+        ];
+        if let Some(attrib_distance_params) = self.attrib_distance_params {
+            attributes.push((attrib_distance_params, 2));
+        }
+        attributes
+    }
+
+    /// Lets all vertex attributes of this program read their values from the given (interleaved) buffer
+    pub fn bind(&self, buffer: BufferHandle){
+        bind_interleaved_attributes(self.backend.as_ref(), buffer, &self.get_attributes(), 0);
+    }
+
+    /// Disables the vertex attributes of this program, so that they won't bother the draw calls of other programs
+    pub fn unbind(&self){
+        disable_attributes(self.backend.as_ref(), &self.get_attributes());
+    }
+}
+
+impl Drop for BatchProgram {
+
+    fn drop(&mut self){
+        self.backend.delete_program(self.program);
+    }
+}
+
+/// The shader program that is used by RichTextModel's. Like the BatchProgram, the colors of this program are vertex
+/// attributes, so that every span of a RichTextModel can have its own colors. Like the TextProgram, the positions are in
+/// model space and the screen position, scale and distance field parameters are uniform variables, so that the buffer
+/// of a RichTextModel can be rendered anywhere.
+///
+/// The vertices are interleaved: every vertex consists of its position (2 floats), texture coordinates (2 floats), fill
+/// color (4 floats), stroke color (4 floats) and background color (4 floats). Every quad consists of 4 vertices, which
+/// are drawn with the indices of QUAD_INDICES.
+pub struct RichTextProgram {
+
+    backend: Rc<dyn TextBackend>,
+
+    program: ProgramHandle,
+
+    attrib_relative_position: i32,
+    attrib_texture_coords: i32,
+    attrib_fill_color: i32,
+    attrib_stroke_color: i32,
+    attrib_background_color: i32,
+
+    quad_indices: QuadIndices,
+
+    uniform_texture_sampler: UniformHandle,
+
+    uniform_screen_position: UniformHandle,
+    uniform_scale: UniformHandle,
+
+    uniform_smoothing: Option<UniformHandle>,
+    uniform_stroke_width: Option<UniformHandle>
+}
+
+/// The number of floats of a single vertex of a RichTextProgram
+pub(super) const FLOATS_PER_RICH_VERTEX: usize = 16;
+
+impl RichTextProgram {
+
+    pub fn create_instance(backend: Rc<dyn TextBackend>) -> Result<RichTextProgram, TextError> {
+        Self::create_with_source(backend, BATCH_FRAGMENT_SOURCE, false)
+    }
+
+    /// Creates the rich text program for Font's with a signed distance field atlas
+    pub fn create_distance_field_instance(backend: Rc<dyn TextBackend>) -> Result<RichTextProgram, TextError> {
+        Self::create_with_source(backend, RICH_DISTANCE_FIELD_FRAGMENT_SOURCE, true)
+    }
+
+    fn create_with_source(backend: Rc<dyn TextBackend>, fragment_source: &str, distance_field: bool) -> Result<RichTextProgram, TextError> {
+        let program = backend.create_program(RICH_VERTEX_SOURCE, fragment_source)?;
+
+        // Don't leak the program if one of its uniforms is missing
+        Self::create_with_program(Rc::clone(&backend), program, distance_field).inspect_err(|_| backend.delete_program(program))
+    }
+
+    fn create_with_program(backend: Rc<dyn TextBackend>, program: ProgramHandle, distance_field: bool) -> Result<RichTextProgram, TextError> {
+        let uniform_texture_sampler = backend.get_uniform_location(program, "textureSampler")?;
+
+        let uniform_screen_position = backend.get_uniform_location(program, "screenPosition")?;
+        let uniform_scale = backend.get_uniform_location(program, "scale")?;
+
+        let uniform_smoothing;
+        let uniform_stroke_width;
+        if distance_field {
+            uniform_smoothing = Some(backend.get_uniform_location(program, "smoothing")?);
+            uniform_stroke_width = Some(backend.get_uniform_location(program, "strokeWidth")?);
+        } else {
+            uniform_smoothing = None;
+            uniform_stroke_width = None;
+        }
+
+        // The index buffer is created last, so that it won't be leaked when one of the uniforms is missing
+        let quad_indices = QuadIndices::new(backend.as_ref())?;
+
+        Ok(RichTextProgram {
+            attrib_relative_position: backend.get_attrib_location(program, "relativePosition"),
+            attrib_texture_coords: backend.get_attrib_location(program, "textureCoords"),
+            attrib_fill_color: backend.get_attrib_location(program, "fillColor"),
+            attrib_stroke_color: backend.get_attrib_location(program, "strokeColor"),
+            attrib_background_color: backend.get_attrib_location(program, "backgroundColor"),
+
+            quad_indices,
+
+            uniform_texture_sampler,
+
+            uniform_screen_position,
+            uniform_scale,
+
+            uniform_smoothing,
+            uniform_stroke_width,
+
+            backend,
+            program
+        })
+    }
+
+    pub fn use_program(&self){
+        self.backend.use_program(self.program);
+    }
+
+    pub fn set_texture_sampler(&self, texture_unit: i32){
+        self.backend.set_uniform_1i(self.uniform_texture_sampler, texture_unit);
+    }
+
+    pub fn set_screen_position(&self, x: f32, y: f32){
+        self.backend.set_uniform_2f(self.uniform_screen_position, x, y);
+    }
+
+    pub fn set_scale(&self, x: f32, y: f32){
+        self.backend.set_uniform_2f(self.uniform_scale, x, y);
+    }
+
+    /// Sets the smoothing of the edges of distance field characters. This method doesn't do anything if this is not
+    /// a distance field program.
+    pub fn set_smoothing(&self, smoothing: f32){
+        if let Some(uniform) = self.uniform_smoothing {
+            self.backend.set_uniform_1f(uniform, smoothing);
+        }
+    }
+
+    /// Sets the stroke width of distance field characters. This method doesn't do anything if this is not a distance
+    /// field program.
+    pub fn set_stroke_width(&self, stroke_width: f32){
+        if let Some(uniform) = self.uniform_stroke_width {
+            self.backend.set_uniform_1f(uniform, stroke_width);
+        }
+    }
+
+    /// Gets the locations and number of components of all vertex attributes of this program, in the order in which they are
+    /// stored in a vertex
+    fn get_attributes(&self) -> [(i32, i32); 5] {
+        [
+            (self.attrib_relative_position, 2),
+            (self.attrib_texture_coords, 2),
+            (self.attrib_fill_color, 4),
+            (self.attrib_stroke_color, 4),
+            (self.attrib_background_color, 4)
+        ]
+    }
+
+    /// Lets all vertex attributes of this program read the vertices of quad_count quads from the given (interleaved) buffer,
+    /// starting at the given offset (in bytes), and binds the index buffer for those quads. The quad_count must not be
+    /// larger than MAX_QUADS_PER_DRAW.
+    pub fn bind(&self, buffer: BufferHandle, offset: i32, quad_count: usize){
+        bind_interleaved_attributes(self.backend.as_ref(), buffer, &self.get_attributes(), offset);
+        self.quad_indices.bind(self.backend.as_ref(), quad_count);
+    }
+
+    /// Disables the vertex attributes of this program, so that they won't bother the draw calls of other programs
+    pub fn unbind(&self){
+        disable_attributes(self.backend.as_ref(), &self.get_attributes());
+    }
+}
+
+impl Drop for RichTextProgram {
+
+    fn drop(&mut self){
+        self.backend.delete_buffer(self.quad_indices.buffer);
+        self.backend.delete_program(self.program);
+    }
+}
+
+/// Gets the red, green, blue and alpha components of the given color as floats between 0.0 and 1.0, in the order in which
+/// the shaders expect them
+pub(super) fn get_color_components(color: Color) -> [f32; 4] {
+    [color.get_red_float(), color.get_green_float(), color.get_blue_float(), color.get_alpha_float()]
+}
+
+/// Lets the given vertex attributes read their values from the given buffer, whose vertices consist of the float components
+/// of all attributes, in the given order. The attributes are given as (location, number of components) pairs, and the
+/// first vertex starts at the given offset (in bytes).
+fn bind_interleaved_attributes(backend: &dyn TextBackend, buffer: BufferHandle, attributes: &[(i32, i32)], offset: i32){
+    let f32_size = 4;
+    let stride = f32_size * attributes.iter().map(|(_location, num_components)| num_components).sum::<i32>();
+    let mut offset = offset;
+    for (location, num_components) in attributes {
+
+        // The compiler may have removed attributes that don't affect the result
+        if *location >= 0 {
+            backend.set_vertex_attribute(buffer, *location, *num_components, AttributeType::Float, stride, offset);
+        }
+        offset += f32_size * num_components;
+    }
+}
+
+/// Disables the given vertex attributes (see bind_interleaved_attributes)
+fn disable_attributes(backend: &dyn TextBackend, attributes: &[(i32, i32)]){
+    for (location, _num_components) in attributes {
+        if *location >= 0 {
+            backend.disable_vertex_attribute(*location);
+        }
+    }
+}
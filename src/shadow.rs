@@ -0,0 +1,187 @@
+use wasmuri_core::Color;
+
+use super::metrics::FontMetrics;
+use super::rasterizer::*;
+
+/// A drop shadow that can be given to a TextModel with its set_shadow method. The shadow is a copy of the characters in the
+/// shadow color, which is drawn behind the characters (but in front of the background) during the same render call.
+#[derive(PartialEq,Copy,Clone)]
+pub struct TextShadow {
+
+    /// The horizontal distance between the characters and their shadow, as a fraction of the line height. Positive values
+    /// move the shadow to the right.
+    pub offset_x: f32,
+
+    /// The vertical distance between the characters and their shadow, as a fraction of the line height. Positive values
+    /// move the shadow up, so drop shadows usually have a negative offset_y.
+    pub offset_y: f32,
+
+    /// The color of the entire shadow, including the shadow of the stroke of the characters
+    pub color: Color,
+
+    /// The softness determines how blurry the edges of the shadow are, from 0.0 (sharp) to 1.0 (the softest shadow the Font
+    /// supports). Font's with a distance field atlas can blur the shadow over the full spread of their distance field. Font's
+    /// with a bitmap atlas can only blur the shadow if the shadow_blur of the TextRenderer was larger than 0 when the Font
+    /// was added. Otherwise, their shadows will always be sharp.
+    pub softness: f32
+}
+
+impl TextShadow {
+
+    /// Creates a sharp TextShadow with the given offset and color
+    pub fn new(offset_x: f32, offset_y: f32, color: Color) -> TextShadow {
+        TextShadow {
+            offset_x,
+            offset_y,
+            color,
+            softness: 0.0
+        }
+    }
+
+    /// Creates a TextShadow with the given offset, color and softness
+    pub fn soft(offset_x: f32, offset_y: f32, color: Color, softness: f32) -> TextShadow {
+        TextShadow {
+            softness,
+            ..TextShadow::new(offset_x, offset_y, color)
+        }
+    }
+}
+
+/// The GlyphRasterizer for Font's with a bitmap atlas that support soft shadows. It wraps another rasterizer and stores a
+/// blurred copy of the coverage (fill plus stroke) of every character in the alpha channel of its glyphs. The glyphs get an
+/// empty margin of the blur radius, so that the blurred coverage won't be cut off.
+pub(super) struct ShadowRasterizer {
+
+    inner: Box<dyn GlyphRasterizer>,
+
+    /// The radius (in pixels) of the box blur
+    radius: u32
+}
+
+impl ShadowRasterizer {
+
+    pub fn new(inner: Box<dyn GlyphRasterizer>, radius: u32) -> ShadowRasterizer {
+        ShadowRasterizer {
+            inner,
+            radius
+        }
+    }
+}
+
+impl GlyphRasterizer for ShadowRasterizer {
+
+    fn get_line_height(&self) -> u32 {
+        self.inner.get_line_height()
+    }
+
+    fn get_baseline(&self) -> f32 {
+        self.inner.get_baseline()
+    }
+
+    fn get_font_metrics(&self) -> FontMetrics {
+        self.inner.get_font_metrics()
+    }
+
+    fn get_kerning(&self, left: char, right: char) -> f32 {
+        self.inner.get_kerning(left, right)
+    }
+
+    fn get_advance(&self, character: char) -> Option<f32> {
+        self.inner.get_advance(character)
+    }
+
+    fn get_empty_pixel(&self) -> [u8; 4] {
+        self.inner.get_empty_pixel()
+    }
+
+    fn rasterize(&self, character: char) -> Option<GlyphBitmap> {
+        let source = self.inner.rasterize(character)?;
+
+        let radius = self.radius;
+        let width = source.width + 2 * radius;
+        let height = source.height + 2 * radius;
+        let mut bitmap = GlyphBitmap::empty(width, height, source.offset_x - radius as i32, source.offset_y - radius as i32, source.metrics);
+        source.copy_to(&mut bitmap.pixels, width, radius, radius);
+
+        // The coverage is everything that is not empty space, so the fill and the stroke
+        let coverage: Vec<f32> = bitmap.pixels.chunks_exact(4).map(|pixel| 1.0 - pixel[0] as f32 / 255.0).collect();
+        let horizontal = box_blur(&coverage, width, height, radius, 1, width);
+        let blurred = box_blur(&horizontal, height, width, radius, width, 1);
+        for (pixel, value) in bitmap.pixels.chunks_exact_mut(4).zip(blurred) {
+            pixel[3] = (255.0 * value).round().clamp(0.0, 255.0) as u8;
+        }
+
+        Some(bitmap)
+    }
+}
+
+/// Blurs the given values with a box blur of the given radius along 1 axis. The values consist of line_count lines of
+/// line_length values, where step is the distance (in values) between neighbouring values of a line and line_step is the
+/// distance between the first values of neighbouring lines. Values outside the lines count as 0.
+fn box_blur(values: &[f32], line_length: u32, line_count: u32, radius: u32, step: u32, line_step: u32) -> Vec<f32> {
+    let mut result = vec![0.0; values.len()];
+    let radius = radius as i32;
+    let window_size = (2 * radius + 1) as f32;
+    for line in 0..line_count {
+        let index = |position: i32| (line * line_step + position as u32 * step) as usize;
+
+        // Slide a window over the line, adding the value that enters the window and subtracting the value that leaves it
+        let mut sum: f32 = (0..radius.min(line_length as i32)).map(|position| values[index(position)]).sum();
+        for position in 0..line_length as i32 {
+            let entering = position + radius;
+            if entering < line_length as i32 {
+                sum += values[index(entering)];
+            }
+            let leaving = position - radius - 1;
+            if leaving >= 0 {
+                sum -= values[index(leaving)];
+            }
+            result[index(position)] = sum / window_size;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use super::super::testing::*;
+    use super::super::ttf::TtfRasterizer;
+
+    #[test]
+    fn box_blur_spreads_values_over_the_window() {
+        assert_eq!(box_blur(&[0.0, 0.0, 3.0, 0.0, 0.0], 5, 1, 1, 1, 5), vec![0.0, 1.0, 1.0, 1.0, 0.0]);
+
+        // Values outside the line count as 0
+        assert_eq!(box_blur(&[3.0, 0.0, 6.0], 3, 1, 1, 1, 3), vec![1.0, 3.0, 2.0]);
+        assert_eq!(box_blur(&[5.0, 5.0], 2, 1, 3, 1, 2), vec![10.0 / 7.0, 10.0 / 7.0]);
+    }
+
+    #[test]
+    fn box_blur_only_blurs_along_its_axis() {
+
+        // 2 rows of 3 values
+        let values = [3.0, 0.0, 0.0, 0.0, 0.0, 6.0];
+        assert_eq!(box_blur(&values, 3, 2, 1, 1, 3), vec![1.0, 1.0, 0.0, 0.0, 2.0, 2.0]);
+
+        // The columns have 2 values, and the first value of the next column is right after the first value of a column
+        assert_eq!(box_blur(&values, 2, 3, 1, 3, 1), vec![1.0, 0.0, 2.0, 1.0, 0.0, 2.0]);
+    }
+
+    #[test]
+    fn shadow_glyphs_get_a_blurred_margin() {
+        let radius = 3;
+        let inner = TtfRasterizer::new(TEST_FONT_DATA.to_vec(), TEST_FONT_SIZE, 0.05).unwrap();
+        let source = inner.rasterize('H').unwrap();
+        let rasterizer = ShadowRasterizer::new(Box::new(inner), radius);
+        let bitmap = rasterizer.rasterize('H').unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (source.width + 2 * radius, source.height + 2 * radius));
+        assert_eq!((bitmap.offset_x, bitmap.offset_y), (source.offset_x - radius as i32, source.offset_y - radius as i32));
+
+        // The blurred coverage reaches into the empty space around the ink, but not into the outer margin
+        let alpha = |x: u32, y: u32| bitmap.pixels[4 * (y * bitmap.width + x) as usize + 3];
+        assert!((0..bitmap.height).all(|y| alpha(0, y) == 0 && alpha(bitmap.width - 1, y) == 0));
+        assert!(bitmap.pixels.chunks_exact(4).any(|pixel| pixel[..3] == EMPTY_PIXEL[..3] && pixel[3] > 0));
+    }
+}
@@ -0,0 +1,38 @@
+use wasmuri_core::{
+    Color,
+    TextColors
+};
+
+use std::rc::Rc;
+
+use super::*;
+
+/// A subset of DejaVu Sans that only contains the printable ASCII characters and é, which keeps the repository small. See
+/// testdata/DejaVu-LICENSE.txt for its license.
+pub(super) const TEST_FONT_DATA: &[u8] = include_bytes!("../testdata/DejaVuSans-Subset.ttf");
+
+/// The font size of the test fonts, which is much smaller than the default to keep the tests fast
+pub(super) const TEST_FONT_SIZE: usize = 32;
+
+/// Creates a RecordingBackend and a TextRenderer that renders with it. The TextRenderer uses TEST_FONT_SIZE.
+pub(super) fn create_test_renderer(supports_instancing: bool) -> (Rc<RecordingBackend>, TextRenderer) {
+    let backend = Rc::new(RecordingBackend::new());
+    backend.set_supports_instancing(supports_instancing);
+    let mut renderer = TextRenderer::from_backend(Rc::clone(&backend) as Rc<dyn TextBackend>);
+    renderer.font_size = TEST_FONT_SIZE;
+    (backend, renderer)
+}
+
+/// Adds the test font to the given TextRenderer, with the current settings of the TextRenderer
+pub(super) fn add_test_font(renderer: &mut TextRenderer) -> Rc<Font> {
+    renderer.add_ttf_font(FontDetails::from_str("", "px DejaVu Sans"), TEST_FONT_DATA.to_vec())
+}
+
+/// Creates TextColors with the given fill color, a black stroke and a transparent background
+pub(super) fn test_colors(fill_color: Color) -> TextColors {
+    TextColors {
+        fill_color,
+        stroke_color: Color::from_rgb(0, 0, 0),
+        background_color: Color::from_rgba(0, 0, 0, 0)
+    }
+}
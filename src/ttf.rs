@@ -0,0 +1,484 @@
+use ab_glyph::{
+    Font as AbFont,
+    FontVec,
+    InvalidFont,
+    OutlineCurve
+};
+
+use std::convert::TryInto;
+use std::ops::Range;
+
+use super::metrics::{
+    FontMetrics,
+    GlyphMetrics
+};
+use super::outline::Outline;
+use super::rasterizer::*;
+
+/// The GlyphRasterizer that rasterizes characters from the raw bytes of a TrueType or OpenType font file. This rasterizer
+/// doesn't need a DOM, so it also works in web workers and outside the browser. Its results are the same on every platform.
+pub(super) struct TtfRasterizer {
+
+    font: FontVec,
+
+    /// The number of pixels per font unit
+    scale: f32,
+    baseline: f32,
+    font_metrics: FontMetrics,
+
+    /// The pair adjustment subtables of the lookups of the kern feature in the GPOS table, as offsets in the font data. If
+    /// the font doesn't have such lookups, the kerning will be taken from the legacy kern table instead.
+    kerning_lookups: Vec<Vec<usize>>,
+
+    line_width: f32,
+    line_margin: u32,
+    line_height: u32
+}
+
+impl TtfRasterizer {
+
+    /// Creates a TtfRasterizer for the given font file. The font_size will be the size of an em in pixels (just like
+    /// a canvas font with that size in px). The line_width has the same meaning as the line_width of the TextRenderer.
+    pub fn new(font_data: Vec<u8>, font_size: usize, line_width: f64) -> Result<TtfRasterizer, InvalidFont> {
+
+        let font = FontVec::try_from_vec(font_data)?;
+        let units_per_em = font.units_per_em().ok_or(InvalidFont)?;
+        let scale = font_size as f32 / units_per_em;
+
+        let baseline = font.ascent_unscaled() * scale;
+        let line_height = (font.height_unscaled() * scale).ceil() as u32;
+
+        let mut font_metrics = FontMetrics::new(font.ascent_unscaled() * scale, -font.descent_unscaled() * scale, font.line_gap_unscaled() * scale);
+        if let Some((position, thickness)) = read_underline_metrics(font.font_data()) {
+            font_metrics = font_metrics.with_underline(-position as f32 * scale, thickness as f32 * scale);
+        }
+        if let Some((position, thickness)) = read_strikethrough_metrics(font.font_data()) {
+            font_metrics = font_metrics.with_strikethrough(position as f32 * scale, thickness as f32 * scale);
+        }
+
+        let kerning_lookups = find_kerning_lookups(font.font_data()).unwrap_or_default();
+
+        Ok(TtfRasterizer {
+            font,
+            kerning_lookups,
+
+            scale,
+            baseline,
+            font_metrics,
+
+            line_width: (line_width * font_size as f64) as f32,
+            line_margin: (2.0 * line_width * font_size as f64).ceil() as u32,
+            line_height
+        })
+    }
+
+    /// Converts the given curves (in font units) to an Outline in the pixel space of a GlyphBitmap with the given offset
+    fn create_outline(&self, curves: &[OutlineCurve], offset_x: i32, offset_y: i32) -> Outline {
+        let mut outline = Outline::new();
+
+        // Font units have their y-axis pointing upwards, but the rows of the bitmap go downwards
+        let scale = self.scale;
+        let baseline = self.baseline;
+        let x = |value: f32| value * scale - offset_x as f32;
+        let y = |value: f32| baseline - value * scale - offset_y as f32;
+
+        for curve in curves {
+            match curve {
+                OutlineCurve::Line(p0, p1) => outline.add_line(x(p0.x), y(p0.y), x(p1.x), y(p1.y)),
+                OutlineCurve::Quad(p0, p1, p2) => outline.add_quad(x(p0.x), y(p0.y), x(p1.x), y(p1.y), x(p2.x), y(p2.y)),
+                OutlineCurve::Cubic(p0, p1, p2, p3) => outline.add_cubic(x(p0.x), y(p0.y), x(p1.x), y(p1.y), x(p2.x), y(p2.y), x(p3.x), y(p3.y))
+            };
+        }
+
+        outline
+    }
+}
+
+impl GlyphRasterizer for TtfRasterizer {
+
+    fn get_line_height(&self) -> u32 {
+        self.line_height
+    }
+
+    fn get_baseline(&self) -> f32 {
+        self.baseline
+    }
+
+    fn rasterize(&self, character: char) -> Option<GlyphBitmap> {
+        let glyph_id = self.font.glyph_id(character);
+
+        // Glyph 0 is the 'missing character' glyph
+        if glyph_id.0 == 0 {
+            return None;
+        }
+
+        let advance = self.font.h_advance_unscaled(glyph_id) * self.scale;
+
+        // Characters like spaces don't have an outline
+        let maybe_outline = self.font.outline(glyph_id);
+        let metrics = match &maybe_outline {
+            Some(font_outline) => {
+
+                // The min.y of the bounds of ab_glyph is the top of the glyph, and max.y is the bottom
+                let bounds = font_outline.bounds;
+                GlyphMetrics::new(advance, bounds.min.x * self.scale, advance - bounds.max.x * self.scale, bounds.min.y * self.scale, -bounds.max.y * self.scale)
+            }, None => GlyphMetrics::new(advance, 0.0, advance, 0.0, 0.0)
+        };
+
+        // Leave room for the stroke and the anti-aliasing around the ink
+        let (offset_x, offset_y, width, height) = compute_ink_box(&metrics, self.baseline, self.line_margin + 1);
+        let mut bitmap = GlyphBitmap::empty(width, height, offset_x, offset_y, metrics);
+
+        if let Some(font_outline) = maybe_outline {
+            let outline = self.create_outline(&font_outline.curves, offset_x, offset_y);
+            let fill = outline.fill_coverage(width, height);
+
+            // The stroke is centered on the outline, just like the stroke of a canvas
+            let half_line_width = self.line_width / 2.0;
+            let distances = outline.distances(width, height, half_line_width + 1.0);
+
+            for y in 0..height {
+                for x in 0..width {
+                    let index = (x + y * width) as usize;
+                    let stroke = if self.line_width > 0.0 { half_line_width + 0.5 - distances[index] } else { 0.0 };
+                    bitmap.set_coverage(x, y, fill[index], stroke);
+                }
+            }
+        }
+
+        Some(bitmap)
+    }
+
+    fn get_font_metrics(&self) -> FontMetrics {
+        self.font_metrics
+    }
+
+    fn get_advance(&self, character: char) -> Option<f32> {
+        let glyph_id = self.font.glyph_id(character);
+        match glyph_id.0 {
+            0 => None,
+            _ => Some(self.font.h_advance_unscaled(glyph_id) * self.scale)
+        }
+    }
+
+    fn get_kerning(&self, left: char, right: char) -> f32 {
+        let left = self.font.glyph_id(left);
+        let right = self.font.glyph_id(right);
+        let kerning = match self.kerning_lookups.is_empty() {
+            true => self.font.kern_unscaled(left, right),
+            false => read_gpos_kerning(self.font.font_data(), &self.kerning_lookups, left.0, right.0) as f32
+        };
+        kerning * self.scale
+    }
+}
+
+// ab_glyph doesn't expose the underline and strikethrough metrics, but they are stored at fixed positions in the post and
+// OS/2 tables, so they can be read directly from the font data rather than parsing the entire font a second time.
+
+/// Reads the position and thickness (in font units) of the underline from the post table of the given font data. Returns
+/// None if the font doesn't specify them.
+fn read_underline_metrics(font_data: &[u8]) -> Option<(i16, i16)> {
+    let post = find_table(font_data, b"post")?;
+    let position = read_i16(post, 8)?;
+    let thickness = read_i16(post, 10)?;
+    if thickness > 0 { Some((position, thickness)) } else { None }
+}
+
+/// Reads the position and thickness (in font units) of the strikethrough line from the OS/2 table of the given font data.
+/// Returns None if the font doesn't specify them.
+fn read_strikethrough_metrics(font_data: &[u8]) -> Option<(i16, i16)> {
+    let os2 = find_table(font_data, b"OS/2")?;
+    let thickness = read_i16(os2, 26)?;
+    let position = read_i16(os2, 28)?;
+    if thickness > 0 { Some((position, thickness)) } else { None }
+}
+
+// Most modern fonts only store their kerning as pair adjustments in the GPOS table, but ab_glyph only supports the legacy
+// kern table. Only the pair adjustments that change the advance of the first character are needed for kerning.
+
+const PAIR_ADJUSTMENT_LOOKUP: u16 = 2;
+const EXTENSION_LOOKUP: u16 = 9;
+
+/// The bit of a value format that indicates that its value records contain a horizontal advance adjustment
+const X_ADVANCE: u16 = 0x0004;
+
+/// Finds the pair adjustment subtables of the lookups of the kern feature in the GPOS table of the given font data. The
+/// subtables are given as offsets in the font data, grouped per lookup in the order in which the lookups should be applied.
+/// Returns None if the font doesn't have a GPOS table or if it is malformed.
+fn find_kerning_lookups(font_data: &[u8]) -> Option<Vec<Vec<usize>>> {
+    let gpos = find_table_range(font_data, b"GPOS")?.start;
+    let feature_list = gpos + read_u16(font_data, gpos + 6)? as usize;
+    let lookup_list = gpos + read_u16(font_data, gpos + 8)? as usize;
+
+    // Every script can have its own kern feature, but they usually share most of their lookups
+    let mut lookup_indices = Vec::new();
+    let feature_count = read_u16(font_data, feature_list)? as usize;
+    for index in 0..feature_count {
+        let record = feature_list + 2 + 6 * index;
+        if font_data.get(record..record + 4)? != b"kern" {
+            continue;
+        }
+        let feature = feature_list + read_u16(font_data, record + 4)? as usize;
+        let lookup_count = read_u16(font_data, feature + 2)? as usize;
+        for lookup in 0..lookup_count {
+            lookup_indices.push(read_u16(font_data, feature + 4 + 2 * lookup)? as usize);
+        }
+    }
+    lookup_indices.sort_unstable();
+    lookup_indices.dedup();
+
+    lookup_indices.into_iter().map(|lookup_index| {
+        let lookup = lookup_list + read_u16(font_data, lookup_list + 2 + 2 * lookup_index)? as usize;
+        let lookup_type = read_u16(font_data, lookup)?;
+        let subtable_count = read_u16(font_data, lookup + 4)? as usize;
+        let mut subtables = Vec::with_capacity(subtable_count);
+        for index in 0..subtable_count {
+            let subtable = lookup + read_u16(font_data, lookup + 6 + 2 * index)? as usize;
+
+            // Extension subtables point to the actual subtable with a 32-bit offset, which is needed in large fonts
+            if lookup_type == PAIR_ADJUSTMENT_LOOKUP {
+                subtables.push(subtable);
+            } else if lookup_type == EXTENSION_LOOKUP && read_u16(font_data, subtable + 2)? == PAIR_ADJUSTMENT_LOOKUP {
+                subtables.push(subtable + read_u32(font_data, subtable + 4)? as usize);
+            }
+        }
+        Some(subtables)
+    }).collect()
+}
+
+/// Computes the kerning (in font units) between the given pair of glyphs from the given lookups (see find_kerning_lookups)
+fn read_gpos_kerning(font_data: &[u8], kerning_lookups: &[Vec<usize>], left: u16, right: u16) -> i32 {
+
+    // Only the first subtable of a lookup that contains the pair applies, but the adjustments of all lookups add up
+    kerning_lookups.iter().filter_map(|subtables| {
+        subtables.iter().find_map(|&subtable| read_pair_adjustment(font_data, subtable, left, right))
+    }).map(|adjustment| adjustment as i32).sum()
+}
+
+/// Reads the horizontal advance adjustment (in font units) of the left glyph from the pair adjustment subtable at the
+/// given offset in the font data. Returns None if the left glyph is not covered by the subtable (or the pair is not in a
+/// subtable of format 1), in which case the next subtable of the lookup should be tried.
+fn read_pair_adjustment(font_data: &[u8], subtable: usize, left: u16, right: u16) -> Option<i16> {
+    let format = read_u16(font_data, subtable)?;
+    let coverage_index = read_coverage_index(font_data, subtable + read_u16(font_data, subtable + 2)? as usize, left)?;
+    let value_format1 = read_u16(font_data, subtable + 4)?;
+    let value_format2 = read_u16(font_data, subtable + 6)?;
+
+    // Every value record consists of 1 u16 or i16 per bit of its value format
+    let value_size1 = 2 * value_format1.count_ones() as usize;
+    let value_size2 = 2 * value_format2.count_ones() as usize;
+    let value_record = match format {
+        1 => {
+            let pair_set = subtable + read_u16(font_data, subtable + 10 + 2 * coverage_index)? as usize;
+            let pair_count = read_u16(font_data, pair_set)? as usize;
+            let pair_size = 2 + value_size1 + value_size2;
+            let pair_index = (0..pair_count).find(|pair_index| read_u16(font_data, pair_set + 2 + pair_size * pair_index) == Some(right))?;
+            pair_set + 2 + pair_size * pair_index + 2
+        }, 2 => {
+            let class1 = read_class(font_data, subtable + read_u16(font_data, subtable + 8)? as usize, left)?;
+            let class2 = read_class(font_data, subtable + read_u16(font_data, subtable + 10)? as usize, right)?;
+            let class2_count = read_u16(font_data, subtable + 14)? as usize;
+            subtable + 16 + (class1 * class2_count + class2) * (value_size1 + value_size2)
+        }, _ => return None
+    };
+
+    if value_format1 & X_ADVANCE == 0 {
+        return Some(0);
+    }
+    let x_advance_offset = 2 * (value_format1 & (X_ADVANCE - 1)).count_ones() as usize;
+    read_i16(font_data, value_record + x_advance_offset)
+}
+
+/// Gets the index of the given glyph in the coverage table at the given offset in the font data, or None if the glyph is
+/// not covered
+fn read_coverage_index(font_data: &[u8], coverage: usize, glyph: u16) -> Option<usize> {
+    let count = read_u16(font_data, coverage + 2)? as usize;
+    match read_u16(font_data, coverage)? {
+        1 => (0..count).find(|index| read_u16(font_data, coverage + 4 + 2 * index) == Some(glyph)),
+        2 => (0..count).find_map(|index| {
+            let record = coverage + 4 + 6 * index;
+            let start = read_u16(font_data, record)?;
+            let end = read_u16(font_data, record + 2)?;
+            if glyph < start || glyph > end {
+                return None;
+            }
+            Some(read_u16(font_data, record + 4)? as usize + (glyph - start) as usize)
+        }),
+        _ => None
+    }
+}
+
+/// Gets the class of the given glyph in the class definition table at the given offset in the font data. Glyphs that are
+/// not in the table belong to class 0.
+fn read_class(font_data: &[u8], class_def: usize, glyph: u16) -> Option<usize> {
+    let class = match read_u16(font_data, class_def)? {
+        1 => {
+            let start = read_u16(font_data, class_def + 2)?;
+            let count = read_u16(font_data, class_def + 4)?;
+            if glyph >= start && glyph - start < count {
+                read_u16(font_data, class_def + 6 + 2 * (glyph - start) as usize)?
+            } else {
+                0
+            }
+        }, 2 => {
+            let count = read_u16(font_data, class_def + 2)? as usize;
+            (0..count).find_map(|index| {
+                let record = class_def + 4 + 6 * index;
+                let start = read_u16(font_data, record)?;
+                let end = read_u16(font_data, record + 2)?;
+                if glyph >= start && glyph <= end { read_u16(font_data, record + 4) } else { None }
+            }).unwrap_or(0)
+        }, _ => return None
+    };
+    Some(class as usize)
+}
+
+/// Finds the table with the given tag in the table directory of the given font data. Like FontVec::try_from_vec, this will
+/// use the first font of a font collection.
+fn find_table<'a>(font_data: &'a [u8], tag: &[u8; 4]) -> Option<&'a [u8]> {
+    font_data.get(find_table_range(font_data, tag)?)
+}
+
+/// Finds the range (in the given font data) of the table with the given tag, see find_table
+fn find_table_range(font_data: &[u8], tag: &[u8; 4]) -> Option<Range<usize>> {
+    let font_offset = match font_data.get(0..4)? {
+        b"ttcf" => read_u32(font_data, 12)? as usize,
+        _ => 0
+    };
+    let table_count = read_u16(font_data, font_offset + 4)? as usize;
+    (0..table_count).find_map(|index| {
+        let record = font_offset + 12 + 16 * index;
+        if font_data.get(record..record + 4)? != tag {
+            return None;
+        }
+        let table_offset = read_u32(font_data, record + 8)? as usize;
+        let table_length = read_u32(font_data, record + 12)? as usize;
+        let range = table_offset..table_offset.checked_add(table_length)?;
+        font_data.get(range.clone()).map(|_| range)
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use super::super::testing::TEST_FONT_DATA;
+
+    #[test]
+    fn decoration_metrics_are_read_from_the_font(){
+
+        // The test font has 2048 units per em, so this font size makes 1 pixel equal to 1 font unit
+        let rasterizer = TtfRasterizer::new(TEST_FONT_DATA.to_vec(), 2048, 0.0).expect("The test font should be valid");
+        let metrics = rasterizer.get_font_metrics();
+        assert_eq!(metrics.get_underline_position(), 40.0);
+        assert_eq!(metrics.get_underline_thickness(), 90.0);
+        assert_eq!(metrics.get_strikethrough_position(), 530.0);
+        assert_eq!(metrics.get_strikethrough_thickness(), 102.0);
+    }
+
+    #[test]
+    fn rasterized_characters_have_ink(){
+        let rasterizer = TtfRasterizer::new(TEST_FONT_DATA.to_vec(), 32, 0.05).expect("The test font should be valid");
+        let bitmap = rasterizer.rasterize('A').expect("The test font should have an A");
+        assert_eq!(bitmap.pixels.len(), (4 * bitmap.width * bitmap.height) as usize);
+        let pixels: Vec<&[u8]> = bitmap.pixels.chunks_exact(4).collect();
+        assert!(pixels.iter().any(|pixel| pixel[1] == 255), "The A should have a solid interior");
+        assert!(pixels.iter().any(|pixel| pixel[2] > 128), "The A should have a stroke");
+        assert!(pixels.iter().any(|pixel| pixel[0] == 255), "The A should have empty space around it");
+
+        let space = rasterizer.rasterize(' ').expect("The test font should have a space");
+        assert!(space.pixels.chunks_exact(4).all(|pixel| pixel == EMPTY_PIXEL));
+        assert!(space.metrics.get_advance() > 0.0);
+
+        // The test font doesn't have any Chinese characters
+        assert!(rasterizer.rasterize('\u{4e2d}').is_none());
+    }
+
+    /// Creates font data that only contains a GPOS table with 3 lookups of pair adjustments. The first lookup belongs to a
+    /// mark feature, so it must be ignored for kerning. The kern feature uses the second lookup (whose subtable of format 1
+    /// is behind an extension subtable) and the third lookup (with a subtable of format 2).
+    fn create_gpos_font_data() -> Vec<u8> {
+        let tag = |tag: &[u8; 2]| u16::from_be_bytes(*tag) as i32;
+        let words = [
+
+            // The table directory with the GPOS table at byte 28
+            0x0001, 0x0000, 1, 16, 0, 0,
+            tag(b"GP"), tag(b"OS"), 0, 0, 0, 28, 0, 182,
+
+            // The GPOS header with the feature list at 10 and the lookup list at 38 (relative to the GPOS table)
+            1, 0, 0, 10, 38,
+
+            // The feature list with the mark feature at 24 and the kern feature at 30, which uses lookup 2 and 1
+            2, tag(b"ma"), tag(b"rk"), 14, tag(b"ke"), tag(b"rn"), 20,
+            0, 1, 0,
+            0, 2, 2, 1,
+
+            // The lookup list, followed by the lookups at 46, 54 (with its extension subtable at 62) and 70
+            3, 8, 16, 32,
+            2, 0, 1, 76,
+            9, 0, 1, 8,
+            1, 2, 0, 16,
+            2, 0, 1, 52,
+
+            // The subtable of format 1 at 78 for glyphs 3 and 5, whose value records start with an x placement of 99
+            1, 14, 0x0005, 0, 2, 22, 36,
+            1, 2, 3, 5,
+            2, 4, 99, -50, 6, 99, -20,
+            1, 6, 99, -30,
+
+            // The subtable of format 2 at 122 for glyphs 3 to 5. Glyph 3 has class 1 and glyph 5 has class 2. On the right,
+            // glyphs 4 to 6 have class 1.
+            2, 28, 0x0004, 0, 38, 50, 3, 2,
+            0, 0, 0, -5, 0, -8,
+            2, 1, 3, 5, 0,
+            1, 3, 3, 1, 0, 2,
+            2, 1, 4, 6, 1
+        ];
+        words.iter().flat_map(|word| (*word as u16).to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn kerning_is_read_from_the_kern_feature_of_gpos(){
+        let font_data = create_gpos_font_data();
+        let lookups = find_kerning_lookups(&font_data).expect("The GPOS table should be valid");
+        assert_eq!(lookups.len(), 2);
+
+        let kerning = |left: u16, right: u16| read_gpos_kerning(&font_data, &lookups, left, right);
+        assert_eq!(kerning(3, 4), -55);
+        assert_eq!(kerning(3, 6), -25);
+        assert_eq!(kerning(5, 6), -38);
+
+        // Pairs that are only in one of the lookups
+        assert_eq!(kerning(5, 4), -8);
+        assert_eq!(kerning(3, 7), 0);
+        assert_eq!(kerning(4, 6), 0);
+        assert_eq!(kerning(7, 4), 0);
+    }
+
+    #[test]
+    fn kerning_is_read_from_the_kern_table(){
+        let rasterizer = TtfRasterizer::new(TEST_FONT_DATA.to_vec(), 2048, 0.0).expect("The test font should be valid");
+        assert!(rasterizer.kerning_lookups.is_empty());
+        assert!(rasterizer.get_kerning('A', 'V') < 0.0);
+        assert_eq!(rasterizer.get_kerning('H', 'H'), 0.0);
+    }
+
+    #[test]
+    fn missing_tables_are_ignored(){
+        assert_eq!(find_table(TEST_FONT_DATA, b"GPOS"), None);
+        assert_eq!(read_underline_metrics(&TEST_FONT_DATA[0..100]), None);
+        assert_eq!(read_strikethrough_metrics(&[]), None);
+    }
+}
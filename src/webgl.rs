@@ -0,0 +1,358 @@
+use web_sys::{
+    HtmlCanvasElement,
+    WebGlBuffer,
+    WebGlProgram,
+    WebGlRenderingContext,
+    WebGlRenderingContext as GL,
+    WebGlShader,
+    WebGlTexture,
+    WebGlUniformLocation
+};
+
+use js_sys::Uint16Array;
+
+use wasm_bindgen::JsCast;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::backend::*;
+use super::error::TextError;
+
+/// Keeps track of the webgl objects of a WebGlBackend. The index of an object in the objects vector is the value of its
+/// handle. Deleted objects will leave a None behind so that the handles of the other objects remain valid.
+pub(super) struct ObjectTable<T> {
+
+    objects: Vec<Option<T>>
+}
+
+impl<T> ObjectTable<T> {
+
+    pub fn new() -> ObjectTable<T> {
+        ObjectTable {
+            objects: Vec::new()
+        }
+    }
+
+    pub fn insert(&mut self, object: T) -> u32 {
+        for (index, slot) in self.objects.iter_mut().enumerate() {
+            if slot.is_none() {
+                *slot = Some(object);
+                return index as u32;
+            }
+        }
+        self.objects.push(Some(object));
+        (self.objects.len() - 1) as u32
+    }
+
+    pub fn get(&self, value: u32) -> &T {
+        self.objects[value as usize].as_ref().expect("Attempted to use a webgl object that has been deleted")
+    }
+
+    pub fn get_mut(&mut self, value: u32) -> &mut T {
+        self.objects[value as usize].as_mut().expect("Attempted to use a webgl object that has been deleted")
+    }
+
+    pub fn remove(&mut self, value: u32) -> Option<T> {
+        self.objects[value as usize].take()
+    }
+}
+
+pub(super) struct ProgramObjects {
+
+    pub program: WebGlProgram,
+    pub vertex_shader: WebGlShader,
+    pub fragment_shader: WebGlShader,
+
+    /// The handle values of the uniform locations that were obtained from this program, which will be removed from the
+    /// uniforms of the backend when this program is deleted
+    pub uniforms: Vec<u32>
+}
+
+/// Implements the methods of TextBackend that are the same for the WebGlBackend and the WebGl2Backend: everything except the
+/// methods that deal with vertex array objects and instancing. The backend must have the gl, textures, buffers, programs
+/// and uniforms fields, and the module that invokes this macro must import its webgl context type as GL.
+macro_rules! impl_common_webgl_methods {
+    () => {
+        fn create_texture(&self) -> Result<TextureHandle, TextError> {
+            let texture = self.gl.create_texture().ok_or_else(|| TextError::ObjectCreation("texture".to_string()))?;
+            Ok(TextureHandle::new(self.textures.borrow_mut().insert(texture)))
+        }
+
+        fn upload_texture(&self, texture: TextureHandle, width: u32, height: u32, pixels: &[u8]) -> Result<(), TextError> {
+            let gl = &self.gl;
+            let textures = self.textures.borrow();
+            gl.bind_texture(GL::TEXTURE_2D, Some(textures.get(texture.get_value())));
+            gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(GL::TEXTURE_2D, 0, GL::RGBA as i32,
+                width as i32, height as i32, 0, GL::RGBA, GL::UNSIGNED_BYTE, Some(pixels)).map_err(|error| TextError::TextureUpload(format!("{:?}", error)))?;
+            gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE as i32);
+            gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE as i32);
+            gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::LINEAR as i32);
+            gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MAG_FILTER, GL::LINEAR as i32);
+            Ok(())
+        }
+
+        fn bind_texture(&self, texture_unit: u32, texture: TextureHandle){
+            self.gl.active_texture(GL::TEXTURE0 + texture_unit);
+            self.gl.bind_texture(GL::TEXTURE_2D, Some(self.textures.borrow().get(texture.get_value())));
+        }
+
+        fn delete_texture(&self, texture: TextureHandle){
+            let maybe_texture = self.textures.borrow_mut().remove(texture.get_value());
+            self.gl.delete_texture(maybe_texture.as_ref());
+        }
+
+        fn get_max_texture_size(&self) -> u32 {
+
+            // Every device should support at least 2048, so that is a reasonable guess if the parameter can't be obtained
+            self.gl.get_parameter(GL::MAX_TEXTURE_SIZE).ok().and_then(|value| value.as_f64()).map_or(2048, |value| value as u32)
+        }
+
+        fn create_buffer(&self) -> Result<BufferHandle, TextError> {
+            let buffer = self.gl.create_buffer().ok_or_else(|| TextError::ObjectCreation("buffer".to_string()))?;
+            Ok(BufferHandle::new(self.buffers.borrow_mut().insert(buffer)))
+        }
+
+        fn upload_buffer(&self, buffer: BufferHandle, data: &[u8]){
+            let gl = &self.gl;
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(self.buffers.borrow().get(buffer.get_value())));
+            gl.buffer_data_with_u8_array(GL::ARRAY_BUFFER, data, GL::STATIC_DRAW);
+        }
+
+        fn allocate_buffer(&self, buffer: BufferHandle, byte_count: usize, usage: BufferUsage){
+            let gl = &self.gl;
+            let usage = match usage {
+                BufferUsage::Static => GL::STATIC_DRAW,
+                BufferUsage::Dynamic => GL::DYNAMIC_DRAW
+            };
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(self.buffers.borrow().get(buffer.get_value())));
+            gl.buffer_data_with_i32(GL::ARRAY_BUFFER, byte_count as i32, usage);
+        }
+
+        fn update_buffer(&self, buffer: BufferHandle, offset: usize, data: &[u8]){
+            let gl = &self.gl;
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(self.buffers.borrow().get(buffer.get_value())));
+            gl.buffer_sub_data_with_i32_and_u8_array(GL::ARRAY_BUFFER, offset as i32, data);
+        }
+
+        fn delete_buffer(&self, buffer: BufferHandle){
+            let maybe_buffer = self.buffers.borrow_mut().remove(buffer.get_value());
+            self.gl.delete_buffer(maybe_buffer.as_ref());
+        }
+
+        fn set_vertex_attribute(&self, buffer: BufferHandle, location: i32, num_components: i32, attribute_type: AttributeType, stride: i32, offset: i32){
+            let gl = &self.gl;
+            let (data_type, normalized) = match attribute_type {
+                AttributeType::Float => (GL::FLOAT, false),
+                AttributeType::NormalizedUnsignedShort => (GL::UNSIGNED_SHORT, true)
+            };
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(self.buffers.borrow().get(buffer.get_value())));
+            gl.vertex_attrib_pointer_with_i32(location as u32, num_components, data_type, normalized, stride, offset);
+            gl.enable_vertex_attrib_array(location as u32);
+        }
+
+        fn disable_vertex_attribute(&self, location: i32){
+            self.gl.disable_vertex_attrib_array(location as u32);
+        }
+
+        fn upload_index_buffer(&self, buffer: BufferHandle, indices: &[u16]){
+            let gl = &self.gl;
+            gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(self.buffers.borrow().get(buffer.get_value())));
+
+            // The view must not outlive the indices, and nothing may allocate while it exists
+            unsafe {
+                let js_array = Uint16Array::view(indices);
+                gl.buffer_data_with_array_buffer_view(GL::ELEMENT_ARRAY_BUFFER, &js_array, GL::STATIC_DRAW);
+            }
+        }
+
+        fn bind_index_buffer(&self, buffer: BufferHandle){
+            self.gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(self.buffers.borrow().get(buffer.get_value())));
+        }
+
+        fn draw_indexed_triangles(&self, first: i32, count: i32){
+            let u16_size = 2;
+            self.gl.draw_elements_with_i32(GL::TRIANGLES, count, GL::UNSIGNED_SHORT, u16_size * first);
+        }
+
+        fn create_program(&self, vertex_source: &str, fragment_source: &str) -> Result<ProgramHandle, TextError> {
+            let gl = &self.gl;
+            let compile_shader = |shader_type: u32, source: &str, description: &str| {
+                let shader = gl.create_shader(shader_type).ok_or_else(|| TextError::ObjectCreation(format!("{} shader", description)))?;
+                gl.shader_source(&shader, source);
+                gl.compile_shader(&shader);
+                if !gl.get_shader_parameter(&shader, GL::COMPILE_STATUS).as_bool().unwrap_or(false) {
+                    let info_log = gl.get_shader_info_log(&shader).unwrap_or_default();
+                    gl.delete_shader(Some(&shader));
+                    return Err(TextError::ShaderCompilation {
+                        shader_type: description.to_string(),
+                        info_log
+                    });
+                }
+                Ok(shader)
+            };
+
+            let vertex_shader = compile_shader(GL::VERTEX_SHADER, vertex_source, "vertex")?;
+            let fragment_shader = match compile_shader(GL::FRAGMENT_SHADER, fragment_source, "fragment") {
+                Ok(shader) => shader,
+                Err(error) => {
+                    gl.delete_shader(Some(&vertex_shader));
+                    return Err(error);
+                }
+            };
+
+            let delete_shaders = || {
+                gl.delete_shader(Some(&vertex_shader));
+                gl.delete_shader(Some(&fragment_shader));
+            };
+
+            let program = match gl.create_program() {
+                Some(program) => program,
+                None => {
+                    delete_shaders();
+                    return Err(TextError::ObjectCreation("shader program".to_string()));
+                }
+            };
+
+            gl.attach_shader(&program, &vertex_shader);
+            gl.attach_shader(&program, &fragment_shader);
+            gl.link_program(&program);
+
+            if !gl.get_program_parameter(&program, GL::LINK_STATUS).as_bool().unwrap_or(false) {
+                let info_log = gl.get_program_info_log(&program).unwrap_or_default();
+                gl.delete_program(Some(&program));
+                delete_shaders();
+                return Err(TextError::ProgramLink { info_log });
+            }
+
+            Ok(ProgramHandle::new(self.programs.borrow_mut().insert(ProgramObjects {
+                program,
+                vertex_shader,
+                fragment_shader,
+                uniforms: Vec::new()
+            })))
+        }
+
+        fn use_program(&self, program: ProgramHandle){
+            self.gl.use_program(Some(&self.programs.borrow().get(program.get_value()).program));
+        }
+
+        fn delete_program(&self, program: ProgramHandle){
+            if let Some(objects) = self.programs.borrow_mut().remove(program.get_value()) {
+                self.gl.delete_program(Some(&objects.program));
+                self.gl.delete_shader(Some(&objects.vertex_shader));
+                self.gl.delete_shader(Some(&objects.fragment_shader));
+                let mut uniforms = self.uniforms.borrow_mut();
+                for uniform in objects.uniforms {
+                    uniforms.remove(uniform);
+                }
+            }
+        }
+
+        fn get_attrib_location(&self, program: ProgramHandle, name: &str) -> i32 {
+            self.gl.get_attrib_location(&self.programs.borrow().get(program.get_value()).program, name)
+        }
+
+        fn get_uniform_location(&self, program: ProgramHandle, name: &str) -> Result<UniformHandle, TextError> {
+            let mut programs = self.programs.borrow_mut();
+            let objects = programs.get_mut(program.get_value());
+            let location = self.gl.get_uniform_location(&objects.program, name).ok_or_else(|| TextError::MissingUniform(name.to_string()))?;
+            let uniform = self.uniforms.borrow_mut().insert(location);
+            objects.uniforms.push(uniform);
+            Ok(UniformHandle::new(uniform))
+        }
+
+        fn set_uniform_1i(&self, uniform: UniformHandle, value: i32){
+            self.gl.uniform1i(Some(self.uniforms.borrow().get(uniform.get_value())), value);
+        }
+
+        fn set_uniform_1f(&self, uniform: UniformHandle, value: f32){
+            self.gl.uniform1f(Some(self.uniforms.borrow().get(uniform.get_value())), value);
+        }
+
+        fn set_uniform_2f(&self, uniform: UniformHandle, x: f32, y: f32){
+            self.gl.uniform2f(Some(self.uniforms.borrow().get(uniform.get_value())), x, y);
+        }
+
+        fn set_uniform_4f(&self, uniform: UniformHandle, x: f32, y: f32, z: f32, w: f32){
+            self.gl.uniform4f(Some(self.uniforms.borrow().get(uniform.get_value())), x, y, z, w);
+        }
+
+        fn draw_triangles(&self, first: i32, count: i32){
+            self.gl.draw_arrays(GL::TRIANGLES, first, count);
+        }
+
+        fn enable_blending(&self){
+            self.gl.enable(GL::BLEND);
+            self.gl.blend_func_separate(GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA, GL::ONE, GL::ONE_MINUS_SRC_ALPHA);
+        }
+
+        fn get_surface_size(&self) -> Option<(u32, u32)> {
+            let maybe_bound_canvas = self.gl.canvas();
+
+            // If there is no canvas bound to it anymore, don't bother rendering
+            maybe_bound_canvas.map(|bound_canvas| {
+                let bound_canvas = bound_canvas.dyn_into::<HtmlCanvasElement>().expect("The bound webgl canvas should be a canvas element");
+                (bound_canvas.width(), bound_canvas.height())
+            })
+        }
+    };
+}
+
+#[cfg(feature = "webgl2")]
+pub(super) use impl_common_webgl_methods;
+
+/// The TextBackend implementation that renders with a WebGlRenderingContext. This is the backend that will be used by
+/// the from_gl, from_rc and from_canvas methods of TextRenderer.
+pub struct WebGlBackend {
+
+    gl: Rc<WebGlRenderingContext>,
+
+    textures: RefCell<ObjectTable<WebGlTexture>>,
+    buffers: RefCell<ObjectTable<WebGlBuffer>>,
+    programs: RefCell<ObjectTable<ProgramObjects>>,
+    uniforms: RefCell<ObjectTable<WebGlUniformLocation>>
+}
+
+impl WebGlBackend {
+
+    pub fn new(gl: Rc<WebGlRenderingContext>) -> WebGlBackend {
+        WebGlBackend {
+            gl,
+
+            textures: RefCell::new(ObjectTable::new()),
+            buffers: RefCell::new(ObjectTable::new()),
+            programs: RefCell::new(ObjectTable::new()),
+            uniforms: RefCell::new(ObjectTable::new())
+        }
+    }
+
+    /// Gets the webgl context this backend is rendering with
+    pub fn get_gl(&self) -> &Rc<WebGlRenderingContext> {
+        &self.gl
+    }
+}
+
+impl TextBackend for WebGlBackend {
+
+    impl_common_webgl_methods!();
+
+    // WebGl 1 doesn't support vertex array objects or instancing without extensions, so the methods below will never be
+    // called on this backend
+
+    fn supports_instancing(&self) -> bool {
+        false
+    }
+
+    fn create_vertex_array(&self) -> Result<VertexArrayHandle, TextError> {
+        Err(TextError::ObjectCreation("vertex array (not supported by WebGl 1)".to_string()))
+    }
+
+    fn bind_vertex_array(&self, _vertex_array: Option<VertexArrayHandle>){}
+
+    fn delete_vertex_array(&self, _vertex_array: VertexArrayHandle){}
+
+    fn set_attribute_divisor(&self, _location: i32, _divisor: u32){}
+
+    fn draw_instanced_triangles(&self, _first: i32, _count: i32, _instance_count: i32){}
+}
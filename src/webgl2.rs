@@ -0,0 +1,93 @@
+use web_sys::{
+    HtmlCanvasElement,
+    WebGlBuffer,
+    WebGl2RenderingContext,
+    WebGl2RenderingContext as GL,
+    WebGlTexture,
+    WebGlUniformLocation,
+    WebGlVertexArrayObject
+};
+
+use js_sys::Uint16Array;
+
+use wasm_bindgen::JsCast;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::backend::*;
+use super::error::TextError;
+use super::webgl::{
+    ObjectTable,
+    ProgramObjects,
+    impl_common_webgl_methods
+};
+
+/// The TextBackend implementation that renders with a WebGl2RenderingContext. This is the backend that will be used by
+/// the from_gl2, from_rc_gl2 and from_canvas_gl2 methods of TextRenderer, which are only available when the webgl2 feature
+/// of this crate is enabled.
+///
+/// Unlike the WebGlBackend, this backend supports instancing, so the TextModel's will store only 1 instance (8 floats)
+/// per character rather than 6 vertices (24 floats), and will keep their vertex attributes in vertex array objects.
+pub struct WebGl2Backend {
+
+    gl: Rc<WebGl2RenderingContext>,
+
+    textures: RefCell<ObjectTable<WebGlTexture>>,
+    buffers: RefCell<ObjectTable<WebGlBuffer>>,
+    programs: RefCell<ObjectTable<ProgramObjects>>,
+    vertex_arrays: RefCell<ObjectTable<WebGlVertexArrayObject>>,
+    uniforms: RefCell<ObjectTable<WebGlUniformLocation>>
+}
+
+impl WebGl2Backend {
+
+    pub fn new(gl: Rc<WebGl2RenderingContext>) -> WebGl2Backend {
+        WebGl2Backend {
+            gl,
+
+            textures: RefCell::new(ObjectTable::new()),
+            buffers: RefCell::new(ObjectTable::new()),
+            programs: RefCell::new(ObjectTable::new()),
+            vertex_arrays: RefCell::new(ObjectTable::new()),
+            uniforms: RefCell::new(ObjectTable::new())
+        }
+    }
+
+    /// Gets the webgl2 context this backend is rendering with
+    pub fn get_gl(&self) -> &Rc<WebGl2RenderingContext> {
+        &self.gl
+    }
+}
+
+impl TextBackend for WebGl2Backend {
+
+    impl_common_webgl_methods!();
+
+    fn supports_instancing(&self) -> bool {
+        true
+    }
+
+    fn create_vertex_array(&self) -> Result<VertexArrayHandle, TextError> {
+        let vertex_array = self.gl.create_vertex_array().ok_or_else(|| TextError::ObjectCreation("vertex array".to_string()))?;
+        Ok(VertexArrayHandle::new(self.vertex_arrays.borrow_mut().insert(vertex_array)))
+    }
+
+    fn bind_vertex_array(&self, vertex_array: Option<VertexArrayHandle>){
+        let vertex_arrays = self.vertex_arrays.borrow();
+        self.gl.bind_vertex_array(vertex_array.map(|vertex_array| vertex_arrays.get(vertex_array.get_value())));
+    }
+
+    fn delete_vertex_array(&self, vertex_array: VertexArrayHandle){
+        let maybe_vertex_array = self.vertex_arrays.borrow_mut().remove(vertex_array.get_value());
+        self.gl.delete_vertex_array(maybe_vertex_array.as_ref());
+    }
+
+    fn set_attribute_divisor(&self, location: i32, divisor: u32){
+        self.gl.vertex_attrib_divisor(location as u32, divisor);
+    }
+
+    fn draw_instanced_triangles(&self, first: i32, count: i32, instance_count: i32){
+        self.gl.draw_arrays_instanced(GL::TRIANGLES, first, count, instance_count);
+    }
+}